@@ -0,0 +1,163 @@
+// Convert sprites from assets/*.bmp into palettised RLE data placed in flash.
+//
+// Supported input: uncompressed 24-bit or 32-bit BMP. Pure magenta (ff00ff)
+// pixels are treated as transparent. PNG images can be converted to BMP
+// using tools/png2bmp.py script.
+
+use std::env;
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::path::Path;
+
+const ASSETS: &str = "assets";
+const KEY: (u8, u8, u8) = (0xff, 0x00, 0xff);
+
+struct Image {
+    width: u32,
+    height: u32,
+    // top-down rows of (r, g, b) pixels
+    pixels: Vec<(u8, u8, u8)>,
+}
+
+fn le16(b: &[u8], off: usize) -> u32 {
+    u16::from_le_bytes([b[off], b[off + 1]]) as u32
+}
+
+fn le32(b: &[u8], off: usize) -> u32 {
+    u32::from_le_bytes([b[off], b[off + 1], b[off + 2], b[off + 3]])
+}
+
+fn parse_bmp(name: &str, b: &[u8]) -> Image {
+    if b.len() < 54 || &b[0..2] != b"BM" {
+        panic!("{}: not a BMP file", name);
+    }
+
+    let offset = le32(b, 10) as usize;
+    let width = le32(b, 18) as i32;
+    let height = le32(b, 22) as i32;
+    let bpp = le16(b, 28);
+    let compression = le32(b, 30);
+
+    if (bpp != 24 && bpp != 32) || (compression != 0 && compression != 3) {
+        panic!("{}: only uncompressed 24/32 bpp BMP is supported", name);
+    }
+
+    let w = width.unsigned_abs();
+    let h = height.unsigned_abs();
+    let bytes = (bpp / 8) as usize;
+    let stride = (w as usize * bytes + 3) & !3;
+    let mut pixels = Vec::with_capacity((w * h) as usize);
+
+    for y in 0..h as usize {
+        // positive height means bottom-up rows
+        let row = if height > 0 { h as usize - 1 - y } else { y };
+        let base = offset + row * stride;
+
+        for x in 0..w as usize {
+            let p = base + x * bytes;
+            pixels.push((b[p + 2], b[p + 1], b[p]));
+        }
+    }
+
+    Image {
+        width: w,
+        height: h,
+        pixels,
+    }
+}
+
+fn rgb565(c: (u8, u8, u8)) -> u16 {
+    ((c.0 as u16 >> 3) << 11) | ((c.1 as u16 >> 2) << 5) | (c.2 as u16 >> 3)
+}
+
+fn convert(name: &str, img: &Image, out: &mut String) {
+    let mut palette: Vec<u16> = Vec::new();
+    let mut indices: Vec<u8> = Vec::with_capacity(img.pixels.len());
+    let mut key = None;
+
+    for &p in img.pixels.iter() {
+        let color = if p == KEY {
+            if key.is_none() {
+                key = Some(palette.len() as u8);
+                palette.push(rgb565(p));
+            }
+            key.unwrap() as usize
+        } else {
+            let c = rgb565(p);
+            match palette
+                .iter()
+                .enumerate()
+                .position(|(i, &q)| q == c && Some(i as u8) != key)
+            {
+                Some(i) => i,
+                None => {
+                    palette.push(c);
+                    palette.len() - 1
+                }
+            }
+        };
+
+        if palette.len() > 256 {
+            panic!("{}: too many colors, max 256", name);
+        }
+
+        indices.push(color as u8);
+    }
+
+    // runs of (count, index) pairs, runs never cross row boundaries
+    let mut data: Vec<u8> = Vec::new();
+
+    for row in indices.chunks(img.width as usize) {
+        let mut i = 0;
+
+        while i < row.len() {
+            let mut n = 1;
+
+            while i + n < row.len() && row[i + n] == row[i] && n < 255 {
+                n += 1;
+            }
+
+            data.push(n as u8);
+            data.push(row[i]);
+            i += n;
+        }
+    }
+
+    writeln!(out, "pub const {}: Sprite = Sprite {{", name).unwrap();
+    writeln!(out, "    width: {},", img.width).unwrap();
+    writeln!(out, "    height: {},", img.height).unwrap();
+    writeln!(out, "    palette: &{:?},", palette).unwrap();
+    writeln!(out, "    key: {:?},", key).unwrap();
+    writeln!(out, "    data: &{:?},", data).unwrap();
+    writeln!(out, "}};\n").unwrap();
+}
+
+fn main() {
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let mut out = String::new();
+    let mut files: Vec<_> = fs::read_dir(ASSETS)
+        .unwrap()
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().and_then(|e| e.to_str()) == Some("bmp"))
+        .collect();
+
+    files.sort();
+
+    println!("cargo:rerun-if-changed={}", ASSETS);
+
+    for path in files.iter() {
+        let name = path
+            .file_stem()
+            .unwrap()
+            .to_string_lossy()
+            .to_uppercase()
+            .replace(|c: char| !c.is_ascii_alphanumeric(), "_");
+        let img = parse_bmp(&name, &fs::read(path).unwrap());
+
+        println!("cargo:rerun-if-changed={}", path.display());
+        convert(&name, &img, &mut out);
+    }
+
+    fs::write(Path::new(&out_dir).join("sprites.rs"), out).unwrap();
+}
@@ -0,0 +1,182 @@
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use cortex_m as cm;
+use hal::adc::Adc;
+use hal::gpio::gpioa::PA0;
+use hal::gpio::gpiob::{PB12, PB13};
+use hal::gpio::{Analog, Input, PullUp};
+use hal::prelude::*;
+use hal::stm32::TIM2;
+use hal::timer::CountDownTimer;
+use hal::timer::Event;
+use hal::timer::Timer;
+use panic_rtt_target as _;
+use rtt_target::{rprintln, rtt_init_print};
+use stm32f1xx_hal as hal;
+
+use embedded_hal::digital::v2::InputPin;
+
+use dso138_tests::hw::capture::{Buffer, Capture, SampleRate, BUF_LEN};
+
+use rtic::app;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Stats {
+    buffers: u32,
+    min: u16,
+    max: u16,
+    sum: u32,
+}
+
+const STATS: Stats = Stats {
+    buffers: 0,
+    min: u16::MAX,
+    max: 0,
+    sum: 0,
+};
+
+#[app(device = stm32f1xx_hal::stm32, peripherals = true)]
+const APP: () = {
+    struct Resources {
+        // early resources
+        #[init(false)]
+        cb1: bool,
+        #[init(false)]
+        cb2: bool,
+        #[init(STATS)]
+        stats: Stats,
+
+        // late resources
+        button1: PB12<Input<PullUp>>,
+        button2: PB13<Input<PullUp>>,
+        tmr: CountDownTimer<TIM2>,
+        capture: Capture<PA0<Analog>>,
+    }
+
+    #[init]
+    fn init(cx: init::Context) -> init::LateResources {
+        static mut BUF: Buffer = [[0; BUF_LEN]; 2];
+
+        rtt_init_print!();
+
+        let mut rcc = cx.device.RCC.constrain();
+        let mut flash = cx.device.FLASH.constrain();
+        let clocks = rcc
+            .cfgr
+            .use_hse(8.mhz())
+            .sysclk(72.mhz())
+            .pclk1(32.mhz())
+            .adcclk(12.mhz())
+            .freeze(&mut flash.acr);
+
+        let mut tmr =
+            Timer::tim2(cx.device.TIM2, &clocks, &mut rcc.apb1).start_count_down(100.hz());
+        tmr.listen(Event::Update);
+
+        /* buttons */
+
+        let mut gpiob = cx.device.GPIOB.split(&mut rcc.apb2);
+        let button1 = gpiob.pb12.into_pull_up_input(&mut gpiob.crh);
+        let button2 = gpiob.pb13.into_pull_up_input(&mut gpiob.crh);
+
+        /* capture */
+
+        let mut gpioa = cx.device.GPIOA.split(&mut rcc.apb2);
+        let probe = gpioa.pa0.into_analog(&mut gpioa.crl);
+        let adc = Adc::adc1(cx.device.ADC1, &mut rcc.apb2, clocks);
+        let dma = cx.device.DMA1.split(&mut rcc.ahb);
+        let ctmr = Timer::tim3(cx.device.TIM3, &clocks, &mut rcc.apb1);
+
+        let capture = Capture::new(adc, probe, dma.1, ctmr, &clocks, BUF, SampleRate::KHz10);
+
+        rprintln!("capture: {} Hz", capture.get_rate().hz());
+
+        /* init late resources */
+        init::LateResources {
+            button1,
+            button2,
+            tmr,
+            capture,
+        }
+    }
+
+    #[idle]
+    fn idle(_: idle::Context) -> ! {
+        loop {
+            cm::asm::nop();
+        }
+    }
+
+    #[task(binds = DMA1_CHANNEL1, priority = 2, resources = [capture, stats])]
+    fn dma1_channel1(cx: dma1_channel1::Context) {
+        let capture = cx.resources.capture;
+        let stats = cx.resources.stats;
+
+        let res = capture.read(|samples| {
+            for s in samples {
+                stats.min = stats.min.min(*s);
+                stats.max = stats.max.max(*s);
+                stats.sum += *s as u32;
+            }
+        });
+
+        if res.is_err() {
+            return;
+        }
+
+        stats.buffers += 1;
+
+        // report once per second of samples
+        if stats.buffers * BUF_LEN as u32 >= capture.get_rate().hz() {
+            rprintln!(
+                "rate {} Hz: min {} max {} mean {} overruns {}",
+                capture.get_rate().hz(),
+                stats.min,
+                stats.max,
+                stats.sum / (stats.buffers * BUF_LEN as u32),
+                capture.get_overruns()
+            );
+
+            *stats = STATS;
+        }
+    }
+
+    #[task(binds = TIM2, resources = [tmr, button1, cb1, button2, cb2, capture, stats])]
+    fn tim2(mut cx: tim2::Context) {
+        let mut rate = None;
+
+        if cx.resources.button1.is_low().unwrap() {
+            if !*cx.resources.cb1 {
+                *cx.resources.cb1 = true;
+                rate = Some(false);
+            }
+        } else if *cx.resources.cb1 {
+            *cx.resources.cb1 = false;
+        }
+
+        if cx.resources.button2.is_low().unwrap() {
+            if !*cx.resources.cb2 {
+                *cx.resources.cb2 = true;
+                rate = Some(true);
+            }
+        } else if *cx.resources.cb2 {
+            *cx.resources.cb2 = false;
+        }
+
+        if let Some(faster) = rate {
+            let hz = cx.resources.capture.lock(|capture| {
+                let rate = capture.get_rate();
+                capture.set_rate(if faster { rate.faster() } else { rate.slower() });
+                capture.get_rate().hz()
+            });
+
+            cx.resources.stats.lock(|stats| *stats = STATS);
+
+            rprintln!("capture: {} Hz", hz);
+        }
+
+        cx.resources.tmr.clear_update_interrupt_flag();
+    }
+};
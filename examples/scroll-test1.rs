@@ -0,0 +1,126 @@
+#![no_main]
+#![no_std]
+
+use cortex_m as cm;
+use cortex_m_rt as rt;
+use display_interface_parallel_gpio::PGPIO8BitInterface;
+use dso138_tests::gfx::scroll::ScrollRegion;
+use dso138_tests::gfx::viewport::Rotation;
+use embedded_graphics::fonts::{Font6x8, Text};
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::style::{PrimitiveStyle, TextStyleBuilder};
+use embedded_hal::digital::v2::OutputPin;
+use hal::delay::Delay;
+use hal::prelude::*;
+use ili9341::Ili9341;
+use panic_rtt_target as _;
+use rt::entry;
+use rtt_target::{rprintln, rtt_init_print};
+use stm32f1xx_hal as hal;
+
+// fixed band on top of the screen
+const TOP: u16 = 20;
+
+#[entry]
+fn main() -> ! {
+    rtt_init_print!();
+
+    let dp = hal::stm32::Peripherals::take().unwrap();
+    let cp = cm::Peripherals::take().unwrap();
+
+    let mut rcc = dp.RCC.constrain();
+    let mut flash = dp.FLASH.constrain();
+    let mut afio = dp.AFIO.constrain(&mut rcc.apb2);
+
+    let clocks = rcc
+        .cfgr
+        .use_hse(8.mhz())
+        .sysclk(72.mhz())
+        .pclk1(32.mhz())
+        .freeze(&mut flash.acr);
+
+    let mut delay = Delay::new(cp.SYST, clocks);
+
+    let mut gpioa = dp.GPIOA.split(&mut rcc.apb2);
+    let mut gpiob = dp.GPIOB.split(&mut rcc.apb2);
+    let mut gpioc = dp.GPIOC.split(&mut rcc.apb2);
+
+    let (pa15, pb3, pb4) = afio.mapr.disable_jtag(gpioa.pa15, gpiob.pb3, gpiob.pb4);
+
+    let mut led = pa15.into_push_pull_output(&mut gpioa.crh);
+
+    let p0 = gpiob.pb0.into_push_pull_output(&mut gpiob.crl);
+    let p1 = gpiob.pb1.into_push_pull_output(&mut gpiob.crl);
+    let p2 = gpiob.pb2.into_push_pull_output(&mut gpiob.crl);
+    let p3 = pb3.into_push_pull_output(&mut gpiob.crl);
+    let p4 = pb4.into_push_pull_output(&mut gpiob.crl);
+    let p5 = gpiob.pb5.into_push_pull_output(&mut gpiob.crl);
+    let p6 = gpiob.pb6.into_push_pull_output(&mut gpiob.crl);
+    let p7 = gpiob.pb7.into_push_pull_output(&mut gpiob.crl);
+
+    let mut ncs = gpioc.pc13.into_push_pull_output(&mut gpioc.crh);
+    let mut nrd = gpiob.pb10.into_push_pull_output(&mut gpiob.crh);
+
+    let nreset = gpiob.pb11.into_push_pull_output(&mut gpiob.crh);
+    let nwr = gpioc.pc15.into_push_pull_output(&mut gpioc.crh);
+    let rs = gpioc.pc14.into_push_pull_output(&mut gpioc.crh);
+
+    ncs.set_low().unwrap();
+    nrd.set_high().unwrap();
+
+    let pio8bit = PGPIO8BitInterface::new(p0, p1, p2, p3, p4, p5, p6, p7, rs, nwr);
+    let mut display = Ili9341::new(pio8bit, nreset, &mut delay).unwrap();
+    let rot = Rotation::Portrait;
+    let w = rot.size().width as i32;
+    let h = rot.size().height as i32;
+
+    display.set_orientation(rot.orientation()).unwrap();
+
+    Rectangle::new(Point::new(0, 0), Point::new(w, h))
+        .into_styled(PrimitiveStyle::with_fill(Rgb565::BLACK))
+        .draw(&mut display)
+        .unwrap();
+
+    let style = TextStyleBuilder::new(Font6x8)
+        .text_color(Rgb565::WHITE)
+        .background_color(Rgb565::BLACK)
+        .build();
+
+    Text::new("hardware scroll test", Point::new(60, 6))
+        .into_styled(style)
+        .draw(&mut display)
+        .unwrap();
+
+    let mut region = ScrollRegion::new(&mut display, rot, TOP, 0).unwrap();
+    let mut x: i32 = w / 2;
+    let mut dx: i32 = 3;
+
+    // strip chart: scroll by one line and draw a new trace point in the freed line
+    loop {
+        region.scroll(&mut display, 1).unwrap();
+
+        let y = region.to_screen(region.row(region.lines() - 1));
+
+        Rectangle::new(Point::new(0, y), Point::new(w - 1, y))
+            .into_styled(PrimitiveStyle::with_fill(Rgb565::BLACK))
+            .draw(&mut display)
+            .unwrap();
+
+        Rectangle::new(Point::new(x - 1, y), Point::new(x + 1, y))
+            .into_styled(PrimitiveStyle::with_fill(Rgb565::GREEN))
+            .draw(&mut display)
+            .unwrap();
+
+        x += dx;
+        if x <= 10 || x >= w - 10 {
+            dx = -dx;
+        }
+
+        if region.offset() == 0 {
+            rprintln!("scroll wrapped");
+            led.toggle().unwrap();
+        }
+    }
+}
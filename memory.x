@@ -1,5 +1,6 @@
 MEMORY
 {
-	FLASH : ORIGIN = 0x08000000, LENGTH = 64K
+	/* last 4K (4 x 1K pages) are reserved for hw::eeprom */
+	FLASH : ORIGIN = 0x08000000, LENGTH = 60K
 	RAM : ORIGIN = 0x20000000, LENGTH = 20K
 }
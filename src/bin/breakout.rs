@@ -0,0 +1,787 @@
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use cortex_m as cm;
+use display_interface_parallel_gpio::PGPIO8BitInterface;
+use dso138_tests::game::breakout::{Breakout, Power, Rules, COLS, ROWS};
+use dso138_tests::game::state::{self, Hooks, Machine, State};
+use dso138_tests::gfx::hud::Hud;
+use dso138_tests::gfx::sprite::assets::{BALL, RACKET};
+use dso138_tests::gfx::viewport::{Rotation, Viewport};
+use dso138_tests::hw::delay_timer::DelayTimer;
+use dso138_tests::phys::balls::Balls;
+use dso138_tests::phys::bricks::Bricks;
+use dso138_tests::phys::particles::{Particle, ParticleColor};
+use dso138_tests::phys::racket::{Intent, Kinematics, Racket};
+use embedded_graphics::fonts::{Font12x16, Text};
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::style::{PrimitiveStyle, TextStyleBuilder};
+use embedded_hal::digital::v2::InputPin;
+use embedded_hal::digital::v2::OutputPin;
+use hal::gpio::gpioa::PA15;
+use hal::gpio::gpiob::{PB0, PB1, PB2, PB3, PB4, PB5, PB6, PB7};
+use hal::gpio::gpiob::{PB11, PB12, PB13, PB14, PB15};
+use hal::gpio::gpioc::{PC14, PC15};
+use hal::gpio::{Input, Output, PullUp, PushPull};
+use hal::prelude::*;
+use hal::stm32::TIM3;
+use hal::timer::CountDownTimer;
+use hal::timer::Event;
+use hal::timer::Timer;
+use ili9341::Ili9341;
+use panic_rtt_target as _;
+use rtic::app;
+use rtic::cyccnt::Instant;
+use rtic::cyccnt::U32Ext;
+use rtt_target::{rprintln, rtt_init_print};
+use stm32f1xx_hal as hal;
+
+type DisplayType = Ili9341<
+    PGPIO8BitInterface<
+        PB0<Output<PushPull>>,
+        PB1<Output<PushPull>>,
+        PB2<Output<PushPull>>,
+        PB3<Output<PushPull>>,
+        PB4<Output<PushPull>>,
+        PB5<Output<PushPull>>,
+        PB6<Output<PushPull>>,
+        PB7<Output<PushPull>>,
+        PC14<Output<PushPull>>,
+        PC15<Output<PushPull>>,
+    >,
+    PB11<Output<PushPull>>,
+>;
+
+/* cpu sysclk: 72 MHz (no external quartz) */
+
+const STEP_PERIOD: u32 = 72_0000; /* 10 msec */
+
+/* ball deflection at the racket edges */
+const SPIN: f32 = 1.0;
+
+/* fraction of racket velocity passed to the ball */
+const DRAG: f32 = 0.2;
+
+/* racket acceleration, max speed, friction per step and simulation time step */
+const KINEMATICS: Kinematics<f32> = Kinematics {
+    acc: 50.0,
+    vmax: 50.0,
+    friction: 0.05,
+    dt: 0.1,
+};
+
+const RULES: Rules = Rules {
+    lives: 3,
+    points: 1,
+    bonus: 10,
+    drop: 5,
+};
+
+const MAX_BALLS: usize = 3;
+const MAX_DROPS: usize = 4;
+
+/* ball radius and brick half-height, brick half-width is a tenth of the field width */
+const BALL_R: f32 = 5.0;
+const BRICK_HH: f32 = 5.0;
+
+/* bricks hit in one step: a ball touches at most 2 columns and 3 rows of bricks */
+const MAX_HITS: usize = MAX_BALLS * 2 * 3;
+
+/* racket half-width: normal and with the wide power-up */
+const HW: f32 = 15.0;
+const HW_WIDE: f32 = 25.0;
+
+/* wide power-up duration in steps */
+const WIDE_STEPS: u32 = 1500;
+
+// falling power-up capsule
+#[derive(Debug, Clone, Copy)]
+struct Drop {
+    p: Particle<f32>,
+    power: Power,
+}
+
+enum Outcome {
+    Rally,
+    // last ball missed
+    Lost,
+    // all bricks destroyed
+    Cleared,
+}
+
+#[app(device = stm32f1xx_hal::stm32, peripherals = true, monotonic = rtic::cyccnt::CYCCNT)]
+const APP: () = {
+    struct Resources {
+        // early resources
+        #[init(false)]
+        cb1: bool,
+        #[init(false)]
+        cb2: bool,
+        #[init(false)]
+        cb3: bool,
+        #[init(false)]
+        cb4: bool,
+        #[init(false)]
+        select: bool,
+        #[init(false)]
+        quit: bool,
+        #[init([None; MAX_DROPS])]
+        drops: [Option<Drop>; MAX_DROPS],
+        #[init(0)]
+        wide: u32,
+
+        // late resources
+        display: DisplayType,
+        button1: PB12<Input<PullUp>>,
+        button2: PB13<Input<PullUp>>,
+        button3: PB14<Input<PullUp>>,
+        button4: PB15<Input<PullUp>>,
+        led: PA15<Output<PushPull>>,
+        btmr: CountDownTimer<TIM3>,
+        balls: Balls<f32, MAX_BALLS>,
+        racket: Racket<f32>,
+        bricks: Bricks<f32, COLS, ROWS>,
+        hud: Hud<3>,
+        vp: Viewport<f32>,
+        logic: Breakout,
+        game: Machine,
+    }
+
+    #[init(schedule = [step_task])]
+    fn init(mut cx: init::Context) -> init::LateResources {
+        rtt_init_print!();
+
+        let mut rcc = cx.device.RCC.constrain();
+        let mut flash = cx.device.FLASH.constrain();
+        let mut afio = cx.device.AFIO.constrain(&mut rcc.apb2);
+
+        let clocks = rcc
+            .cfgr
+            .use_hse(8.mhz())
+            .sysclk(72.mhz())
+            .pclk1(32.mhz())
+            .freeze(&mut flash.acr);
+
+        /* enable monotonic timer */
+        cx.core.DCB.enable_trace();
+        cx.core.DWT.enable_cycle_counter();
+
+        let mut gpioa = cx.device.GPIOA.split(&mut rcc.apb2);
+        let mut gpiob = cx.device.GPIOB.split(&mut rcc.apb2);
+        let mut gpioc = cx.device.GPIOC.split(&mut rcc.apb2);
+
+        let (pa15, pb3, pb4) = afio.mapr.disable_jtag(gpioa.pa15, gpiob.pb3, gpiob.pb4);
+
+        let mut btmr =
+            Timer::tim3(cx.device.TIM3, &clocks, &mut rcc.apb1).start_count_down(5000.hz());
+        btmr.listen(Event::Update);
+
+        let dtmr = Timer::tim2(cx.device.TIM2, &clocks, &mut rcc.apb1)
+            .start_master(1000.khz(), hal::pac::tim2::cr2::MMS_A::RESET);
+
+        /* buttons */
+
+        let button1 = gpiob.pb12.into_pull_up_input(&mut gpiob.crh);
+        let button2 = gpiob.pb13.into_pull_up_input(&mut gpiob.crh);
+        let button3 = gpiob.pb14.into_pull_up_input(&mut gpiob.crh);
+        let button4 = gpiob.pb15.into_pull_up_input(&mut gpiob.crh);
+
+        /* led */
+
+        let led = pa15.into_push_pull_output(&mut gpioa.crh);
+
+        /* display */
+
+        let mut delay = DelayTimer::new(dtmr);
+
+        let p0 = gpiob.pb0.into_push_pull_output(&mut gpiob.crl);
+        let p1 = gpiob.pb1.into_push_pull_output(&mut gpiob.crl);
+        let p2 = gpiob.pb2.into_push_pull_output(&mut gpiob.crl);
+        let p3 = pb3.into_push_pull_output(&mut gpiob.crl);
+        let p4 = pb4.into_push_pull_output(&mut gpiob.crl);
+        let p5 = gpiob.pb5.into_push_pull_output(&mut gpiob.crl);
+        let p6 = gpiob.pb6.into_push_pull_output(&mut gpiob.crl);
+        let p7 = gpiob.pb7.into_push_pull_output(&mut gpiob.crl);
+
+        let mut ncs = gpioc.pc13.into_push_pull_output(&mut gpioc.crh);
+        let mut nrd = gpiob.pb10.into_push_pull_output(&mut gpiob.crh);
+
+        let nreset = gpiob.pb11.into_push_pull_output(&mut gpiob.crh);
+        let nwr = gpioc.pc15.into_push_pull_output(&mut gpioc.crh);
+        let rs = gpioc.pc14.into_push_pull_output(&mut gpioc.crh);
+
+        ncs.set_low().unwrap();
+        nrd.set_high().unwrap();
+
+        let pio8bit = PGPIO8BitInterface::new(p0, p1, p2, p3, p4, p5, p6, p7, rs, nwr);
+        let mut display = Ili9341::new(pio8bit, nreset, &mut delay).unwrap();
+
+        let rot = Rotation::PortraitFlipped;
+        let screen = rot.size();
+
+        display.set_orientation(rot.orientation()).unwrap();
+
+        /* score band on top of the screen, game field below it */
+
+        let mut hud = Hud::new(
+            0,
+            screen.width as i32,
+            [0, 100, 180],
+            Rgb565::WHITE,
+            Rgb565::BLUE,
+        );
+
+        let vp = Viewport::new(
+            rot,
+            Point::new(0, hud.bottom()),
+            Size::new(screen.width, screen.height - hud.bottom() as u32),
+            1.0,
+        );
+
+        /* game objects: brick grid spans the field width below the top gap */
+
+        let mut balls = Balls::new();
+        let mut racket = new_racket();
+        let mut logic = Breakout::new(RULES, 1);
+        let mut bricks = Bricks::new(
+            0.0,
+            vp.world_height() - 40.0,
+            vp.world_width() / COLS as f32 / 2.0,
+            BRICK_HH,
+        );
+
+        /* title screen */
+
+        let game = Machine::new();
+
+        game.start(&mut Scene {
+            display: &mut display,
+            balls: &mut balls,
+            racket: &mut racket,
+            bricks: &mut bricks,
+            drops: &mut [None; MAX_DROPS],
+            wide: &mut 0,
+            hud: &mut hud,
+            logic: &mut logic,
+            vp: &vp,
+        });
+
+        cx.schedule.step_task(Instant::now()).unwrap();
+
+        /* init late resources */
+        init::LateResources {
+            display,
+            button1,
+            button2,
+            button3,
+            button4,
+            led,
+            btmr,
+            balls,
+            racket,
+            bricks,
+            hud,
+            vp,
+            logic,
+            game,
+        }
+    }
+
+    #[idle]
+    fn idle(_: idle::Context) -> ! {
+        loop {
+            cm::asm::nop();
+        }
+    }
+
+    #[task(binds = TIM3, resources = [btmr, button1, cb1, button2, cb2, button3, cb3, button4, cb4, select, quit])]
+    fn tim3(cx: tim3::Context) {
+        *cx.resources.cb1 = cx.resources.button1.is_low().unwrap();
+        *cx.resources.cb4 = cx.resources.button4.is_low().unwrap();
+
+        if cx.resources.button2.is_low().unwrap() {
+            if !*cx.resources.cb2 {
+                *cx.resources.cb2 = true;
+                *cx.resources.select = true;
+            }
+        } else if *cx.resources.cb2 {
+            *cx.resources.cb2 = false;
+        }
+
+        if cx.resources.button3.is_low().unwrap() {
+            if !*cx.resources.cb3 {
+                *cx.resources.cb3 = true;
+                *cx.resources.quit = true;
+            }
+        } else if *cx.resources.cb3 {
+            *cx.resources.cb3 = false;
+        }
+
+        cx.resources.btmr.clear_update_interrupt_flag();
+    }
+
+    #[task(schedule = [step_task], resources = [display, balls, cb1, cb4, select, quit, racket, bricks, drops, wide, hud, logic, vp, game])]
+    fn step_task(cx: step_task::Context) {
+        let game = cx.resources.game;
+        let mut scene = Scene {
+            display: cx.resources.display,
+            balls: cx.resources.balls,
+            racket: cx.resources.racket,
+            bricks: cx.resources.bricks,
+            drops: cx.resources.drops,
+            wide: cx.resources.wide,
+            hud: cx.resources.hud,
+            logic: cx.resources.logic,
+            vp: cx.resources.vp,
+        };
+
+        let intent = match (*cx.resources.cb1, *cx.resources.cb4) {
+            (true, false) => Intent::Right,
+            (false, true) => Intent::Left,
+            (true, true) => Intent::Brake,
+            (false, false) => Intent::Idle,
+        };
+
+        if *cx.resources.select {
+            *cx.resources.select = false;
+            game.handle(state::Event::Select, &mut scene);
+        }
+
+        if *cx.resources.quit {
+            *cx.resources.quit = false;
+            game.handle(state::Event::Quit, &mut scene);
+        }
+
+        if game.state() == State::Playing {
+            match scene.play(intent) {
+                Outcome::Lost => {
+                    if scene.logic.on_miss() {
+                        game.handle(state::Event::Lost, &mut scene);
+                    } else {
+                        scene.serve();
+                    }
+                }
+                Outcome::Cleared => {
+                    scene.logic.next_level();
+                    rprintln!("level up: {}", scene.logic.get_level());
+                    scene.start_level();
+                }
+                Outcome::Rally => {}
+            }
+        }
+
+        cx.schedule
+            .step_task(cx.scheduled + STEP_PERIOD.cycles())
+            .unwrap();
+    }
+
+    // needed for RTIC timer queue and task management
+    extern "C" {
+        fn EXTI2();
+    }
+};
+
+// game objects and drawing for the state machine hooks
+struct Scene<'a> {
+    display: &'a mut DisplayType,
+    balls: &'a mut Balls<f32, MAX_BALLS>,
+    racket: &'a mut Racket<f32>,
+    bricks: &'a mut Bricks<f32, COLS, ROWS>,
+    drops: &'a mut [Option<Drop>; MAX_DROPS],
+    // steps left for the wide racket
+    wide: &'a mut u32,
+    hud: &'a mut Hud<3>,
+    logic: &'a mut Breakout,
+    vp: &'a Viewport<f32>,
+}
+
+impl<'a> Scene<'a> {
+    fn clear(&mut self) {
+        let screen = self.vp.rotation().size();
+
+        Rectangle::new(
+            Point::new(0, 0),
+            Point::new(screen.width as i32, screen.height as i32),
+        )
+        .into_styled(PrimitiveStyle::with_fill(Rgb565::BLACK))
+        .draw(self.display)
+        .unwrap();
+    }
+
+    // big text in the middle of the game field
+    fn banner(&mut self, text: &str, color: Rgb565) {
+        let style = TextStyleBuilder::new(Font12x16)
+            .text_color(color)
+            .background_color(Rgb565::BLACK)
+            .build();
+        let center = self
+            .vp
+            .to_screen(self.vp.world_width() / 2.0, self.vp.world_height() / 2.0);
+
+        Text::new(text, center - Point::new(6 * text.len() as i32, 8))
+            .into_styled(style)
+            .draw(self.display)
+            .unwrap();
+    }
+
+    fn draw_hud(&mut self) {
+        self.hud
+            .set(0, format_args!("SCORE: {}", self.logic.get_score()));
+        self.hud
+            .set(1, format_args!("LIVES: {}", self.logic.get_lives()));
+        self.hud
+            .set(2, format_args!("LEVEL: {}", self.logic.get_level()));
+        self.hud.draw(self.display).unwrap();
+    }
+
+    fn draw_objects(&mut self) {
+        draw_racket(self.display, self.vp, self.racket);
+
+        for b in self.balls.iter() {
+            BALL.draw(self.display, ball_square(self.vp, b).0).unwrap();
+        }
+    }
+
+    // new brick layout and a new ball
+    fn start_level(&mut self) {
+        let area = self.vp.area();
+
+        area.into_styled(PrimitiveStyle::with_fill(Rgb565::BLACK))
+            .draw(self.display)
+            .unwrap();
+
+        self.bricks.load(self.logic.layout());
+
+        for row in 0..ROWS {
+            for col in 0..COLS {
+                draw_brick(self.display, self.vp, self.bricks, row, col);
+            }
+        }
+
+        self.serve();
+    }
+
+    // remove balls and power-ups, put a new ball on the racket
+    fn serve(&mut self) {
+        let ground = PrimitiveStyle::with_fill(Rgb565::BLACK);
+        let speed = 1.0 + 0.2 * (self.logic.get_level() - 1) as f32;
+
+        for b in self.balls.iter() {
+            let (tl, br) = ball_square(self.vp, b);
+
+            Rectangle::new(tl, br)
+                .into_styled(ground)
+                .draw(self.display)
+                .unwrap();
+        }
+
+        for d in self.drops.iter_mut() {
+            if let Some(d) = d.take() {
+                let (tl, br) = ball_square(self.vp, &d.p);
+
+                Rectangle::new(tl, br)
+                    .into_styled(ground)
+                    .draw(self.display)
+                    .unwrap();
+            }
+        }
+
+        let (tl, br) = racket_square(self.vp, self.racket);
+
+        Rectangle::new(tl, br)
+            .into_styled(ground)
+            .draw(self.display)
+            .unwrap();
+
+        *self.wide = 0;
+        self.racket.set_hw(HW);
+        self.balls.clear();
+        self.balls.add(Particle::<f32>::new(
+            self.racket.get_cx(),
+            self.racket.get_cy() + 20.0,
+            10.0 * speed,
+            20.0 * speed,
+            BALL_R,
+            0.1,
+            ParticleColor::Blue,
+        ));
+
+        self.draw_objects();
+        self.draw_hud();
+    }
+
+    fn power_up(&mut self, power: Power) {
+        rprintln!("power-up: {:?}", power);
+
+        match power {
+            Power::Wide => {
+                *self.wide = WIDE_STEPS;
+                self.racket.set_hw(HW_WIDE);
+            }
+            Power::Multi => {
+                // mirrored copies of the balls in play
+                let mut copies = *self.balls;
+
+                for b in copies.iter_mut() {
+                    b.set_velocity(-b.get_vx(), b.get_vy());
+
+                    if self.balls.add(*b).is_none() {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    // one step of the game
+    fn play(&mut self, intent: Intent) -> Outcome {
+        let ground = PrimitiveStyle::with_fill(Rgb565::BLACK);
+        let vp = self.vp;
+        let (ww, wh) = (vp.world_width(), vp.world_height());
+        // power-ups are hidden while they fall through the brick grid
+        let grid_bottom = self.bricks.center(ROWS - 1, 0).1 - self.bricks.get_hh();
+
+        for b in self.balls.iter() {
+            let (tl, br) = ball_square(vp, b);
+
+            Rectangle::new(tl, br)
+                .into_styled(ground)
+                .draw(self.display)
+                .unwrap();
+        }
+
+        /* racket */
+
+        let old = racket_square(vp, self.racket);
+
+        if *self.wide > 0 {
+            *self.wide -= 1;
+
+            if *self.wide == 0 {
+                self.racket.set_hw(HW);
+            }
+        }
+
+        self.racket.step(intent);
+
+        let _racket_bounce = Racket::<f32>::bounce(self.racket, 0.0, ww);
+
+        /* power-ups */
+
+        for i in 0..MAX_DROPS {
+            let mut d = match self.drops[i] {
+                Some(d) => d,
+                None => continue,
+            };
+
+            if d.p.get_y() < grid_bottom {
+                let (tl, br) = ball_square(vp, &d.p);
+
+                Rectangle::new(tl, br)
+                    .into_styled(ground)
+                    .draw(self.display)
+                    .unwrap();
+            }
+
+            d.p.step();
+
+            let dx = d.p.get_x() - self.racket.get_cx();
+            let dy = d.p.get_y() - self.racket.get_cy();
+            let caught = dx < self.racket.get_hw() + d.p.get_r()
+                && dx > -self.racket.get_hw() - d.p.get_r()
+                && dy < self.racket.get_hh() + d.p.get_r()
+                && dy > -self.racket.get_hh() - d.p.get_r();
+
+            self.drops[i] = if caught || d.p.get_y() < 0.0 {
+                None
+            } else {
+                Some(d)
+            };
+
+            if caught {
+                self.power_up(d.power);
+            }
+        }
+
+        if racket_square(vp, self.racket) != old {
+            Rectangle::new(old.0, old.1)
+                .into_styled(ground)
+                .draw(self.display)
+                .unwrap();
+
+            draw_racket(self.display, vp, self.racket);
+        }
+
+        /* balls */
+
+        let mut hits = [(0, 0, 0); MAX_HITS];
+        let mut n = 0;
+
+        for b in self.balls.iter_mut() {
+            b.step();
+
+            let _ball_bounce = Particle::<f32>::bounce(b, 0.0, ww, f32::MIN, wh - b.get_r());
+            let _ball_return = Racket::<f32>::collide(self.racket, b, SPIN, DRAG);
+
+            self.bricks.collide(b, |row, col, hp| {
+                if n < hits.len() {
+                    hits[n] = (row, col, hp);
+                    n += 1;
+                }
+            });
+        }
+
+        for &(row, col, hp) in hits[..n].iter() {
+            draw_brick(self.display, vp, self.bricks, row, col);
+
+            if let Some(power) = self.logic.on_brick(hp) {
+                let (x, y) = self.bricks.center(row, col);
+                let color = match power {
+                    Power::Wide => ParticleColor::Green,
+                    Power::Multi => ParticleColor::Red,
+                };
+                let drop = Drop {
+                    p: Particle::<f32>::new(x, y, 0.0, -15.0, 3.0, 0.1, color),
+                    power,
+                };
+
+                if let Some(slot) = self.drops.iter_mut().find(|d| d.is_none()) {
+                    *slot = Some(drop);
+                }
+            }
+        }
+
+        if n > 0 {
+            self.draw_hud();
+        }
+
+        for i in 0..MAX_BALLS {
+            if let Some(b) = self.balls.get(i) {
+                if missed(b, self.racket) {
+                    self.balls.remove(i);
+                }
+            }
+        }
+
+        /* draw moving objects */
+
+        for b in self.balls.iter() {
+            BALL.draw(self.display, ball_square(vp, b).0).unwrap();
+        }
+
+        for d in self.drops.iter().flatten() {
+            if d.p.get_y() < grid_bottom {
+                let (tl, br) = ball_square(vp, &d.p);
+                let color = match d.power {
+                    Power::Wide => Rgb565::GREEN,
+                    Power::Multi => Rgb565::RED,
+                };
+
+                Rectangle::new(tl, br)
+                    .into_styled(PrimitiveStyle::with_fill(color))
+                    .draw(self.display)
+                    .unwrap();
+            }
+        }
+
+        if self.bricks.is_clear() {
+            Outcome::Cleared
+        } else if self.balls.is_empty() {
+            Outcome::Lost
+        } else {
+            Outcome::Rally
+        }
+    }
+}
+
+impl<'a> Hooks for Scene<'a> {
+    fn enter(&mut self, s: State) {
+        rprintln!("enter {:?}", s);
+
+        match s {
+            State::Title => {
+                self.clear();
+                self.banner("BREAKOUT", Rgb565::GREEN);
+            }
+            State::Paused => self.banner("PAUSED", Rgb565::WHITE),
+            State::GameOver => self.banner("GAME OVER", Rgb565::YELLOW),
+            State::Playing => {}
+        }
+    }
+
+    fn exit(&mut self, s: State) {
+        match s {
+            State::Title => {
+                // new game, power-up drops depend on the start time
+                *self.logic = Breakout::new(RULES, cm::peripheral::DWT::get_cycle_count());
+                *self.racket = new_racket();
+
+                self.clear();
+                self.hud.clear(self.display).unwrap();
+                self.start_level();
+            }
+            State::Paused => {
+                self.banner("      ", Rgb565::BLACK);
+                self.draw_objects();
+            }
+            State::Playing | State::GameOver => {}
+        }
+    }
+}
+
+fn new_racket() -> Racket<f32> {
+    Racket::<f32>::new(120.0, 5.0, HW, 5.0, KINEMATICS)
+}
+
+// brick color shows hit points left, one pixel gap between the bricks
+fn draw_brick(
+    display: &mut DisplayType,
+    vp: &Viewport<f32>,
+    bricks: &Bricks<f32, COLS, ROWS>,
+    row: usize,
+    col: usize,
+) {
+    let (cx, cy) = bricks.center(row, col);
+    let (tl, br) = vp.to_rect(cx, cy, bricks.get_hw(), bricks.get_hh());
+    let color = match bricks.get_hp(row, col) {
+        0 => Rgb565::BLACK,
+        1 => Rgb565::YELLOW,
+        2 => Rgb565::CYAN,
+        _ => Rgb565::MAGENTA,
+    };
+
+    Rectangle::new(tl + Point::new(1, 1), br - Point::new(1, 1))
+        .into_styled(PrimitiveStyle::with_fill(color))
+        .draw(display)
+        .unwrap();
+}
+
+// racket sprite matches the normal racket size only, wide racket is a plain box
+fn draw_racket(display: &mut DisplayType, vp: &Viewport<f32>, r: &Racket<f32>) {
+    let (tl, br) = racket_square(vp, r);
+
+    if (br.x - tl.x + 1) as u32 == RACKET.width {
+        RACKET.draw(display, tl).unwrap();
+    } else {
+        Rectangle::new(tl, br)
+            .into_styled(PrimitiveStyle::with_fill(Rgb565::GREEN))
+            .draw(display)
+            .unwrap();
+    }
+}
+
+fn ball_square(vp: &Viewport<f32>, p: &Particle<f32>) -> (Point, Point) {
+    vp.to_rect(p.get_x(), p.get_y(), p.get_r(), p.get_r())
+}
+
+fn racket_square(vp: &Viewport<f32>, r: &Racket<f32>) -> (Point, Point) {
+    vp.to_rect(r.get_cx(), r.get_cy(), r.get_hw(), r.get_hh())
+}
+
+// ball has passed the racket and can not be returned anymore
+fn missed(b: &Particle<f32>, r: &Racket<f32>) -> bool {
+    b.get_y() < r.get_cy() - r.get_hh()
+}
@@ -2,8 +2,13 @@
 #![no_std]
 
 use cortex_m as cm;
+use cortex_m::peripheral::DWT;
 use cortex_m_rt as rt;
 use display_interface_parallel_gpio::PGPIO8BitInterface;
+use dso138_tests::game::clock::Clock;
+use dso138_tests::gfx::hud::Hud;
+use dso138_tests::gfx::trail::Trail;
+use dso138_tests::gfx::viewport::{Rotation, Viewport};
 use dso138_tests::phys::particles::{Particle, ParticleColor};
 use embedded_graphics::pixelcolor::Rgb565;
 use embedded_graphics::prelude::*;
@@ -12,7 +17,7 @@ use embedded_graphics::style::PrimitiveStyle;
 use embedded_hal::digital::v2::OutputPin;
 use hal::delay::Delay;
 use hal::prelude::*;
-use ili9341::{Ili9341, Orientation};
+use ili9341::Ili9341;
 use panic_rtt_target as _;
 use rand_core::RngCore;
 use rt::entry;
@@ -22,30 +27,39 @@ use wyhash::WyRng;
 
 const PNUM: usize = 30;
 
+// cpu sysclk: 72 MHz
+const SYSCLK: u32 = 72_000_000;
+
+// physics step: 10 msec, at most 5 steps per frame
+const STEP: u32 = SYSCLK / 100;
+const MAX_STEPS: u32 = 5;
+
+// trail length: set to 0 to disable motion trails
+const TLEN: usize = 8;
+
 // colliding particles: floating point arithmetic
 type T = f32;
 
-fn get_color(p: &Particle<T>) -> PrimitiveStyle<Rgb565> {
+fn get_rgb(p: &Particle<T>) -> Rgb565 {
     match p.get_color() {
-        ParticleColor::Green => PrimitiveStyle::with_fill(Rgb565::GREEN),
-        ParticleColor::Red => PrimitiveStyle::with_fill(Rgb565::RED),
-        ParticleColor::Blue => PrimitiveStyle::with_fill(Rgb565::BLUE),
-        ParticleColor::Yellow => PrimitiveStyle::with_fill(Rgb565::YELLOW),
-        ParticleColor::White => PrimitiveStyle::with_fill(Rgb565::WHITE),
+        ParticleColor::Green => Rgb565::GREEN,
+        ParticleColor::Red => Rgb565::RED,
+        ParticleColor::Blue => Rgb565::BLUE,
+        ParticleColor::Yellow => Rgb565::YELLOW,
+        ParticleColor::White => Rgb565::WHITE,
     }
 }
 
-fn area(p: &Particle<T>) -> (Point, Point) {
-    (
-        Point::new(
-            (p.get_x() - p.get_r()) as i32,
-            (p.get_y() - p.get_r()) as i32,
-        ),
-        Point::new(
-            (p.get_x() + p.get_r()) as i32,
-            (p.get_y() + p.get_r()) as i32,
-        ),
-    )
+fn get_color(p: &Particle<T>) -> PrimitiveStyle<Rgb565> {
+    PrimitiveStyle::with_fill(get_rgb(p))
+}
+
+fn center(vp: &Viewport<T>, p: &Particle<T>) -> Point {
+    vp.to_screen(p.get_x(), p.get_y())
+}
+
+fn area(vp: &Viewport<T>, p: &Particle<T>) -> (Point, Point) {
+    vp.to_rect(p.get_x(), p.get_y(), p.get_r(), p.get_r())
 }
 
 #[entry]
@@ -53,7 +67,7 @@ fn main() -> ! {
     rtt_init_print!();
 
     let dp = hal::stm32::Peripherals::take().unwrap();
-    let cp = cm::Peripherals::take().unwrap();
+    let mut cp = cm::Peripherals::take().unwrap();
 
     let mut rcc = dp.RCC.constrain();
     let mut flash = dp.FLASH.constrain();
@@ -66,6 +80,10 @@ fn main() -> ! {
         .pclk1(32.mhz())
         .freeze(&mut flash.acr);
 
+    // enable cycle counter for game clock
+    cp.DCB.enable_trace();
+    cp.DWT.enable_cycle_counter();
+
     let mut delay = Delay::new(cp.SYST, clocks);
 
     let mut gpioa = dp.GPIOA.split(&mut rcc.apb2);
@@ -98,16 +116,40 @@ fn main() -> ! {
     let pio8bit = PGPIO8BitInterface::new(p0, p1, p2, p3, p4, p5, p6, p7, rs, nwr);
     let mut display = Ili9341::new(pio8bit, nreset, &mut delay).unwrap();
     let fc = PrimitiveStyle::with_fill(Rgb565::BLACK);
-    let h = display.height() as T;
-    let w = display.width() as T;
+    let rot = Rotation::Portrait;
+    let screen = rot.size();
 
-    display.set_orientation(Orientation::Portrait).unwrap();
+    display.set_orientation(rot.orientation()).unwrap();
 
     // black screen
-    Rectangle::new(Point::new(0, 0), Point::new(w as i32, h as i32))
-        .into_styled(fc)
-        .draw(&mut display)
-        .unwrap();
+    Rectangle::new(
+        Point::new(0, 0),
+        Point::new(screen.width as i32 - 1, screen.height as i32 - 1),
+    )
+    .into_styled(fc)
+    .draw(&mut display)
+    .unwrap();
+
+    // statistics band on top of the screen: fps and skipped physics steps, energy, collisions
+    let mut hud = Hud::new(
+        0,
+        screen.width as i32,
+        [0, 90, 170],
+        Rgb565::WHITE,
+        Rgb565::BLUE,
+    );
+    let top = hud.bottom() + 3;
+
+    hud.clear(&mut display).unwrap();
+
+    // particles box below the band
+    let vp = Viewport::new(
+        rot,
+        Point::new(0, top),
+        Size::new(screen.width, screen.height - top as u32),
+        1.0,
+    );
+    let (w, h) = (vp.world_width(), vp.world_height());
 
     let mut ens: [Particle<T>; PNUM] = Default::default();
     let mut rng = WyRng::default();
@@ -133,7 +175,9 @@ fn main() -> ! {
     ens[2].set_color(ParticleColor::Yellow);
     ens[3].set_color(ParticleColor::White);
 
+    let mut trails: [Trail<TLEN>; PNUM] = [Trail::new(); PNUM];
     let mut collisions: u64 = 0;
+    let mut clock = Clock::new(SYSCLK, STEP, MAX_STEPS, DWT::get_cycle_count());
 
     loop {
         let mut energy: T = 0.0;
@@ -144,45 +188,64 @@ fn main() -> ! {
 
         rprintln!("energy: {} collisions: {}", energy, collisions);
 
-        for i in 0..PNUM {
-            let (head, tail) = ens.split_at_mut(i + 1);
-            let p = &mut head[i];
+        let stats = clock.stats();
 
-            if p.collided() {
-                continue;
-            }
+        hud.set(0, format_args!("FPS:{} S:{}", stats.frames, stats.skipped));
+        hud.set(1, format_args!("E:{}", energy as u32));
+        hud.set(2, format_args!("C:{}", collisions));
+        hud.draw(&mut display).unwrap();
 
-            if Particle::bounce(p, 0.0, w, 0.0, h) {
-                continue;
-            }
+        // erase particles before the physics steps move them
+        for p in ens.iter() {
+            let (tl, br) = area(&vp, p);
+
+            Rectangle::new(tl, br)
+                .into_styled(fc)
+                .draw(&mut display)
+                .unwrap();
+        }
+
+        for _ in 0..clock.tick(DWT::get_cycle_count()) {
+            for i in 0..PNUM {
+                let (head, tail) = ens.split_at_mut(i + 1);
+                let p = &mut head[i];
 
-            for q in tail {
-                if q.collided() {
+                if p.collided() {
                     continue;
                 }
 
-                if Particle::collide(p, q) {
-                    collisions += 1;
-                    break;
+                if Particle::bounce(p, 0.0, w, 0.0, h) {
+                    continue;
+                }
+
+                for q in tail {
+                    if q.collided() {
+                        continue;
+                    }
+
+                    if Particle::collide(p, q) {
+                        collisions += 1;
+                        break;
+                    }
                 }
             }
+
+            for p in ens.iter_mut() {
+                p.step();
+            }
         }
 
-        for p in &mut ens {
-            Rectangle::new(area(p).0, area(p).1)
-                .into_styled(fc)
-                .draw(&mut display)
-                .unwrap();
+        for (p, t) in ens.iter_mut().zip(trails.iter_mut()) {
+            if let Some(q) = t.push(center(&vp, p)) {
+                Trail::<TLEN>::erase(&mut display, q, Rgb565::BLACK, 2).unwrap();
+            }
 
-            p.step();
+            t.draw(&mut display, get_rgb(p), Rgb565::BLACK, 2).unwrap();
 
-            Circle::new(
-                Point::new(p.get_x() as i32, p.get_y() as i32),
-                p.get_r() as u32,
-            )
-            .into_styled(get_color(p))
-            .draw(&mut display)
-            .unwrap();
+            Circle::new(center(&vp, p), vp.to_len(p.get_r()) as u32)
+                .into_styled(get_color(p))
+                .draw(&mut display)
+                .unwrap();
         }
 
         led.toggle().unwrap();
@@ -2,8 +2,12 @@
 #![no_std]
 
 use cortex_m as cm;
+use cortex_m::peripheral::DWT;
 use cortex_m_rt as rt;
 use display_interface_parallel_gpio::PGPIO8BitInterface;
+use dso138_tests::game::clock::Clock;
+use dso138_tests::gfx::trail::Trail;
+use dso138_tests::gfx::viewport::{Rotation, Viewport};
 use dso138_tests::phys::particles::{Particle, ParticleColor};
 use embedded_graphics::pixelcolor::Rgb565;
 use embedded_graphics::prelude::*;
@@ -12,27 +16,44 @@ use embedded_graphics::style::PrimitiveStyle;
 use embedded_hal::digital::v2::OutputPin;
 use hal::delay::Delay;
 use hal::prelude::*;
-use ili9341::{Ili9341, Orientation};
+use ili9341::Ili9341;
 use panic_rtt_target as _;
 use rt::entry;
 use rtt_target::{rprintln, rtt_init_print};
 use stm32f1xx_hal as hal;
 
-fn get_color(p: &Particle<i32>) -> PrimitiveStyle<Rgb565> {
+const PNUM: usize = 4;
+
+// cpu sysclk: 72 MHz
+const SYSCLK: u32 = 72_000_000;
+
+// physics step: 10 msec, at most 5 steps per frame
+const STEP: u32 = SYSCLK / 100;
+const MAX_STEPS: u32 = 5;
+
+// trail length: set to 0 to disable motion trails
+const TLEN: usize = 8;
+
+fn get_rgb(p: &Particle<i32>) -> Rgb565 {
     match p.get_color() {
-        ParticleColor::Green => PrimitiveStyle::with_fill(Rgb565::GREEN),
-        ParticleColor::Red => PrimitiveStyle::with_fill(Rgb565::RED),
-        ParticleColor::Blue => PrimitiveStyle::with_fill(Rgb565::BLUE),
-        ParticleColor::Yellow => PrimitiveStyle::with_fill(Rgb565::YELLOW),
-        ParticleColor::White => PrimitiveStyle::with_fill(Rgb565::WHITE),
+        ParticleColor::Green => Rgb565::GREEN,
+        ParticleColor::Red => Rgb565::RED,
+        ParticleColor::Blue => Rgb565::BLUE,
+        ParticleColor::Yellow => Rgb565::YELLOW,
+        ParticleColor::White => Rgb565::WHITE,
     }
 }
 
-fn area(p: &Particle<i32>) -> (Point, Point) {
-    (
-        Point::new(p.get_x() - p.get_r(), p.get_y() - p.get_r()),
-        Point::new(p.get_x() + p.get_r(), p.get_y() + p.get_r()),
-    )
+fn get_color(p: &Particle<i32>) -> PrimitiveStyle<Rgb565> {
+    PrimitiveStyle::with_fill(get_rgb(p))
+}
+
+fn center(vp: &Viewport<i32>, p: &Particle<i32>) -> Point {
+    vp.to_screen(p.get_x(), p.get_y())
+}
+
+fn area(vp: &Viewport<i32>, p: &Particle<i32>) -> (Point, Point) {
+    vp.to_rect(p.get_x(), p.get_y(), p.get_r(), p.get_r())
 }
 
 #[entry]
@@ -40,7 +61,7 @@ fn main() -> ! {
     rtt_init_print!();
 
     let dp = hal::stm32::Peripherals::take().unwrap();
-    let cp = cm::Peripherals::take().unwrap();
+    let mut cp = cm::Peripherals::take().unwrap();
 
     let mut rcc = dp.RCC.constrain();
     let mut flash = dp.FLASH.constrain();
@@ -53,6 +74,10 @@ fn main() -> ! {
         .pclk1(32.mhz())
         .freeze(&mut flash.acr);
 
+    // enable cycle counter for game clock
+    cp.DCB.enable_trace();
+    cp.DWT.enable_cycle_counter();
+
     let mut delay = Delay::new(cp.SYST, clocks);
 
     let mut gpioa = dp.GPIOA.split(&mut rcc.apb2);
@@ -85,25 +110,28 @@ fn main() -> ! {
     let pio8bit = PGPIO8BitInterface::new(p0, p1, p2, p3, p4, p5, p6, p7, rs, nwr);
     let mut display = Ili9341::new(pio8bit, nreset, &mut delay).unwrap();
     let fc = PrimitiveStyle::with_fill(Rgb565::BLACK);
-    let h = display.height() as i32;
-    let w = display.width() as i32;
+    let rot = Rotation::Portrait;
+    let screen = rot.size();
+
+    display.set_orientation(rot.orientation()).unwrap();
 
-    display.set_orientation(Orientation::Portrait).unwrap();
+    // particles box is the whole screen
+    let vp = Viewport::new(rot, Point::new(0, 0), screen, 1);
+    let (w, h) = (vp.world_width(), vp.world_height());
 
     // black screen
-    Rectangle::new(Point::new(0, 0), Point::new(w, h))
-        .into_styled(fc)
-        .draw(&mut display)
-        .unwrap();
+    vp.area().into_styled(fc).draw(&mut display).unwrap();
 
-    let mut ens: [Particle<i32>; 4] = [
+    let mut ens: [Particle<i32>; PNUM] = [
         Particle::new(100, 50, 0, -4, 10, 1, ParticleColor::Green),
         Particle::new(100, 100, 0, -5, 10, 1, ParticleColor::Red),
         Particle::new(100, 150, 0, 5, 10, 1, ParticleColor::Blue),
         Particle::new(100, 200, 0, 4, 10, 1, ParticleColor::Yellow),
     ];
 
+    let mut trails: [Trail<TLEN>; PNUM] = [Trail::new(); PNUM];
     let mut collisions: u64 = 0;
+    let mut clock = Clock::new(SYSCLK, STEP, MAX_STEPS, DWT::get_cycle_count());
 
     loop {
         let mut energy: u64 = 0;
@@ -114,45 +142,70 @@ fn main() -> ! {
 
         rprintln!("energy: {} collisions: {}", energy, collisions);
 
-        for i in 0..ens.len() {
-            let (head, tail) = ens.split_at_mut(i + 1);
-            let p = &mut head[i];
+        if let Some(stats) = clock.take_stats() {
+            rprintln!(
+                "fps: {} steps: {} skipped: {}",
+                stats.frames,
+                stats.steps,
+                stats.skipped
+            );
+        }
 
-            if p.collided() {
-                continue;
-            }
+        // erase particles before the physics steps move them
+        for p in ens.iter() {
+            let (tl, br) = area(&vp, p);
 
-            if Particle::bounce(p, 0, w, 0, h) {
-                continue;
-            }
+            Rectangle::new(tl, br)
+                .into_styled(fc)
+                .draw(&mut display)
+                .unwrap();
+        }
+
+        for _ in 0..clock.tick(DWT::get_cycle_count()) {
+            for i in 0..ens.len() {
+                let (head, tail) = ens.split_at_mut(i + 1);
+                let p = &mut head[i];
 
-            for q in tail {
-                if q.collided() {
+                if p.collided() {
                     continue;
                 }
 
-                if Particle::collide(p, q) {
-                    collisions += 1;
-                    break;
+                if Particle::bounce(p, 0, w, 0, h) {
+                    continue;
+                }
+
+                for q in tail {
+                    if q.collided() {
+                        continue;
+                    }
+
+                    if Particle::collide(p, q) {
+                        collisions += 1;
+                        break;
+                    }
                 }
             }
+
+            for p in ens.iter_mut() {
+                p.step();
+            }
         }
 
-        for p in &mut ens {
-            Rectangle::new(area(p).0, area(p).1)
-                .into_styled(fc)
-                .draw(&mut display)
-                .unwrap();
+        for (p, t) in ens.iter().zip(trails.iter_mut()) {
+            if let Some(q) = t.push(center(&vp, p)) {
+                Trail::<TLEN>::erase(&mut display, q, Rgb565::BLACK, 2).unwrap();
+            }
+
+            t.draw(&mut display, get_rgb(p), Rgb565::BLACK, 2).unwrap();
 
-            p.step();
+            let (tl, br) = area(&vp, p);
 
-            Rectangle::new(area(p).0, area(p).1)
+            Rectangle::new(tl, br)
                 .into_styled(get_color(p))
                 .draw(&mut display)
                 .unwrap();
         }
 
-        delay.delay_ms(10u16);
         led.toggle().unwrap();
     }
 }
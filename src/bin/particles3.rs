@@ -2,8 +2,12 @@
 #![no_std]
 
 use cortex_m as cm;
+use cortex_m::peripheral::DWT;
 use cortex_m_rt as rt;
 use display_interface_parallel_gpio::PGPIO8BitInterface;
+use dso138_tests::game::clock::Clock;
+use dso138_tests::gfx::trail::Trail;
+use dso138_tests::gfx::viewport::{Rotation, Viewport};
 use dso138_tests::phys::particles::{Particle, ParticleColor};
 use embedded_graphics::pixelcolor::Rgb565;
 use embedded_graphics::prelude::*;
@@ -13,7 +17,7 @@ use embedded_hal::digital::v2::OutputPin;
 use fixed::{types::extra::U12, FixedI32};
 use hal::delay::Delay;
 use hal::prelude::*;
-use ili9341::{Ili9341, Orientation};
+use ili9341::Ili9341;
 use panic_rtt_target as _;
 use rand_core::RngCore;
 use rt::entry;
@@ -23,30 +27,39 @@ use wyhash::WyRng;
 
 const PNUM: usize = 20;
 
+// cpu sysclk: 72 MHz
+const SYSCLK: u32 = 72_000_000;
+
+// physics step: 10 msec, at most 5 steps per frame
+const STEP: u32 = SYSCLK / 100;
+const MAX_STEPS: u32 = 5;
+
+// trail length: set to 0 to disable motion trails
+const TLEN: usize = 8;
+
 // colliding particles: fixed point arithmetic
 type T = FixedI32<U12>;
 
-fn get_color(p: &Particle<T>) -> PrimitiveStyle<Rgb565> {
+fn get_rgb(p: &Particle<T>) -> Rgb565 {
     match p.get_color() {
-        ParticleColor::Green => PrimitiveStyle::with_fill(Rgb565::GREEN),
-        ParticleColor::Red => PrimitiveStyle::with_fill(Rgb565::RED),
-        ParticleColor::Blue => PrimitiveStyle::with_fill(Rgb565::BLUE),
-        ParticleColor::Yellow => PrimitiveStyle::with_fill(Rgb565::YELLOW),
-        ParticleColor::White => PrimitiveStyle::with_fill(Rgb565::WHITE),
+        ParticleColor::Green => Rgb565::GREEN,
+        ParticleColor::Red => Rgb565::RED,
+        ParticleColor::Blue => Rgb565::BLUE,
+        ParticleColor::Yellow => Rgb565::YELLOW,
+        ParticleColor::White => Rgb565::WHITE,
     }
 }
 
-fn area(p: &Particle<T>) -> (Point, Point) {
-    (
-        Point::new(
-            (p.get_x() - p.get_r()).to_num::<i32>(),
-            (p.get_y() - p.get_r()).to_num::<i32>(),
-        ),
-        Point::new(
-            (p.get_x() + p.get_r()).to_num::<i32>(),
-            (p.get_y() + p.get_r()).to_num::<i32>(),
-        ),
-    )
+fn get_color(p: &Particle<T>) -> PrimitiveStyle<Rgb565> {
+    PrimitiveStyle::with_fill(get_rgb(p))
+}
+
+fn center(vp: &Viewport<T>, p: &Particle<T>) -> Point {
+    vp.to_screen(p.get_x(), p.get_y())
+}
+
+fn area(vp: &Viewport<T>, p: &Particle<T>) -> (Point, Point) {
+    vp.to_rect(p.get_x(), p.get_y(), p.get_r(), p.get_r())
 }
 
 #[entry]
@@ -54,7 +67,7 @@ fn main() -> ! {
     rtt_init_print!();
 
     let dp = hal::stm32::Peripherals::take().unwrap();
-    let cp = cm::Peripherals::take().unwrap();
+    let mut cp = cm::Peripherals::take().unwrap();
 
     let mut rcc = dp.RCC.constrain();
     let mut flash = dp.FLASH.constrain();
@@ -67,6 +80,10 @@ fn main() -> ! {
         .pclk1(32.mhz())
         .freeze(&mut flash.acr);
 
+    // enable cycle counter for game clock
+    cp.DCB.enable_trace();
+    cp.DWT.enable_cycle_counter();
+
     let mut delay = Delay::new(cp.SYST, clocks);
 
     let mut gpioa = dp.GPIOA.split(&mut rcc.apb2);
@@ -99,19 +116,17 @@ fn main() -> ! {
     let pio8bit = PGPIO8BitInterface::new(p0, p1, p2, p3, p4, p5, p6, p7, rs, nwr);
     let mut display = Ili9341::new(pio8bit, nreset, &mut delay).unwrap();
     let fc = PrimitiveStyle::with_fill(Rgb565::BLACK);
-    let h = T::from_num(display.height());
-    let w = T::from_num(display.width());
+    let rot = Rotation::Portrait;
+    let screen = rot.size();
 
-    display.set_orientation(Orientation::Portrait).unwrap();
+    display.set_orientation(rot.orientation()).unwrap();
+
+    // particles box is the whole screen
+    let vp = Viewport::new(rot, Point::new(0, 0), screen, T::from_num(1));
+    let (w, h) = (vp.world_width(), vp.world_height());
 
     // black screen
-    Rectangle::new(
-        Point::new(0, 0),
-        Point::new(w.to_num::<i32>(), h.to_num::<i32>()),
-    )
-    .into_styled(fc)
-    .draw(&mut display)
-    .unwrap();
+    vp.area().into_styled(fc).draw(&mut display).unwrap();
 
     let mut ens: [Particle<T>; PNUM] = Default::default();
     let mut rng = WyRng::default();
@@ -137,7 +152,9 @@ fn main() -> ! {
     ens[2].set_color(ParticleColor::Yellow);
     ens[3].set_color(ParticleColor::White);
 
+    let mut trails: [Trail<TLEN>; PNUM] = [Trail::new(); PNUM];
     let mut collisions: u64 = 0;
+    let mut clock = Clock::new(SYSCLK, STEP, MAX_STEPS, DWT::get_cycle_count());
 
     loop {
         let mut energy: T = T::from_num(0);
@@ -148,45 +165,66 @@ fn main() -> ! {
 
         rprintln!("energy: {} collisions: {}", energy, collisions);
 
-        for i in 0..PNUM {
-            let (head, tail) = ens.split_at_mut(i + 1);
-            let p = &mut head[i];
+        if let Some(stats) = clock.take_stats() {
+            rprintln!(
+                "fps: {} steps: {} skipped: {}",
+                stats.frames,
+                stats.steps,
+                stats.skipped
+            );
+        }
 
-            if p.collided() {
-                continue;
-            }
+        // erase particles before the physics steps move them
+        for p in ens.iter() {
+            let (tl, br) = area(&vp, p);
 
-            if Particle::bounce(p, T::from_num(0), w, T::from_num(0), h) {
-                continue;
-            }
+            Rectangle::new(tl, br)
+                .into_styled(fc)
+                .draw(&mut display)
+                .unwrap();
+        }
+
+        for _ in 0..clock.tick(DWT::get_cycle_count()) {
+            for i in 0..PNUM {
+                let (head, tail) = ens.split_at_mut(i + 1);
+                let p = &mut head[i];
+
+                if p.collided() {
+                    continue;
+                }
 
-            for q in tail {
-                if q.collided() {
+                if Particle::bounce(p, T::from_num(0), w, T::from_num(0), h) {
                     continue;
                 }
 
-                if Particle::collide(p, q) {
-                    collisions += 1;
-                    break;
+                for q in tail {
+                    if q.collided() {
+                        continue;
+                    }
+
+                    if Particle::collide(p, q) {
+                        collisions += 1;
+                        break;
+                    }
                 }
             }
+
+            for p in ens.iter_mut() {
+                p.step();
+            }
         }
 
-        for p in &mut ens {
-            Rectangle::new(area(p).0, area(p).1)
-                .into_styled(fc)
-                .draw(&mut display)
-                .unwrap();
+        for (p, t) in ens.iter().zip(trails.iter_mut()) {
+            if let Some(q) = t.push(center(&vp, p)) {
+                Trail::<TLEN>::erase(&mut display, q, Rgb565::BLACK, 2).unwrap();
+            }
 
-            p.step();
+            t.draw(&mut display, get_rgb(p), Rgb565::BLACK, 2).unwrap();
 
-            Circle::new(
-                Point::new(p.get_x().to_num::<i32>(), p.get_y().to_num::<i32>()),
-                p.get_r().to_num::<u32>(),
-            )
-            .into_styled(get_color(p))
-            .draw(&mut display)
-            .unwrap();
+            Circle::new(center(&vp, p), vp.to_len(p.get_r()) as u32)
+                .into_styled(get_color(p))
+                .draw(&mut display)
+                .unwrap();
         }
 
         led.toggle().unwrap();
@@ -0,0 +1,517 @@
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use cortex_m as cm;
+use display_interface_parallel_gpio::PGPIO8BitInterface;
+use dso138_tests::game::pong::{Player, Pong};
+use dso138_tests::gfx::hud::Hud;
+use dso138_tests::gfx::sprite::assets::{BALL, RACKET};
+use dso138_tests::gfx::viewport::{Rotation, Viewport};
+use dso138_tests::hw::delay_timer::DelayTimer;
+use dso138_tests::phys::particles::{Particle, ParticleColor};
+use dso138_tests::phys::racket::{Intent, Kinematics, Racket};
+use embedded_graphics::fonts::{Font12x16, Text};
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::style::{PrimitiveStyle, TextStyleBuilder};
+use embedded_hal::digital::v2::InputPin;
+use embedded_hal::digital::v2::OutputPin;
+use hal::gpio::gpioa::PA15;
+use hal::gpio::gpiob::{PB0, PB1, PB2, PB3, PB4, PB5, PB6, PB7};
+use hal::gpio::gpiob::{PB11, PB12, PB13, PB14, PB15};
+use hal::gpio::gpioc::{PC14, PC15};
+use hal::gpio::{Input, Output, PullUp, PushPull};
+use hal::prelude::*;
+use hal::stm32::TIM3;
+use hal::timer::CountDownTimer;
+use hal::timer::Event;
+use hal::timer::Timer;
+use ili9341::Ili9341;
+use panic_rtt_target as _;
+use rtic::app;
+use rtic::cyccnt::Instant;
+use rtic::cyccnt::U32Ext;
+use rtt_target::{rprintln, rtt_init_print};
+use stm32f1xx_hal as hal;
+
+type DisplayType = Ili9341<
+    PGPIO8BitInterface<
+        PB0<Output<PushPull>>,
+        PB1<Output<PushPull>>,
+        PB2<Output<PushPull>>,
+        PB3<Output<PushPull>>,
+        PB4<Output<PushPull>>,
+        PB5<Output<PushPull>>,
+        PB6<Output<PushPull>>,
+        PB7<Output<PushPull>>,
+        PC14<Output<PushPull>>,
+        PC15<Output<PushPull>>,
+    >,
+    PB11<Output<PushPull>>,
+>;
+
+/* cpu sysclk: 72 MHz (no external quartz) */
+
+const STEP_PERIOD: u32 = 72_0000; /* 10 msec */
+
+/* ball deflection at the racket edges */
+const SPIN: f32 = 1.0;
+
+/* fraction of racket velocity passed to the ball */
+const DRAG: f32 = 0.2;
+
+/* racket acceleration, max speed, friction per step and simulation time step */
+const KINEMATICS: Kinematics<f32> = Kinematics {
+    acc: 50.0,
+    vmax: 50.0,
+    friction: 0.05,
+    dt: 0.1,
+};
+
+/* points to win the game */
+const POINTS: u32 = 11;
+
+/* pause before serve and after the game, in steps */
+const SERVE_DELAY: u32 = 100;
+const WIN_DELAY: u32 = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Phase {
+    // ball waits in the middle, rackets can move
+    Serve(u32),
+    Rally,
+    // game is won, new game starts when countdown expires
+    Won(u32),
+}
+
+#[app(device = stm32f1xx_hal::stm32, peripherals = true, monotonic = rtic::cyccnt::CYCCNT)]
+const APP: () = {
+    struct Resources {
+        // early resources
+        #[init(false)]
+        cb1: bool,
+        #[init(false)]
+        cb2: bool,
+        #[init(false)]
+        cb3: bool,
+        #[init(false)]
+        cb4: bool,
+        #[init(Phase::Serve(SERVE_DELAY))]
+        phase: Phase,
+
+        // late resources
+        display: DisplayType,
+        button1: PB12<Input<PullUp>>,
+        button2: PB13<Input<PullUp>>,
+        button3: PB14<Input<PullUp>>,
+        button4: PB15<Input<PullUp>>,
+        led: PA15<Output<PushPull>>,
+        btmr: CountDownTimer<TIM3>,
+        ball: Particle<f32>,
+        bottom: Racket<f32>,
+        top: Racket<f32>,
+        hud: Hud<2>,
+        vp: Viewport<f32>,
+        logic: Pong<f32>,
+    }
+
+    #[init(schedule = [step_task])]
+    fn init(mut cx: init::Context) -> init::LateResources {
+        rtt_init_print!();
+
+        let mut rcc = cx.device.RCC.constrain();
+        let mut flash = cx.device.FLASH.constrain();
+        let mut afio = cx.device.AFIO.constrain(&mut rcc.apb2);
+
+        let clocks = rcc
+            .cfgr
+            .use_hse(8.mhz())
+            .sysclk(72.mhz())
+            .pclk1(32.mhz())
+            .freeze(&mut flash.acr);
+
+        /* enable monotonic timer */
+        cx.core.DCB.enable_trace();
+        cx.core.DWT.enable_cycle_counter();
+
+        let mut gpioa = cx.device.GPIOA.split(&mut rcc.apb2);
+        let mut gpiob = cx.device.GPIOB.split(&mut rcc.apb2);
+        let mut gpioc = cx.device.GPIOC.split(&mut rcc.apb2);
+
+        let (pa15, pb3, pb4) = afio.mapr.disable_jtag(gpioa.pa15, gpiob.pb3, gpiob.pb4);
+
+        let mut btmr =
+            Timer::tim3(cx.device.TIM3, &clocks, &mut rcc.apb1).start_count_down(5000.hz());
+        btmr.listen(Event::Update);
+
+        let dtmr = Timer::tim2(cx.device.TIM2, &clocks, &mut rcc.apb1)
+            .start_master(1000.khz(), hal::pac::tim2::cr2::MMS_A::RESET);
+
+        /* buttons */
+
+        let button1 = gpiob.pb12.into_pull_up_input(&mut gpiob.crh);
+        let button2 = gpiob.pb13.into_pull_up_input(&mut gpiob.crh);
+        let button3 = gpiob.pb14.into_pull_up_input(&mut gpiob.crh);
+        let button4 = gpiob.pb15.into_pull_up_input(&mut gpiob.crh);
+
+        /* led */
+
+        let led = pa15.into_push_pull_output(&mut gpioa.crh);
+
+        /* display */
+
+        let mut delay = DelayTimer::new(dtmr);
+
+        let p0 = gpiob.pb0.into_push_pull_output(&mut gpiob.crl);
+        let p1 = gpiob.pb1.into_push_pull_output(&mut gpiob.crl);
+        let p2 = gpiob.pb2.into_push_pull_output(&mut gpiob.crl);
+        let p3 = pb3.into_push_pull_output(&mut gpiob.crl);
+        let p4 = pb4.into_push_pull_output(&mut gpiob.crl);
+        let p5 = gpiob.pb5.into_push_pull_output(&mut gpiob.crl);
+        let p6 = gpiob.pb6.into_push_pull_output(&mut gpiob.crl);
+        let p7 = gpiob.pb7.into_push_pull_output(&mut gpiob.crl);
+
+        let mut ncs = gpioc.pc13.into_push_pull_output(&mut gpioc.crh);
+        let mut nrd = gpiob.pb10.into_push_pull_output(&mut gpiob.crh);
+
+        let nreset = gpiob.pb11.into_push_pull_output(&mut gpiob.crh);
+        let nwr = gpioc.pc15.into_push_pull_output(&mut gpioc.crh);
+        let rs = gpioc.pc14.into_push_pull_output(&mut gpioc.crh);
+
+        ncs.set_low().unwrap();
+        nrd.set_high().unwrap();
+
+        let pio8bit = PGPIO8BitInterface::new(p0, p1, p2, p3, p4, p5, p6, p7, rs, nwr);
+        let mut display = Ili9341::new(pio8bit, nreset, &mut delay).unwrap();
+
+        let rot = Rotation::PortraitFlipped;
+        let screen = rot.size();
+
+        display.set_orientation(rot.orientation()).unwrap();
+
+        /* score band on top of the screen, game field below it */
+
+        let mut hud = Hud::new(
+            0,
+            screen.width as i32,
+            [0, 120],
+            Rgb565::WHITE,
+            Rgb565::BLUE,
+        );
+
+        let vp = Viewport::new(
+            rot,
+            Point::new(0, hud.bottom()),
+            Size::new(screen.width, screen.height - hud.bottom() as u32),
+            1.0,
+        );
+
+        /* game objects */
+
+        let mut ball = Particle::<f32>::new(0.0, 0.0, 0.0, 0.0, 5.0, 0.1, ParticleColor::Blue);
+        let mut bottom = new_racket(&vp, Player::Bottom);
+        let mut top = new_racket(&vp, Player::Top);
+        let mut logic = new_logic();
+
+        Scene {
+            display: &mut display,
+            ball: &mut ball,
+            bottom: &mut bottom,
+            top: &mut top,
+            hud: &mut hud,
+            logic: &mut logic,
+            vp: &vp,
+        }
+        .new_game();
+
+        cx.schedule.step_task(Instant::now()).unwrap();
+
+        /* init late resources */
+        init::LateResources {
+            display,
+            button1,
+            button2,
+            button3,
+            button4,
+            led,
+            ball,
+            btmr,
+            bottom,
+            top,
+            hud,
+            vp,
+            logic,
+        }
+    }
+
+    #[idle]
+    fn idle(_: idle::Context) -> ! {
+        loop {
+            cm::asm::nop();
+        }
+    }
+
+    #[task(binds = TIM3, resources = [btmr, button1, cb1, button2, cb2, button3, cb3, button4, cb4])]
+    fn tim3(cx: tim3::Context) {
+        *cx.resources.cb1 = cx.resources.button1.is_low().unwrap();
+        *cx.resources.cb2 = cx.resources.button2.is_low().unwrap();
+        *cx.resources.cb3 = cx.resources.button3.is_low().unwrap();
+        *cx.resources.cb4 = cx.resources.button4.is_low().unwrap();
+
+        cx.resources.btmr.clear_update_interrupt_flag();
+    }
+
+    #[task(schedule = [step_task], resources = [display, ball, cb1, cb2, cb3, cb4, bottom, top, hud, logic, vp, phase])]
+    fn step_task(cx: step_task::Context) {
+        let mut scene = Scene {
+            display: cx.resources.display,
+            ball: cx.resources.ball,
+            bottom: cx.resources.bottom,
+            top: cx.resources.top,
+            hud: cx.resources.hud,
+            logic: cx.resources.logic,
+            vp: cx.resources.vp,
+        };
+
+        // bottom player: B2 left, B1 right; top player: B4 left, B3 right
+        let intents = (
+            intent(*cx.resources.cb2, *cx.resources.cb1),
+            intent(*cx.resources.cb4, *cx.resources.cb3),
+        );
+
+        *cx.resources.phase = match *cx.resources.phase {
+            Phase::Serve(0) => Phase::Rally,
+            Phase::Serve(n) => {
+                scene.move_rackets(intents);
+                Phase::Serve(n - 1)
+            }
+            Phase::Rally => {
+                scene.move_rackets(intents);
+
+                match scene.play() {
+                    Some(p) => {
+                        rprintln!("ball missed by {:?}", p);
+
+                        if scene.logic.on_miss(p) {
+                            scene.game_over();
+                            Phase::Won(WIN_DELAY)
+                        } else {
+                            scene.serve();
+                            Phase::Serve(SERVE_DELAY)
+                        }
+                    }
+                    None => Phase::Rally,
+                }
+            }
+            Phase::Won(0) => {
+                scene.new_game();
+                Phase::Serve(SERVE_DELAY)
+            }
+            Phase::Won(n) => Phase::Won(n - 1),
+        };
+
+        cx.schedule
+            .step_task(cx.scheduled + STEP_PERIOD.cycles())
+            .unwrap();
+    }
+
+    // needed for RTIC timer queue and task management
+    extern "C" {
+        fn EXTI2();
+    }
+};
+
+struct Scene<'a> {
+    display: &'a mut DisplayType,
+    ball: &'a mut Particle<f32>,
+    bottom: &'a mut Racket<f32>,
+    top: &'a mut Racket<f32>,
+    hud: &'a mut Hud<2>,
+    logic: &'a mut Pong<f32>,
+    vp: &'a Viewport<f32>,
+}
+
+impl<'a> Scene<'a> {
+    fn new_game(&mut self) {
+        let screen = self.vp.rotation().size();
+
+        *self.logic = new_logic();
+        *self.bottom = new_racket(self.vp, Player::Bottom);
+        *self.top = new_racket(self.vp, Player::Top);
+
+        Rectangle::new(
+            Point::new(0, 0),
+            Point::new(screen.width as i32, screen.height as i32),
+        )
+        .into_styled(PrimitiveStyle::with_fill(Rgb565::BLACK))
+        .draw(self.display)
+        .unwrap();
+
+        self.hud.clear(self.display).unwrap();
+        RACKET
+            .draw(self.display, racket_square(self.vp, self.bottom).0)
+            .unwrap();
+        RACKET
+            .draw(self.display, racket_square(self.vp, self.top).0)
+            .unwrap();
+        self.serve();
+    }
+
+    // dashed line across the middle of the field
+    fn draw_net(&mut self) {
+        let screen = self.vp.rotation().size();
+        let y = self.vp.to_screen(0.0, self.vp.world_height() / 2.0).y;
+
+        for x in (0..screen.width as i32).step_by(16) {
+            Rectangle::new(Point::new(x + 4, y - 1), Point::new(x + 11, y))
+                .into_styled(PrimitiveStyle::with_fill(Rgb565::new(16, 32, 16)))
+                .draw(self.display)
+                .unwrap();
+        }
+    }
+
+    fn draw_hud(&mut self) {
+        self.hud.set(
+            0,
+            format_args!("P1: {}", self.logic.get_score(Player::Bottom)),
+        );
+        self.hud
+            .set(1, format_args!("P2: {}", self.logic.get_score(Player::Top)));
+        self.hud.draw(self.display).unwrap();
+    }
+
+    // remove missed ball and put a new one in the middle
+    fn serve(&mut self) {
+        let (tl, br) = ball_square(self.vp, self.ball);
+
+        Rectangle::new(tl, br)
+            .into_styled(PrimitiveStyle::with_fill(Rgb565::BLACK))
+            .draw(self.display)
+            .unwrap();
+
+        self.logic.serve(
+            self.ball,
+            self.vp.world_width() / 2.0,
+            self.vp.world_height() / 2.0,
+        );
+
+        self.draw_net();
+        BALL.draw(self.display, ball_square(self.vp, self.ball).0)
+            .unwrap();
+        self.draw_hud();
+    }
+
+    fn game_over(&mut self) {
+        let text = match self.logic.winner() {
+            Some(Player::Bottom) => "P1 WINS",
+            _ => "P2 WINS",
+        };
+        let style = TextStyleBuilder::new(Font12x16)
+            .text_color(Rgb565::YELLOW)
+            .background_color(Rgb565::BLACK)
+            .build();
+        let center = self
+            .vp
+            .to_screen(self.vp.world_width() / 2.0, self.vp.world_height() / 2.0);
+
+        self.draw_hud();
+
+        Text::new(text, center - Point::new(6 * text.len() as i32, 8))
+            .into_styled(style)
+            .draw(self.display)
+            .unwrap();
+    }
+
+    fn move_rackets(&mut self, intents: (Intent, Intent)) {
+        move_racket(self.display, self.vp, self.bottom, intents.0);
+        move_racket(self.display, self.vp, self.top, intents.1);
+    }
+
+    // one step of the rally, returns the player who missed the ball
+    fn play(&mut self) -> Option<Player> {
+        let vp = self.vp;
+        let ball = &mut *self.ball;
+        let (tl, br) = ball_square(vp, ball);
+
+        Rectangle::new(tl, br)
+            .into_styled(PrimitiveStyle::with_fill(Rgb565::BLACK))
+            .draw(self.display)
+            .unwrap();
+
+        ball.step();
+
+        let _ball_bounce = Particle::<f32>::bounce(ball, 0.0, vp.world_width(), f32::MIN, f32::MAX);
+
+        if Racket::<f32>::collide(self.bottom, ball, SPIN, DRAG)
+            || Racket::<f32>::collide(self.top, ball, SPIN, DRAG)
+        {
+            rprintln!("ball returned: ({}, {})", ball.get_vx(), ball.get_vy());
+        }
+
+        // ball erased part of the net
+        let d = ball.get_y() - vp.world_height() / 2.0;
+
+        if d < 2.0 * ball.get_r() && d > -2.0 * ball.get_r() {
+            self.draw_net();
+        }
+
+        BALL.draw(self.display, ball_square(vp, self.ball).0)
+            .unwrap();
+
+        if self.ball.get_y() < self.bottom.get_cy() - self.bottom.get_hh() {
+            Some(Player::Bottom)
+        } else if self.ball.get_y() > self.top.get_cy() + self.top.get_hh() {
+            Some(Player::Top)
+        } else {
+            None
+        }
+    }
+}
+
+fn intent(left: bool, right: bool) -> Intent {
+    match (left, right) {
+        (false, true) => Intent::Right,
+        (true, false) => Intent::Left,
+        (true, true) => Intent::Brake,
+        (false, false) => Intent::Idle,
+    }
+}
+
+fn new_logic() -> Pong<f32> {
+    Pong::new(POINTS, 10.0, 20.0)
+}
+
+fn new_racket(vp: &Viewport<f32>, p: Player) -> Racket<f32> {
+    let cy = match p {
+        Player::Bottom => 5.0,
+        Player::Top => vp.world_height() - 5.0,
+    };
+    Racket::<f32>::new(vp.world_width() / 2.0, cy, 15.0, 5.0, KINEMATICS)
+}
+
+fn move_racket(display: &mut DisplayType, vp: &Viewport<f32>, r: &mut Racket<f32>, i: Intent) {
+    let old = racket_square(vp, r);
+
+    r.step(i);
+
+    let _racket_bounce = Racket::<f32>::bounce(r, 0.0, vp.world_width());
+
+    if racket_square(vp, r) != old {
+        Rectangle::new(old.0, old.1)
+            .into_styled(PrimitiveStyle::with_fill(Rgb565::BLACK))
+            .draw(display)
+            .unwrap();
+
+        RACKET.draw(display, racket_square(vp, r).0).unwrap();
+    }
+}
+
+fn ball_square(vp: &Viewport<f32>, p: &Particle<f32>) -> (Point, Point) {
+    vp.to_rect(p.get_x(), p.get_y(), p.get_r(), p.get_r())
+}
+
+fn racket_square(vp: &Viewport<f32>, r: &Racket<f32>) -> (Point, Point) {
+    vp.to_rect(r.get_cx(), r.get_cy(), r.get_hw(), r.get_hh())
+}
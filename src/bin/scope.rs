@@ -0,0 +1,1190 @@
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use cortex_m as cm;
+use display_interface_parallel_gpio::PGPIO8BitInterface;
+use dso138_tests::gfx::hud::Hud;
+use dso138_tests::gfx::viewport::Rotation;
+use dso138_tests::hw::capture::{Buffer, Capture, SampleRate, BUF_LEN};
+use dso138_tests::hw::delay_timer::DelayTimer;
+use dso138_tests::hw::siggen::{self, SigGen, Waveform};
+use dso138_tests::scope::display::{Plot, Scale, DIVS_X, DIVS_Y};
+use dso138_tests::scope::export::{self, Header};
+use dso138_tests::scope::fft::{self, Decibels, Fft, Window};
+use dso138_tests::scope::mask::Envelope;
+use dso138_tests::scope::measure::{self, Hertz, Measurements, Micros, Millivolts};
+use dso138_tests::scope::roll::Roll;
+use dso138_tests::scope::trigger::{Config, Mode, Slope, Trigger};
+use dso138_tests::scope::xy::{Reference, Xy};
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_hal::digital::v2::InputPin;
+use embedded_hal::digital::v2::OutputPin;
+use fixed::types::{I16F16, U24F8};
+use hal::adc::Adc;
+use hal::gpio::gpioa::PA0;
+use hal::gpio::gpiob::{PB0, PB1, PB2, PB3, PB4, PB5, PB6, PB7};
+use hal::gpio::gpiob::{PB11, PB12, PB13, PB14, PB15};
+use hal::gpio::gpioc::{PC14, PC15};
+use hal::gpio::{Analog, Input, Output, PullUp, PushPull};
+use hal::prelude::*;
+use hal::stm32::TIM4;
+use hal::timer::CountDownTimer;
+use hal::timer::Event;
+use hal::timer::Timer;
+use ili9341::Ili9341;
+use panic_rtt_target as _;
+use rtic::app;
+use rtt_target::{rprintln, rtt_init, set_print_channel, UpChannel};
+use stm32f1xx_hal as hal;
+
+type DisplayType = Ili9341<
+    PGPIO8BitInterface<
+        PB0<Output<PushPull>>,
+        PB1<Output<PushPull>>,
+        PB2<Output<PushPull>>,
+        PB3<Output<PushPull>>,
+        PB4<Output<PushPull>>,
+        PB5<Output<PushPull>>,
+        PB6<Output<PushPull>>,
+        PB7<Output<PushPull>>,
+        PC14<Output<PushPull>>,
+        PC15<Output<PushPull>>,
+    >,
+    PB11<Output<PushPull>>,
+>;
+
+/* waveform area: 10 x 8 divisions of 24 pixels */
+const WIDTH: usize = 240;
+
+/* timebase: fixed number of samples per division, time/div is set by the sample rate */
+const SAMPLES_PER_DIV: u32 = 50;
+const FRAME: usize = DIVS_X as usize * SAMPLES_PER_DIV as usize;
+
+/* volts/div at the ADC input: 12-bit ADC, 3.3V reference */
+const VDIV_MV: [u32; 4] = [100, 200, 500, 1000];
+const VREF_MV: u32 = 3300;
+const ADC_MAX: u32 = 4096;
+
+/* trigger in the middle of the frame, ground level at the center line */
+const MIDDLE: u16 = 2048;
+
+const TRIGGER: Config = Config {
+    slope: Slope::Rising,
+    mode: Mode::Auto,
+    level: MIDDLE,
+    hysteresis: 40,
+    holdoff: 0,
+    pre: 50,
+    timeout: 0,
+};
+
+const RATE: SampleRate = SampleRate::KHz50;
+
+/* roll mode from 100ms/div: input is sampled at 20kHz, every column shows min/max of its samples */
+const ROLL_MS: u32 = 100;
+const ROLL_RATE: SampleRate = SampleRate::KHz20;
+
+/* test signal: probe calibration square wave by default */
+const SIGNAL: siggen::Config = siggen::Config {
+    waveform: Waveform::Square,
+    freq: 1000,
+    duty: 50,
+};
+
+const SIGNALS: [Option<Waveform>; 4] = [
+    None,
+    Some(Waveform::Square),
+    Some(Waveform::Sine),
+    Some(Waveform::Triangle),
+];
+
+const FREQS: [u32; 13] = [
+    10, 20, 50, 100, 200, 500, 1_000, 2_000, 5_000, 10_000, 20_000, 50_000, 100_000,
+];
+
+/* reference traces: frames decimated to 4 samples per point, mask tolerance in 1/100 of division */
+const REFS: usize = 3;
+const REF_POINTS: usize = FRAME / 4;
+const MASKS: [Option<u32>; 4] = [None, Some(10), Some(20), Some(50)];
+
+/* spectrum: the first WIDTH of BUF_LEN / 2 bins, 10dB/div from 0dB at the top */
+const BINS: usize = BUF_LEN / 2;
+const BINS_PER_DIV: u32 = WIDTH as u32 / DIVS_X as u32;
+const DB_PER_DIV: i32 = 10;
+const COUNTS_PER_DB: i32 = 10;
+const DB_RANGE: i32 = DB_PER_DIV * DIVS_Y;
+
+const SPECTRUM: Scale = Scale {
+    samples: BINS_PER_DIV,
+    counts: (DB_PER_DIV * COUNTS_PER_DB) as u32,
+    offset: (DB_RANGE * COUNTS_PER_DB / 2) as u16,
+};
+
+/* XY: probe input against reference sine at the test signal frequency, 4 divisions amplitude */
+const XY_POINTS: usize = BUF_LEN / 2;
+const PERSISTENCE: usize = 4;
+const XREF_AMPLITUDE: u16 = 1024;
+
+const XSCALE: Scale = Scale {
+    samples: SAMPLES_PER_DIV,
+    counts: XREF_AMPLITUDE as u32 / 4,
+    offset: MIDDLE,
+};
+
+// roll replaces the scope view on slow timebases, it is not selected directly
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum View {
+    Scope,
+    Roll,
+    Xy,
+    Spectrum(Window),
+}
+
+const VIEWS: [View; 5] = [
+    View::Scope,
+    View::Xy,
+    View::Spectrum(Window::Hann),
+    View::Spectrum(Window::Hamming),
+    View::Spectrum(Window::Rect),
+];
+
+// setting changed by buttons B1/B4, B2 selects the next one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Setting {
+    Timebase,
+    Volts,
+    Mode,
+    Slope,
+    View,
+    Signal,
+    Frequency,
+    Duty,
+    Reference,
+    Mask,
+}
+
+impl Setting {
+    fn next(self) -> Setting {
+        match self {
+            Setting::Timebase => Setting::Volts,
+            Setting::Volts => Setting::Mode,
+            Setting::Mode => Setting::Slope,
+            Setting::Slope => Setting::View,
+            Setting::View => Setting::Signal,
+            Setting::Signal => Setting::Frequency,
+            Setting::Frequency => Setting::Duty,
+            Setting::Duty => Setting::Reference,
+            Setting::Reference => Setting::Mask,
+            Setting::Mask => Setting::Timebase,
+        }
+    }
+}
+
+#[app(device = stm32f1xx_hal::stm32, peripherals = true)]
+const APP: () = {
+    struct Resources {
+        // early resources
+        #[init(false)]
+        cb1: bool,
+        #[init(false)]
+        cb2: bool,
+        #[init(false)]
+        cb3: bool,
+        #[init(false)]
+        cb4: bool,
+        #[init(0)]
+        vdiv: usize,
+        #[init(0)]
+        view: usize,
+        #[init(RATE)]
+        timebase: SampleRate,
+        #[init(false)]
+        rolling: bool,
+        #[init(Roll::new())]
+        roll: Roll<WIDTH>,
+        #[init(Setting::Timebase)]
+        setting: Setting,
+        #[init(false)]
+        fresh: bool,
+        #[init([0; BUF_LEN])]
+        frame: [u16; BUF_LEN],
+        #[init([0; BUF_LEN])]
+        trace: [u16; BUF_LEN],
+        #[init([I16F16::from_bits(0); BUF_LEN])]
+        re: [I16F16; BUF_LEN],
+        #[init([I16F16::from_bits(0); BUF_LEN])]
+        im: [I16F16; BUF_LEN],
+        #[init(0)]
+        frame_rate: u32,
+        #[init(None)]
+        frame_trigger: Option<usize>,
+        #[init(0)]
+        sequence: u16,
+        #[init([0; export::frame_len(BUF_LEN)])]
+        packet: [u8; export::frame_len(BUF_LEN)],
+        // reference phase at the start of the next half of the buffer and of the frame
+        #[init(0)]
+        phase: u32,
+        #[init(0)]
+        frame_phase: u32,
+        // shown reference, mask tolerance and the last mask test result: failed samples
+        #[init([Envelope::new(); REFS])]
+        refs: [Envelope<REF_POINTS>; REFS],
+        #[init(None)]
+        reference: Option<usize>,
+        #[init(0)]
+        mask: usize,
+        #[init(None)]
+        failed: Option<usize>,
+
+        // late resources
+        display: DisplayType,
+        export: UpChannel,
+        button1: PB12<Input<PullUp>>,
+        button2: PB13<Input<PullUp>>,
+        button3: PB14<Input<PullUp>>,
+        button4: PB15<Input<PullUp>>,
+        btmr: CountDownTimer<TIM4>,
+        capture: Capture<PA0<Analog>>,
+        siggen: SigGen,
+        xref: Reference,
+        xy: Xy<XY_POINTS, PERSISTENCE>,
+        trigger: Trigger<FRAME>,
+        plot: Plot<WIDTH>,
+        scale: Scale,
+        fft: Fft<BUF_LEN>,
+        hud: Hud<5>,
+        generator: Hud<5>,
+        status: [Hud<4>; 2],
+    }
+
+    #[init]
+    fn init(cx: init::Context) -> init::LateResources {
+        static mut BUF: Buffer = [[0; BUF_LEN]; 2];
+
+        // terminal and captured frames, the whole frame fits into the buffer or it is skipped
+        let channels = rtt_init! {
+            up: {
+                0: {
+                    size: 256
+                    name: "Terminal"
+                }
+                1: {
+                    size: 1024
+                    name: "Capture"
+                }
+            }
+        };
+
+        set_print_channel(channels.up.0);
+        let export = channels.up.1;
+
+        let mut rcc = cx.device.RCC.constrain();
+        let mut flash = cx.device.FLASH.constrain();
+        let mut afio = cx.device.AFIO.constrain(&mut rcc.apb2);
+
+        let clocks = rcc
+            .cfgr
+            .use_hse(8.mhz())
+            .sysclk(72.mhz())
+            .pclk1(32.mhz())
+            .adcclk(12.mhz())
+            .freeze(&mut flash.acr);
+
+        let mut gpioa = cx.device.GPIOA.split(&mut rcc.apb2);
+        let mut gpiob = cx.device.GPIOB.split(&mut rcc.apb2);
+        let mut gpioc = cx.device.GPIOC.split(&mut rcc.apb2);
+
+        let (_, pb3, pb4) = afio.mapr.disable_jtag(gpioa.pa15, gpiob.pb3, gpiob.pb4);
+
+        let mut btmr =
+            Timer::tim4(cx.device.TIM4, &clocks, &mut rcc.apb1).start_count_down(100.hz());
+        btmr.listen(Event::Update);
+
+        let dtmr = Timer::tim2(cx.device.TIM2, &clocks, &mut rcc.apb1)
+            .start_master(1000.khz(), hal::pac::tim2::cr2::MMS_A::RESET);
+
+        /* buttons */
+
+        let button1 = gpiob.pb12.into_pull_up_input(&mut gpiob.crh);
+        let button2 = gpiob.pb13.into_pull_up_input(&mut gpiob.crh);
+        let button3 = gpiob.pb14.into_pull_up_input(&mut gpiob.crh);
+        let button4 = gpiob.pb15.into_pull_up_input(&mut gpiob.crh);
+
+        /* display */
+
+        let mut delay = DelayTimer::new(dtmr);
+
+        let p0 = gpiob.pb0.into_push_pull_output(&mut gpiob.crl);
+        let p1 = gpiob.pb1.into_push_pull_output(&mut gpiob.crl);
+        let p2 = gpiob.pb2.into_push_pull_output(&mut gpiob.crl);
+        let p3 = pb3.into_push_pull_output(&mut gpiob.crl);
+        let p4 = pb4.into_push_pull_output(&mut gpiob.crl);
+        let p5 = gpiob.pb5.into_push_pull_output(&mut gpiob.crl);
+        let p6 = gpiob.pb6.into_push_pull_output(&mut gpiob.crl);
+        let p7 = gpiob.pb7.into_push_pull_output(&mut gpiob.crl);
+
+        let mut ncs = gpioc.pc13.into_push_pull_output(&mut gpioc.crh);
+        let mut nrd = gpiob.pb10.into_push_pull_output(&mut gpiob.crh);
+
+        let nreset = gpiob.pb11.into_push_pull_output(&mut gpiob.crh);
+        let nwr = gpioc.pc15.into_push_pull_output(&mut gpioc.crh);
+        let rs = gpioc.pc14.into_push_pull_output(&mut gpioc.crh);
+
+        ncs.set_low().unwrap();
+        nrd.set_high().unwrap();
+
+        let pio8bit = PGPIO8BitInterface::new(p0, p1, p2, p3, p4, p5, p6, p7, rs, nwr);
+        let mut display = Ili9341::new(pio8bit, nreset, &mut delay).unwrap();
+
+        let rot = Rotation::Landscape;
+        let screen = rot.size();
+
+        display.set_orientation(rot.orientation()).unwrap();
+        display.clear(Rgb565::BLACK).unwrap();
+
+        /* settings band on top of the screen: scope, test signal and reference, waveform area centered below it */
+
+        let mut hud = Hud::new(
+            0,
+            screen.width as i32,
+            [4, 70, 136, 196, 220],
+            Rgb565::WHITE,
+            Rgb565::BLUE,
+        );
+
+        let mut generator = Hud::new(
+            hud.bottom(),
+            screen.width as i32,
+            [4, 76, 148, 196, 238],
+            Rgb565::WHITE,
+            Rgb565::BLUE,
+        );
+
+        let mut plot = Plot::new(
+            (screen.width as i32 - Plot::<WIDTH>::width()) / 2,
+            generator.bottom() + 2,
+            Rgb565::YELLOW,
+            Rgb565::new(16, 32, 16),
+            Rgb565::BLACK,
+        );
+
+        let scale = Scale {
+            samples: SAMPLES_PER_DIV,
+            counts: counts_per_div(0),
+            offset: MIDDLE,
+        };
+
+        /* measurements in two status lines at the bottom */
+
+        let bottom = screen.height as i32 - 2 * Hud::<4>::height() - 2;
+        let mut status = [0, 1].map(|n| {
+            Hud::new(
+                bottom + n * Hud::<4>::height(),
+                screen.width as i32,
+                [4, 84, 164, 244],
+                Rgb565::WHITE,
+                Rgb565::BLACK,
+            )
+        });
+
+        plot.draw_grid(&mut display).unwrap();
+        hud.clear(&mut display).unwrap();
+        generator.clear(&mut display).unwrap();
+
+        for line in status.iter_mut() {
+            line.clear(&mut display).unwrap();
+        }
+
+        /* capture */
+
+        let probe = gpioa.pa0.into_analog(&mut gpioa.crl);
+        let adc = Adc::adc1(cx.device.ADC1, &mut rcc.apb2, clocks);
+        let dma = cx.device.DMA1.split(&mut rcc.ahb);
+        let ctmr = Timer::tim3(cx.device.TIM3, &clocks, &mut rcc.apb1);
+
+        let capture = Capture::new(adc, probe, dma.1, ctmr, &clocks, BUF, RATE);
+        let trigger = Trigger::new(trigger_config(TRIGGER, RATE));
+        let fft = Fft::new();
+
+        /* test signal */
+
+        let out = gpioa.pa8.into_alternate_push_pull(&mut gpioa.crh);
+        let stmr = Timer::tim1(cx.device.TIM1, &clocks, &mut rcc.apb2);
+        let siggen = SigGen::new(stmr, out, &clocks, SIGNAL);
+
+        let mut xref = Reference::new(XREF_AMPLITUDE, MIDDLE);
+        xref.set_frequency(siggen.get_frequency(), RATE.hz());
+
+        show_settings(
+            &mut hud,
+            Setting::Timebase,
+            &scale,
+            &trigger.get_config(),
+            RATE,
+            0,
+            VIEWS[0],
+        );
+        hud.draw(&mut display).unwrap();
+
+        show_generator(&mut generator, Setting::Timebase, &siggen);
+        show_reference(&mut generator, Setting::Timebase, None, 0, None);
+        generator.draw(&mut display).unwrap();
+
+        rprintln!("scope: {} Hz", RATE.hz());
+
+        /* init late resources */
+        init::LateResources {
+            display,
+            export,
+            button1,
+            button2,
+            button3,
+            button4,
+            btmr,
+            capture,
+            siggen,
+            xref,
+            xy: Xy::new(),
+            trigger,
+            plot,
+            scale,
+            fft,
+            hud,
+            generator,
+            status,
+        }
+    }
+
+    #[idle]
+    fn idle(_: idle::Context) -> ! {
+        loop {
+            cm::asm::nop();
+        }
+    }
+
+    // Completed half of the capture buffer: feed it to the trigger, pass new frames to render.
+    // XY and spectrum views need no trigger, the whole half of the buffer is the frame.
+    // XY reference phase follows the samples, a lost half of the buffer shifts it.
+    // In roll mode samples are appended to the columns, render is called for new columns.
+    #[task(binds = DMA1_CHANNEL1, priority = 2, spawn = [render], resources = [capture, trigger, frame, frame_rate, frame_trigger, fresh, view, rolling, roll, xref, phase, frame_phase])]
+    fn dma1_channel1(cx: dma1_channel1::Context) {
+        let capture = cx.resources.capture;
+        let trigger = cx.resources.trigger;
+        let frame = cx.resources.frame;
+        let fresh = cx.resources.fresh;
+        let view = active_view(*cx.resources.view, *cx.resources.rolling);
+        let roll = cx.resources.roll;
+        let xref = cx.resources.xref;
+        let phase = cx.resources.phase;
+        let frame_phase = cx.resources.frame_phase;
+        let frame_trigger = cx.resources.frame_trigger;
+        let mut ready = false;
+
+        capture
+            .read(|samples| match view {
+                View::Scope => {
+                    trigger.feed(samples, |f| {
+                        // previous frame is still on the way to the screen: skip this one
+                        if !*fresh {
+                            f.copy_to(frame);
+                            *frame_trigger = f.trigger();
+                            *fresh = true;
+                            ready = true;
+                        }
+                    });
+                }
+                View::Roll => {
+                    if roll.feed(samples) > 0 && !*fresh {
+                        *frame_trigger = None;
+                        *fresh = true;
+                        ready = true;
+                    }
+                }
+                View::Xy | View::Spectrum(_) => {
+                    if !*fresh {
+                        frame.copy_from_slice(samples);
+                        *frame_trigger = None;
+                        *frame_phase = *phase;
+                        *fresh = true;
+                        ready = true;
+                    }
+
+                    *phase = xref.advance(*phase, samples.len());
+                }
+            })
+            .ok();
+
+        if ready {
+            *cx.resources.frame_rate = capture.get_rate().hz();
+            cx.spawn.render().ok();
+        }
+    }
+
+    // next DDS sample of the test signal
+    #[task(binds = TIM1_UP, priority = 3, resources = [siggen])]
+    fn tim1_up(cx: tim1_up::Context) {
+        cx.resources.siggen.update();
+    }
+
+    #[task(resources = [display, plot, scale, hud, generator, status, frame, frame_rate, frame_trigger, fresh, trace, view, rolling, roll, vdiv, fft, re, im, xref, frame_phase, xy, export, packet, sequence, refs, reference, mask, failed, setting])]
+    fn render(mut cx: render::Context) {
+        let trace = cx.resources.trace;
+        let fresh = &mut cx.resources.fresh;
+        let frame_rate = &mut cx.resources.frame_rate;
+        let frame_trigger = &mut cx.resources.frame_trigger;
+        let view = &mut cx.resources.view;
+        let rolling = &mut cx.resources.rolling;
+        let xref = &mut cx.resources.xref;
+        let frame_phase = &mut cx.resources.frame_phase;
+        let mut rate = 0;
+        let mut trigger = None;
+        let mut shown = View::Scope;
+        let mut phase = 0;
+
+        cx.resources.frame.lock(|frame| {
+            trace.copy_from_slice(frame);
+            rate = frame_rate.lock(|r| *r);
+            trigger = frame_trigger.lock(|t| *t);
+            shown = active_view(view.lock(|v| *v), rolling.lock(|r| *r));
+            phase = frame_phase.lock(|p| *p);
+            fresh.lock(|fresh| *fresh = false);
+        });
+
+        /* captured samples to the host before the spectrum replaces them, roll mode has no frames */
+
+        if shown != View::Roll {
+            let header = Header {
+                sequence: *cx.resources.sequence,
+                rate,
+                vdiv: VDIV_MV[*cx.resources.vdiv] as u16,
+                vref: VREF_MV as u16,
+                offset: MIDDLE,
+                trigger: trigger.map(|t| t as u16),
+            };
+            let len = if shown == View::Scope { FRAME } else { BUF_LEN };
+            let packet = cx.resources.packet;
+
+            if let Ok(n) = export::encode(&header, &trace[..len], packet) {
+                cx.resources.export.write(&packet[..n]);
+            }
+
+            *cx.resources.sequence = cx.resources.sequence.wrapping_add(1);
+        }
+
+        let display = cx.resources.display;
+        let plot = cx.resources.plot;
+        let status = cx.resources.status;
+
+        match shown {
+            View::Scope => {
+                let trace = &trace[..FRAME];
+                let scale = cx.resources.scale;
+                let refs = cx.resources.refs;
+                let reference = *cx.resources.reference;
+                let mask = *cx.resources.mask;
+                let failed = cx.resources.failed;
+                let tolerance = MASKS[mask].map(|t| (scale.counts * t / 100) as u16);
+
+                // reference under the trace, widened by the mask tolerance; failed samples are marked
+                match reference.map(|r| &refs[r]) {
+                    Some(r) if !r.is_empty() => {
+                        let t = tolerance.unwrap_or(0);
+
+                        plot.draw_under(display, FRAME, scale, |j| r.bounds(j, t))
+                            .unwrap();
+
+                        if tolerance.is_some() {
+                            plot.draw_marked(display, trace, scale, |j| r.fails(trace, j, t))
+                                .unwrap();
+                            *failed = Some(r.test(trace, t));
+                        } else {
+                            plot.draw_trace(display, trace, scale).unwrap();
+                            *failed = None;
+                        }
+                    }
+                    _ => {
+                        plot.clear_under(display).unwrap();
+                        plot.draw_trace(display, trace, scale).unwrap();
+                        *failed = None;
+                    }
+                }
+
+                show_measurements(status, &measure::measure(trace, MIDDLE), rate);
+                show_reference(
+                    cx.resources.generator,
+                    *cx.resources.setting,
+                    reference,
+                    mask,
+                    *failed,
+                );
+                cx.resources.generator.draw(display).unwrap();
+            }
+            View::Roll => {
+                // columns (min, max) from the oldest one replace the samples, the newest is at the right edge
+                let len = cx.resources.roll.lock(|r| {
+                    for (i, (lo, hi)) in r.iter().enumerate() {
+                        trace[2 * i] = lo;
+                        trace[2 * i + 1] = hi;
+                    }
+
+                    r.len()
+                });
+                let columns = &trace[..2 * len];
+                let empty = WIDTH - len;
+                let ranges = (0..WIDTH).map(|c| {
+                    c.checked_sub(empty)
+                        .map(|k| (columns[2 * k], columns[2 * k + 1]))
+                });
+
+                plot.clear_under(display).unwrap();
+                plot.draw_columns(display, ranges, cx.resources.scale)
+                    .unwrap();
+                show_roll(status, columns);
+            }
+            View::Xy => {
+                let xref = xref.lock(|r| *r);
+                let points = xref
+                    .iter(phase)
+                    .zip(trace.iter().copied())
+                    .step_by(BUF_LEN / XY_POINTS);
+
+                cx.resources
+                    .xy
+                    .draw(display, plot, points, &XSCALE, cx.resources.scale)
+                    .unwrap();
+                show_measurements(status, &measure::measure(trace, MIDDLE), rate);
+            }
+            View::Spectrum(w) => {
+                let (re, im) = (cx.resources.re, cx.resources.im);
+
+                cx.resources.fft.load(trace, w, re, im);
+                cx.resources.fft.transform(re, im);
+
+                // levels replace the first half of the transform, plot counts replace the samples
+                for k in 0..BINS {
+                    re[k] = fft::db(re[k], im[k], w);
+                    trace[k] = level_counts(re[k]);
+                }
+
+                plot.draw_trace(display, &trace[..BINS], &SPECTRUM).unwrap();
+                show_spectrum(status, fft::peak(&re[..BINS]), rate);
+            }
+        }
+
+        cx.resources.hud.draw(display).unwrap();
+
+        for line in status.iter_mut() {
+            line.draw(display).unwrap();
+        }
+    }
+
+    #[task(binds = TIM4, resources = [btmr, button1, cb1, button2, cb2, button3, cb3, button4, cb4, display, capture, siggen, xref, trigger, plot, xy, scale, hud, generator, vdiv, view, timebase, rolling, roll, setting, trace, refs, reference, mask, failed])]
+    fn tim4(mut cx: tim4::Context) {
+        let mut b1 = false;
+        let mut b2 = false;
+        let mut b3 = false;
+        let mut b4 = false;
+
+        if cx.resources.button1.is_low().unwrap() {
+            if !*cx.resources.cb1 {
+                *cx.resources.cb1 = true;
+                b1 = true;
+            }
+        } else if *cx.resources.cb1 {
+            *cx.resources.cb1 = false;
+        }
+
+        if cx.resources.button2.is_low().unwrap() {
+            if !*cx.resources.cb2 {
+                *cx.resources.cb2 = true;
+                b2 = true;
+            }
+        } else if *cx.resources.cb2 {
+            *cx.resources.cb2 = false;
+        }
+
+        if cx.resources.button3.is_low().unwrap() {
+            if !*cx.resources.cb3 {
+                *cx.resources.cb3 = true;
+                b3 = true;
+            }
+        } else if *cx.resources.cb3 {
+            *cx.resources.cb3 = false;
+        }
+
+        if cx.resources.button4.is_low().unwrap() {
+            if !*cx.resources.cb4 {
+                *cx.resources.cb4 = true;
+                b4 = true;
+            }
+        } else if *cx.resources.cb4 {
+            *cx.resources.cb4 = false;
+        }
+
+        cx.resources.btmr.clear_update_interrupt_flag();
+
+        if !(b1 || b2 || b3 || b4) {
+            return;
+        }
+
+        /* B2: select setting, B1/B4: change it down/up, B3: arm single trigger or store the reference */
+
+        let timebase = cx.resources.timebase;
+        let mut rate = *timebase;
+        let mut cfg = cx.resources.trigger.lock(|t| t.get_config());
+        let mut view = cx.resources.view.lock(|v| *v);
+        let setting = cx.resources.setting;
+        let vdiv = cx.resources.vdiv;
+        let reference = cx.resources.reference;
+        let mask = cx.resources.mask;
+
+        if b2 {
+            *setting = setting.next();
+        }
+
+        if b1 || b4 {
+            match setting {
+                Setting::Timebase => {
+                    rate = if b1 { rate.slower() } else { rate.faster() };
+                }
+                Setting::Volts => {
+                    *vdiv = if b1 {
+                        vdiv.saturating_sub(1)
+                    } else {
+                        (*vdiv + 1).min(VDIV_MV.len() - 1)
+                    };
+                    cx.resources.scale.counts = counts_per_div(*vdiv);
+                }
+                Setting::Mode => {
+                    cfg.mode = match (cfg.mode, b1) {
+                        (Mode::Auto, false) | (Mode::Single, true) => Mode::Normal,
+                        (Mode::Normal, false) | (Mode::Auto, true) => Mode::Single,
+                        (Mode::Single, false) | (Mode::Normal, true) => Mode::Auto,
+                    };
+                }
+                Setting::Slope => {
+                    cfg.slope = match cfg.slope {
+                        Slope::Rising => Slope::Falling,
+                        Slope::Falling => Slope::Rising,
+                    };
+                }
+                Setting::View => {
+                    view = if b1 {
+                        (view + VIEWS.len() - 1) % VIEWS.len()
+                    } else {
+                        (view + 1) % VIEWS.len()
+                    };
+                    cx.resources.view.lock(|v| *v = view);
+
+                    // trace and XY points are drawn differently, erase both
+                    let display = &mut *cx.resources.display;
+                    let plot = &mut *cx.resources.plot;
+
+                    plot.clear(display).unwrap();
+                    plot.clear_under(display).unwrap();
+                    cx.resources.xy.clear(display, plot).unwrap();
+                }
+                Setting::Signal => {
+                    cx.resources.siggen.lock(|g| {
+                        let current = if g.is_running() {
+                            Some(g.get_config().waveform)
+                        } else {
+                            None
+                        };
+                        let i = SIGNALS.iter().position(|s| *s == current).unwrap_or(0);
+                        let i = if b1 {
+                            (i + SIGNALS.len() - 1) % SIGNALS.len()
+                        } else {
+                            (i + 1) % SIGNALS.len()
+                        };
+
+                        match SIGNALS[i] {
+                            Some(waveform) => {
+                                g.set_config(siggen::Config {
+                                    waveform,
+                                    ..g.get_config()
+                                });
+                                g.start();
+                            }
+                            None => g.stop(),
+                        }
+                    });
+                }
+                Setting::Frequency => {
+                    cx.resources.siggen.lock(|g| {
+                        let cfg = g.get_config();
+                        let i = FREQS.iter().position(|f| *f >= cfg.freq).unwrap_or(0);
+                        let i = if b1 {
+                            i.saturating_sub(1)
+                        } else {
+                            (i + 1).min(FREQS.len() - 1)
+                        };
+
+                        g.set_config(siggen::Config {
+                            freq: FREQS[i],
+                            ..cfg
+                        });
+                    });
+                }
+                Setting::Duty => {
+                    cx.resources.siggen.lock(|g| {
+                        let cfg = g.get_config();
+                        let duty = if b1 { cfg.duty - 10 } else { cfg.duty + 10 };
+
+                        g.set_config(siggen::Config {
+                            duty: duty.clamp(10, 90),
+                            ..cfg
+                        });
+                    });
+                }
+                Setting::Reference => {
+                    let r = reference.map_or(0, |r| r + 1);
+                    let r = if b1 {
+                        (r + REFS) % (REFS + 1)
+                    } else {
+                        (r + 1) % (REFS + 1)
+                    };
+
+                    *reference = r.checked_sub(1);
+                    *cx.resources.failed = None;
+                }
+                Setting::Mask => {
+                    *mask = if b1 {
+                        (*mask + MASKS.len() - 1) % MASKS.len()
+                    } else {
+                        (*mask + 1) % MASKS.len()
+                    };
+                    *cx.resources.failed = None;
+                }
+            }
+        }
+
+        // the last shown scope trace becomes the selected reference
+        let store = b3 && *setting == Setting::Reference && reference.is_some();
+
+        if let (true, Some(r), View::Scope) = (store, *reference, VIEWS[view]) {
+            cx.resources.refs[r].store(&cx.resources.trace[..FRAME]);
+            *cx.resources.failed = None;
+        }
+
+        // roll mode on slow timebases in scope view, restarted with an empty trace on timebase change
+        let rolling = VIEWS[view] == View::Scope && rolls(rate);
+        let was_rolling = cx.resources.rolling.lock(|r| *r);
+
+        if rolling != was_rolling || (rolling && rate != *timebase) {
+            let display = &mut *cx.resources.display;
+
+            cx.resources.roll.lock(|r| {
+                r.set_period(
+                    ROLL_RATE.hz() * SAMPLES_PER_DIV,
+                    rate.hz() * Plot::<WIDTH>::div() as u32,
+                )
+            });
+            cx.resources.plot.clear(display).unwrap();
+            cx.resources.rolling.lock(|r| *r = rolling);
+        }
+
+        *timebase = rate;
+
+        let rate = if rolling { ROLL_RATE } else { rate };
+
+        cx.resources.capture.lock(|c| {
+            if c.get_rate() != rate {
+                c.set_rate(rate);
+            }
+        });
+
+        // XY reference follows the actual test signal frequency
+        let freq = cx.resources.siggen.lock(|g| g.get_frequency());
+        cx.resources.xref.lock(|r| r.set_frequency(freq, rate.hz()));
+
+        cfg = trigger_config(cfg, rate);
+        cx.resources.trigger.lock(|t| {
+            t.set_config(cfg);
+
+            if b3 && !store {
+                t.arm();
+            }
+        });
+
+        let hud = cx.resources.hud;
+        show_settings(
+            hud,
+            *setting,
+            cx.resources.scale,
+            &cfg,
+            *timebase,
+            *vdiv,
+            active_view(view, rolling),
+        );
+        hud.draw(cx.resources.display).unwrap();
+
+        let generator = cx.resources.generator;
+        cx.resources
+            .siggen
+            .lock(|g| show_generator(generator, *setting, g));
+        show_reference(generator, *setting, *reference, *mask, *cx.resources.failed);
+        generator.draw(cx.resources.display).unwrap();
+    }
+
+    // needed for RTIC software tasks
+    extern "C" {
+        fn EXTI0();
+    }
+};
+
+fn counts_per_div(vdiv: usize) -> u32 {
+    VDIV_MV[vdiv] * ADC_MAX / VREF_MV
+}
+
+// view on the screen for the selected one
+fn active_view(view: usize, rolling: bool) -> View {
+    if rolling {
+        View::Roll
+    } else {
+        VIEWS[view]
+    }
+}
+
+fn rolls(rate: SampleRate) -> bool {
+    SAMPLES_PER_DIV * 1000 / rate.hz() >= ROLL_MS
+}
+
+// auto mode shows an untriggered frame after about 100 msec without trigger
+fn trigger_config(cfg: Config, rate: SampleRate) -> Config {
+    Config {
+        timeout: rate.hz() / 10,
+        ..cfg
+    }
+}
+
+// spectrum level in dB to plot counts, 0 at the bottom of the area
+fn level_counts(db: I16F16) -> u16 {
+    ((db.to_num::<i32>() + DB_RANGE) * COUNTS_PER_DB).max(0) as u16
+}
+
+// selected setting is marked by '>'
+fn mark(s: Setting, selected: Setting) -> char {
+    if s == selected {
+        '>'
+    } else {
+        ' '
+    }
+}
+
+fn show_settings(
+    hud: &mut Hud<5>,
+    setting: Setting,
+    scale: &Scale,
+    cfg: &Config,
+    rate: SampleRate,
+    vdiv: usize,
+    view: View,
+) {
+    let mark = |s: Setting| mark(s, setting);
+
+    // time/div in microseconds
+    let us = scale.samples * 1_000_000 / rate.hz();
+    let mv = VDIV_MV[vdiv];
+
+    let frac = us % 1000 / 100;
+    let m = mark(Setting::Timebase);
+
+    if us < 1000 {
+        hud.set(0, format_args!("{}{}us/div", m, us));
+    } else if frac > 0 {
+        hud.set(0, format_args!("{}{}.{}ms/div", m, us / 1000, frac));
+    } else {
+        hud.set(0, format_args!("{}{}ms/div", m, us / 1000));
+    }
+
+    let m = mark(Setting::Volts);
+
+    if mv < 1000 {
+        hud.set(1, format_args!("{}{}mV/div", m, mv));
+    } else {
+        hud.set(1, format_args!("{}{}V/div", m, mv / 1000));
+    }
+
+    let mode = match cfg.mode {
+        Mode::Auto => "AUTO",
+        Mode::Normal => "NORM",
+        Mode::Single => "SINGLE",
+    };
+
+    let slope = match cfg.slope {
+        Slope::Rising => "/",
+        Slope::Falling => "\\",
+    };
+
+    hud.set(2, format_args!("{}T:{}", mark(Setting::Mode), mode));
+    hud.set(3, format_args!("{}{}", mark(Setting::Slope), slope));
+
+    let view = match view {
+        View::Scope => "SCOPE",
+        View::Roll => "ROLL",
+        View::Xy => "XY",
+        View::Spectrum(Window::Hann) => "FFT HANN",
+        View::Spectrum(Window::Hamming) => "FFT HAMMING",
+        View::Spectrum(Window::Rect) => "FFT RECT",
+    };
+
+    hud.set(4, format_args!("{}{}", mark(Setting::View), view));
+}
+
+fn show_generator(generator: &mut Hud<5>, setting: Setting, siggen: &SigGen) {
+    let cfg = siggen.get_config();
+
+    let signal = match (siggen.is_running(), cfg.waveform) {
+        (false, _) => "OFF",
+        (true, Waveform::Square) => "SQUARE",
+        (true, Waveform::Sine) => "SINE",
+        (true, Waveform::Triangle) => "TRIANGLE",
+    };
+
+    generator.set(
+        0,
+        format_args!("{}G:{}", mark(Setting::Signal, setting), signal),
+    );
+    generator.set(
+        1,
+        format_args!(
+            "{}{}",
+            mark(Setting::Frequency, setting),
+            Hertz(siggen.get_frequency())
+        ),
+    );
+    generator.set(
+        2,
+        format_args!("{}D:{}%", mark(Setting::Duty, setting), cfg.duty),
+    );
+}
+
+fn show_reference(
+    generator: &mut Hud<5>,
+    setting: Setting,
+    reference: Option<usize>,
+    mask: usize,
+    failed: Option<usize>,
+) {
+    let m = mark(Setting::Reference, setting);
+
+    match reference {
+        Some(r) => generator.set(3, format_args!("{}R:{}", m, r + 1)),
+        None => generator.set(3, format_args!("{}R:OFF", m)),
+    };
+
+    let m = mark(Setting::Mask, setting);
+
+    // tolerance in divisions and test result: PASS or number of failed samples
+    match MASKS[mask] {
+        None => generator.set(4, format_args!("{}M:OFF", m)),
+        Some(t) => {
+            let (div, tenths) = (t / 100, t % 100 / 10);
+
+            match failed {
+                None => generator.set(4, format_args!("{}M:{}.{} -", m, div, tenths)),
+                Some(0) => generator.set(4, format_args!("{}M:{}.{} PASS", m, div, tenths)),
+                Some(n) => generator.set(4, format_args!("{}M:{}.{} F:{}", m, div, tenths, n)),
+            }
+        }
+    };
+}
+
+fn show_spectrum(status: &mut [Hud<4>; 2], peak: Option<(U24F8, I16F16)>, rate: u32) {
+    match peak {
+        Some((pos, db)) => {
+            let f = fft::frequency(pos, rate, BUF_LEN);
+
+            status[0].set(0, format_args!("Fpk:{}", Hertz(f)));
+            status[0].set(1, format_args!("Pk:{}", Decibels(db)));
+        }
+        None => {
+            status[0].set(0, format_args!("Fpk:-"));
+            status[0].set(1, format_args!("Pk:-"));
+        }
+    }
+
+    let bin = fft::frequency(U24F8::from_num(1), rate, BUF_LEN);
+    let div = fft::frequency(U24F8::from_num(BINS_PER_DIV), rate, BUF_LEN);
+
+    status[0].set(2, format_args!("{}dB/div", DB_PER_DIV));
+    status[0].set(3, format_args!("{}/div", Hertz(div)));
+    status[1].set(0, format_args!("Bin:{}", Hertz(bin)));
+
+    for n in 1..4 {
+        status[1].set(n, format_args!(""));
+    }
+}
+
+// signed ADC counts to millivolts
+fn mv(counts: i32) -> Millivolts {
+    let v = measure::millivolts(counts.unsigned_abs(), ADC_MAX, VREF_MV) as i32;
+    Millivolts(if counts < 0 { -v } else { v })
+}
+
+// roll mode: range of the shown columns (min, max)
+fn show_roll(status: &mut [Hud<4>; 2], columns: &[u16]) {
+    let lo = columns.iter().step_by(2).min();
+    let hi = columns.iter().skip(1).step_by(2).max();
+
+    match (lo, hi) {
+        (Some(lo), Some(hi)) => {
+            status[0].set(0, format_args!("Vmax:{}", mv(*hi as i32 - MIDDLE as i32)));
+            status[0].set(1, format_args!("Vmin:{}", mv(*lo as i32 - MIDDLE as i32)));
+            status[0].set(2, format_args!("Vpp:{}", mv(*hi as i32 - *lo as i32)));
+        }
+        _ => {
+            status[0].set(0, format_args!("Vmax:-"));
+            status[0].set(1, format_args!("Vmin:-"));
+            status[0].set(2, format_args!("Vpp:-"));
+        }
+    }
+
+    status[0].set(3, format_args!(""));
+
+    for n in 0..4 {
+        status[1].set(n, format_args!(""));
+    }
+}
+
+fn show_measurements(status: &mut [Hud<4>; 2], m: &Measurements, rate: u32) {
+    match m.frequency(rate) {
+        Some(f) => status[0].set(0, format_args!("F:{}", Hertz(f))),
+        None => status[0].set(0, format_args!("F:-")),
+    };
+
+    match m.period_us(rate) {
+        Some(t) => status[0].set(1, format_args!("T:{}", Micros(t))),
+        None => status[0].set(1, format_args!("T:-")),
+    };
+
+    match m.duty {
+        Some(d) => status[0].set(
+            2,
+            format_args!(
+                "D:{}.{}%",
+                d.to_num::<u32>(),
+                (d.frac().to_bits() * 10) >> 8
+            ),
+        ),
+        None => status[0].set(2, format_args!("D:-")),
+    };
+
+    status[0].set(3, format_args!("Vpp:{}", mv(m.vpp() as i32)));
+    status[1].set(0, format_args!("Vavg:{}", mv(m.mean)));
+    status[1].set(1, format_args!("Vrms:{}", mv(m.rms as i32)));
+
+    match m.rise_us(rate) {
+        Some(t) => status[1].set(2, format_args!("Tr:{}", Micros(t))),
+        None => status[1].set(2, format_args!("Tr:-")),
+    };
+
+    match m.fall_us(rate) {
+        Some(t) => status[1].set(3, format_args!("Tf:{}", Micros(t))),
+        None => status[1].set(3, format_args!("Tf:-")),
+    };
+}
@@ -2,18 +2,31 @@
 #![no_main]
 #![no_std]
 
+use core::fmt::Write;
 use cortex_m as cm;
+use cortex_m::peripheral::DWT;
 use display_interface_parallel_gpio::PGPIO8BitInterface;
+use dso138_tests::game::clock::Clock;
+use dso138_tests::game::scores::{Entry, HighScores, Initials, TABLE_BYTES};
+use dso138_tests::game::squash::{Rules, Squash};
+use dso138_tests::game::state::{self, Hooks, Machine, State};
+use dso138_tests::gfx::hud::{Hud, TextBuf};
+use dso138_tests::gfx::sprite::assets::{BALL, RACKET, TITLE};
+use dso138_tests::gfx::viewport::{Rotation, Viewport};
 use dso138_tests::hw::delay_timer::DelayTimer;
+use dso138_tests::hw::eeprom::Store;
+use dso138_tests::phys::ai::{Ai, Skill};
+use dso138_tests::phys::balls::Balls;
 use dso138_tests::phys::particles::{Particle, ParticleColor};
-use dso138_tests::phys::racket::Racket;
-use embedded_graphics::fonts::{Font12x16, Text};
+use dso138_tests::phys::racket::{Intent, Kinematics, Racket};
+use embedded_graphics::fonts::{Font12x16, Font6x8, Text};
 use embedded_graphics::pixelcolor::Rgb565;
 use embedded_graphics::prelude::*;
 use embedded_graphics::primitives::Rectangle;
 use embedded_graphics::style::{PrimitiveStyle, TextStyleBuilder};
 use embedded_hal::digital::v2::InputPin;
 use embedded_hal::digital::v2::OutputPin;
+use hal::flash;
 use hal::gpio::gpioa::PA15;
 use hal::gpio::gpiob::{PB0, PB1, PB2, PB3, PB4, PB5, PB6, PB7};
 use hal::gpio::gpiob::{PB11, PB12, PB13, PB14, PB15};
@@ -24,11 +37,10 @@ use hal::stm32::TIM3;
 use hal::timer::CountDownTimer;
 use hal::timer::Event;
 use hal::timer::Timer;
-use ili9341::{Ili9341, Orientation};
+use ili9341::Ili9341;
 use panic_rtt_target as _;
 use rtic::app;
 use rtic::cyccnt::Instant;
-use rtic::cyccnt::U32Ext;
 use rtt_target::{rprintln, rtt_init_print};
 use stm32f1xx_hal as hal;
 
@@ -50,7 +62,50 @@ type DisplayType = Ili9341<
 
 /* cpu sysclk: 72 MHz (no external quartz) */
 
-const STEP_PERIOD: u32 = 72_0000; /* 10 msec */
+const SYSCLK: u32 = 72_000_000;
+
+/* physics step: 10 msec, at most 5 steps per frame */
+const STEP_PERIOD: u32 = 72_0000;
+const MAX_STEPS: u32 = 5;
+
+/* ball deflection at the racket edges */
+const SPIN: f32 = 1.0;
+
+/* fraction of racket velocity passed to the ball */
+const DRAG: f32 = 0.2;
+
+/* racket acceleration, max speed, friction per step and simulation time step */
+const KINEMATICS: Kinematics<f32> = Kinematics {
+    acc: 50.0,
+    vmax: 50.0,
+    friction: 0.05,
+    dt: 0.1,
+};
+
+const RULES: Rules<f32> = Rules {
+    lives: 3,
+    points: 10,
+    bonus: 50,
+    returns: 5,
+    speedup: 1.2,
+    shrink: 2.0,
+    hw_min: 7.0,
+};
+
+/* balls in play: a new ball is added on every new level */
+const MAX_BALLS: usize = 3;
+const BALL_R: f32 = 5.0;
+
+/* computer player in pong mode */
+const SKILL: Skill<f32> = Skill {
+    delay: 20,
+    vmax: 40.0,
+    error: 17.0,
+    slack: 2.0,
+};
+
+/* high-score table key in the flash store */
+const KEY_SCORES: u16 = 1;
 
 #[app(device = stm32f1xx_hal::stm32, peripherals = true, monotonic = rtic::cyccnt::CYCCNT)]
 const APP: () = {
@@ -64,6 +119,20 @@ const APP: () = {
         cb3: bool,
         #[init(false)]
         cb4: bool,
+        #[init(false)]
+        select: bool,
+        #[init(false)]
+        quit: bool,
+        #[init(false)]
+        up: bool,
+        #[init(false)]
+        down: bool,
+        #[init(None)]
+        editor: Option<Initials>,
+        #[init(None)]
+        ai: Option<Ai<f32>>,
+        #[init(Shown { balls: [None; MAX_BALLS], racket: None, cpu: None })]
+        shown: Shown,
 
         // late resources
         display: DisplayType,
@@ -73,11 +142,19 @@ const APP: () = {
         button4: PB15<Input<PullUp>>,
         led: PA15<Output<PushPull>>,
         btmr: CountDownTimer<TIM3>,
-        ball: Particle<f32>,
+        balls: Balls<f32, MAX_BALLS>,
         racket: Racket<f32>,
+        cpu: Racket<f32>,
+        hud: Hud<3>,
+        vp: Viewport<f32>,
+        logic: Squash<f32>,
+        game: Machine,
+        store: Store<flash::Parts>,
+        scores: HighScores,
+        clock: Clock,
     }
 
-    #[init(schedule = [step_task])]
+    #[init(schedule = [frame_task])]
     fn init(mut cx: init::Context) -> init::LateResources {
         rtt_init_print!();
 
@@ -96,6 +173,18 @@ const APP: () = {
         cx.core.DCB.enable_trace();
         cx.core.DWT.enable_cycle_counter();
 
+        /* high scores */
+
+        let mut store = Store::mount(flash).unwrap();
+        let mut buf = [0u8; TABLE_BYTES];
+        let mut scores = store
+            .read(KEY_SCORES, &mut buf)
+            .ok()
+            .and_then(|n| HighScores::from_bytes(&buf[..n]))
+            .unwrap_or_default();
+
+        rprintln!("high scores: {}", scores.len());
+
         let mut gpioa = cx.device.GPIOA.split(&mut rcc.apb2);
         let mut gpiob = cx.device.GPIOB.split(&mut rcc.apb2);
         let mut gpioc = cx.device.GPIOC.split(&mut rcc.apb2);
@@ -146,34 +235,58 @@ const APP: () = {
         let pio8bit = PGPIO8BitInterface::new(p0, p1, p2, p3, p4, p5, p6, p7, rs, nwr);
         let mut display = Ili9341::new(pio8bit, nreset, &mut delay).unwrap();
 
-        display
-            .set_orientation(Orientation::PortraitFlipped)
-            .unwrap();
+        let rot = Rotation::PortraitFlipped;
+        let screen = rot.size();
 
-        /* create ball and racket */
+        display.set_orientation(rot.orientation()).unwrap();
 
-        let ball = Particle::<f32>::new(120.0, 160.0, 10.0, 5.0, 5.0, 0.1, ParticleColor::Blue);
-        let racket = Racket::<f32>::new(120.0, 5.0, 15.0, 5.0);
+        /* game objects */
 
-        /* initial screen */
+        let mut balls = Balls::new();
+        let mut racket = new_racket();
+        let mut logic = new_logic();
 
-        let ground = PrimitiveStyle::with_fill(Rgb565::BLACK);
-        let color = PrimitiveStyle::with_fill(Rgb565::GREEN);
+        /* score band on top of the screen, game field below it */
 
-        Rectangle::new(
-            Point::new(0, 0),
-            Point::new(display.width() as i32, display.height() as i32),
-        )
-        .into_styled(ground)
-        .draw(&mut display)
-        .unwrap();
+        let mut hud = Hud::new(
+            0,
+            screen.width as i32,
+            [0, 100, 180],
+            Rgb565::WHITE,
+            Rgb565::BLUE,
+        );
 
-        Rectangle::new(racket_square(&racket).0, racket_square(&racket).1)
-            .into_styled(color)
-            .draw(&mut display)
-            .unwrap();
+        let vp = Viewport::new(
+            rot,
+            Point::new(0, hud.bottom()),
+            Size::new(screen.width, screen.height - hud.bottom() as u32),
+            1.0,
+        );
+
+        let mut cpu = new_cpu(&vp);
+
+        /* title screen */
 
-        cx.schedule.step_task(Instant::now()).unwrap();
+        let game = Machine::new();
+
+        game.start(&mut Scene {
+            display: &mut display,
+            balls: &mut balls,
+            racket: &mut racket,
+            cpu: &mut cpu,
+            ai: &mut None,
+            hud: &mut hud,
+            logic: &mut logic,
+            vp: &vp,
+            store: &mut store,
+            scores: &mut scores,
+            editor: &mut None,
+            shown: &mut Shown::default(),
+        });
+
+        let clock = Clock::new(SYSCLK, STEP_PERIOD, MAX_STEPS, DWT::get_cycle_count());
+
+        cx.schedule.frame_task(Instant::now()).unwrap();
 
         /* init late resources */
         init::LateResources {
@@ -183,9 +296,17 @@ const APP: () = {
             button3,
             button4,
             led,
-            ball,
+            balls,
             btmr,
             racket,
+            cpu,
+            hud,
+            vp,
+            logic,
+            game,
+            store,
+            scores,
+            clock,
         }
     }
 
@@ -196,11 +317,12 @@ const APP: () = {
         }
     }
 
-    #[task(binds = TIM3, resources = [btmr, button1, cb1, button2, cb2, button3, cb3, button4, cb4])]
+    #[task(binds = TIM3, resources = [btmr, button1, cb1, button2, cb2, button3, cb3, button4, cb4, select, quit, up, down])]
     fn tim3(cx: tim3::Context) {
         if cx.resources.button1.is_low().unwrap() {
             if !*cx.resources.cb1 {
                 *cx.resources.cb1 = true;
+                *cx.resources.up = true;
             }
         } else if *cx.resources.cb1 {
             *cx.resources.cb1 = false;
@@ -209,6 +331,7 @@ const APP: () = {
         if cx.resources.button2.is_low().unwrap() {
             if !*cx.resources.cb2 {
                 *cx.resources.cb2 = true;
+                *cx.resources.select = true;
             }
         } else if *cx.resources.cb2 {
             *cx.resources.cb2 = false;
@@ -217,6 +340,7 @@ const APP: () = {
         if cx.resources.button3.is_low().unwrap() {
             if !*cx.resources.cb3 {
                 *cx.resources.cb3 = true;
+                *cx.resources.quit = true;
             }
         } else if *cx.resources.cb3 {
             *cx.resources.cb3 = false;
@@ -225,6 +349,7 @@ const APP: () = {
         if cx.resources.button4.is_low().unwrap() {
             if !*cx.resources.cb4 {
                 *cx.resources.cb4 = true;
+                *cx.resources.down = true;
             }
         } else if *cx.resources.cb4 {
             *cx.resources.cb4 = false;
@@ -233,114 +358,644 @@ const APP: () = {
         cx.resources.btmr.clear_update_interrupt_flag();
     }
 
-    #[task(schedule = [step_task], resources = [display, ball, cb1, cb4, racket])]
-    fn step_task(cx: step_task::Context) {
-        let ground = PrimitiveStyle::with_fill(Rgb565::BLACK);
-        let color1 = PrimitiveStyle::with_fill(Rgb565::GREEN);
-        let color2 = PrimitiveStyle::with_fill(Rgb565::RED);
-        let height = cx.resources.display.height() as i32;
-        let width = cx.resources.display.width() as i32;
-        let display = cx.resources.display;
-        let racket = cx.resources.racket;
-        let ball = cx.resources.ball;
-
-        let dx: Option<f32> = match (*cx.resources.cb1, *cx.resources.cb4) {
-            (true, false) => Some(5.0),
-            (false, true) => Some(-5.0),
-            _ => None,
+    // physics runs at fixed steps, frames are rendered as fast as possible
+    #[task(schedule = [frame_task], resources = [display, balls, cb1, cb4, select, quit, up, down, racket, cpu, ai, hud, logic, vp, game, store, scores, editor, shown, clock])]
+    fn frame_task(cx: frame_task::Context) {
+        let game = cx.resources.game;
+        let mut scene = Scene {
+            display: cx.resources.display,
+            balls: cx.resources.balls,
+            racket: cx.resources.racket,
+            cpu: cx.resources.cpu,
+            ai: cx.resources.ai,
+            hud: cx.resources.hud,
+            logic: cx.resources.logic,
+            vp: cx.resources.vp,
+            store: cx.resources.store,
+            scores: cx.resources.scores,
+            editor: cx.resources.editor,
+            shown: cx.resources.shown,
         };
 
-        Rectangle::new(ball_square(ball).0, ball_square(ball).1)
-            .into_styled(ground)
-            .draw(display)
+        let intent = match (*cx.resources.cb1, *cx.resources.cb4) {
+            (true, false) => Intent::Right,
+            (false, true) => Intent::Left,
+            (true, true) => Intent::Brake,
+            (false, false) => Intent::Idle,
+        };
+
+        let was = game.state();
+        let up = core::mem::replace(cx.resources.up, false);
+        let down = core::mem::replace(cx.resources.down, false);
+        let select = core::mem::replace(cx.resources.select, false);
+
+        // select button enters initials while a new high score is edited
+        if !scene.edit(up, down, select) && select {
+            game.handle(state::Event::Select, &mut scene);
+        }
+
+        if game.state() == State::Title && (up || down) {
+            scene.toggle_mode();
+        }
+
+        if *cx.resources.quit {
+            *cx.resources.quit = false;
+            game.handle(state::Event::Quit, &mut scene);
+        }
+
+        let clock = cx.resources.clock;
+
+        // the clock only runs while playing: time spent on the title, pause and
+        // game over screens is dropped instead of being simulated on return
+        if game.state() == State::Playing {
+            if was != State::Playing {
+                clock.reset(DWT::get_cycle_count());
+            }
+
+            for _ in 0..clock.tick(DWT::get_cycle_count()) {
+                if game.state() != State::Playing {
+                    break;
+                }
+
+                if scene.update(intent) {
+                    if scene.logic.on_miss() {
+                        game.handle(state::Event::Lost, &mut scene);
+                    } else {
+                        scene.serve();
+                    }
+                }
+            }
+
+            if game.state() == State::Playing {
+                scene.render();
+            }
+
+            if let Some(stats) = clock.take_stats() {
+                rprintln!(
+                    "fps: {} steps: {} skipped: {}",
+                    stats.frames,
+                    stats.steps,
+                    stats.skipped
+                );
+            }
+        }
+
+        cx.schedule.frame_task(Instant::now()).unwrap();
+    }
+
+    // needed for RTIC timer queue and task management
+    extern "C" {
+        fn EXTI2();
+    }
+};
+
+// game objects and drawing for the state machine hooks
+struct Scene<'a> {
+    display: &'a mut DisplayType,
+    balls: &'a mut Balls<f32, MAX_BALLS>,
+    racket: &'a mut Racket<f32>,
+    // opponent at the top edge in pong mode, otherwise top edge is a wall
+    cpu: &'a mut Racket<f32>,
+    ai: &'a mut Option<Ai<f32>>,
+    hud: &'a mut Hud<3>,
+    logic: &'a mut Squash<f32>,
+    vp: &'a Viewport<f32>,
+    store: &'a mut Store<flash::Parts>,
+    scores: &'a mut HighScores,
+    editor: &'a mut Option<Initials>,
+    shown: &'a mut Shown,
+}
+
+// screen squares of the objects as they were last drawn
+#[derive(Debug, Clone, Copy, Default)]
+struct Shown {
+    balls: [Option<(Point, Point)>; MAX_BALLS],
+    racket: Option<(Point, Point)>,
+    cpu: Option<(Point, Point)>,
+}
+
+impl<'a> Scene<'a> {
+    fn clear(&mut self) {
+        let screen = self.vp.rotation().size();
+
+        Rectangle::new(
+            Point::new(0, 0),
+            Point::new(screen.width as i32, screen.height as i32),
+        )
+        .into_styled(PrimitiveStyle::with_fill(Rgb565::BLACK))
+        .draw(self.display)
+        .unwrap();
+
+        *self.shown = Shown::default();
+    }
+
+    fn clear_field(&mut self) {
+        self.vp
+            .area()
+            .into_styled(PrimitiveStyle::with_fill(Rgb565::BLACK))
+            .draw(self.display)
             .unwrap();
 
-        ball.step();
+        *self.shown = Shown::default();
+    }
 
-        Rectangle::new(ball_square(ball).0, ball_square(ball).1)
-            .into_styled(color2)
-            .draw(display)
+    // big text in the middle of the game field
+    fn banner(&mut self, text: &str, color: Rgb565) {
+        let style = TextStyleBuilder::new(Font12x16)
+            .text_color(color)
+            .background_color(Rgb565::BLACK)
+            .build();
+        let center = self
+            .vp
+            .to_screen(self.vp.world_width() / 2.0, self.vp.world_height() / 2.0);
+
+        Text::new(text, center - Point::new(6 * text.len() as i32, 8))
+            .into_styled(style)
+            .draw(self.display)
             .unwrap();
+    }
 
-        if let Some(dx) = dx {
-            Rectangle::new(racket_square(racket).0, racket_square(racket).1)
-                .into_styled(ground)
-                .draw(display)
+    // high-score table below the line y, new entry is highlighted
+    fn draw_scores(&mut self, y: i32, mark: Option<usize>) {
+        let screen = self.vp.rotation().size();
+        let mut line = TextBuf::<24>::new();
+
+        if self.scores.is_empty() {
+            line.write_str("NO HIGH SCORES").unwrap();
+        } else {
+            line.write_str("HIGH SCORES").unwrap();
+        }
+
+        text(
+            self.display,
+            line.as_str(),
+            (screen.width as i32 - 6 * line.len() as i32) / 2,
+            y,
+            Rgb565::CYAN,
+        );
+
+        for (i, e) in self.scores.iter().enumerate() {
+            let color = if Some(i) == mark {
+                Rgb565::YELLOW
+            } else {
+                Rgb565::WHITE
+            };
+
+            line.clear();
+            write!(line, "{:2}. {} {:6}", i + 1, e.name(), e.score).unwrap();
+            text(
+                self.display,
+                line.as_str(),
+                (screen.width as i32 - 6 * line.len() as i32) / 2,
+                y + 16 + 10 * i as i32,
+                color,
+            );
+        }
+    }
+
+    // initials editor below the line y, current letter is highlighted
+    fn draw_initials(&mut self, y: i32, e: &Initials) {
+        let screen = self.vp.rotation().size();
+        let x = screen.width as i32 / 2 - 18;
+
+        text(self.display, "NEW HIGH SCORE", x - 24, y, Rgb565::CYAN);
+
+        for i in 0..3 {
+            let color = if i == e.pos() {
+                Rgb565::YELLOW
+            } else {
+                Rgb565::WHITE
+            };
+            let style = TextStyleBuilder::new(Font12x16)
+                .text_color(color)
+                .background_color(Rgb565::BLACK)
+                .build();
+
+            Text::new(&e.as_str()[i..i + 1], Point::new(x + 12 * i as i32, y + 16))
+                .into_styled(style)
+                .draw(self.display)
                 .unwrap();
+        }
+    }
+
+    // game mode line on the title screen
+    fn draw_mode(&mut self, y: i32) {
+        let screen = self.vp.rotation().size();
+        let mode = if self.ai.is_some() {
+            "< PONG VS CPU >"
+        } else {
+            "<   SQUASH    >"
+        };
+
+        text(
+            self.display,
+            mode,
+            (screen.width as i32 - 6 * mode.len() as i32) / 2,
+            y,
+            Rgb565::GREEN,
+        );
+    }
+
+    fn toggle_mode(&mut self) {
+        *self.ai = match *self.ai {
+            Some(_) => None,
+            None => {
+                let line = self.cpu.get_cy() - self.cpu.get_hh() - BALL_R;
+                let seed = cm::peripheral::DWT::get_cycle_count();
+
+                Some(Ai::new(SKILL, line, self.vp.world_width() / 2.0, seed))
+            }
+        };
+
+        self.draw_mode(mode_line(self.vp));
+    }
+
+    fn save_scores(&mut self) {
+        let mut buf = [0u8; TABLE_BYTES];
 
-            racket.step(dx);
+        self.scores.to_bytes(&mut buf);
+
+        if let Err(e) = self.store.write(KEY_SCORES, &buf) {
+            rprintln!("failed to save high scores: {:?}", e);
+        }
+    }
+
+    // initials entry after the game, returns false if no high score is edited
+    fn edit(&mut self, up: bool, down: bool, next: bool) -> bool {
+        let mut editor = match *self.editor {
+            Some(e) => e,
+            None => return false,
+        };
+        let y = self.vp.to_screen(0.0, self.vp.world_height() / 2.0).y + 24;
+
+        if up {
+            editor.up();
+        }
+
+        if down {
+            editor.down();
+        }
+
+        if next && editor.advance() {
+            let pos = self.scores.insert(Entry {
+                initials: editor.get(),
+                score: self.logic.get_score(),
+            });
+
+            *self.editor = None;
+            self.save_scores();
+            self.draw_scores(y, pos);
+
+            return true;
+        }
 
-            Rectangle::new(racket_square(racket).0, racket_square(racket).1)
-                .into_styled(color1)
-                .draw(display)
+        *self.editor = Some(editor);
+        self.draw_initials(y, &editor);
+
+        true
+    }
+
+    // draw all the objects again, e.g. after a banner is removed
+    fn redraw(&mut self) {
+        *self.shown = Shown::default();
+        self.render();
+    }
+
+    // erase the objects at their old places and draw them at the new ones
+    fn render(&mut self) {
+        let ground = PrimitiveStyle::with_fill(Rgb565::BLACK);
+        let vp = self.vp;
+        let racket = Some(racket_square(vp, self.racket));
+        let cpu = self.ai.map(|_| racket_square(vp, self.cpu));
+        let mut balls = [None; MAX_BALLS];
+        let mut erased = Shown::default();
+
+        for (i, b) in balls.iter_mut().enumerate() {
+            *b = self.balls.get(i).map(|b| ball_square(vp, b));
+
+            if *b != self.shown.balls[i] {
+                erased.balls[i] = self.shown.balls[i];
+            }
+        }
+
+        if racket != self.shown.racket {
+            erased.racket = self.shown.racket;
+        }
+
+        if cpu != self.shown.cpu {
+            erased.cpu = self.shown.cpu;
+        }
+
+        for (tl, br) in erased
+            .balls
+            .iter()
+            .chain(&[erased.racket, erased.cpu])
+            .flatten()
+        {
+            Rectangle::new(*tl, *br)
+                .into_styled(ground)
+                .draw(self.display)
                 .unwrap();
         }
 
-        let ball_bounce = Particle::<f32>::bounce(ball, 0.0, width as f32, 0.0, height as f32);
-        let _racket_bounce = Racket::<f32>::bounce(racket, 0.0, width as f32);
+        // rackets are redrawn when moved or nicked by an erased ball
+        let nicked = |r: Option<(Point, Point)>| match r {
+            Some(r) => erased.balls.iter().flatten().any(|b| overlap(*b, r)),
+            None => false,
+        };
 
-        if ball_bounce {
-            rprintln!("ball bounced: ({}, {})", ball.get_x(), ball.get_y());
+        if racket != self.shown.racket || nicked(racket) {
+            draw_racket(self.display, vp, self.racket);
+        }
 
-            if score(ball, racket) {
-                let style = TextStyleBuilder::new(Font12x16)
-                    .text_color(Rgb565::YELLOW)
-                    .background_color(Rgb565::BLACK)
-                    .build();
+        if cpu.is_some() && (cpu != self.shown.cpu || nicked(cpu)) {
+            draw_racket(self.display, vp, self.cpu);
+        }
+
+        // balls may overlap, so all of them are drawn
+        for b in self.balls.iter() {
+            BALL.draw(self.display, ball_square(vp, b).0).unwrap();
+        }
 
-                Text::new("GAME OVER", Point::new(70, 150))
-                    .into_styled(style)
-                    .draw(display)
-                    .unwrap();
+        self.shown.balls = balls;
+        self.shown.racket = racket;
+        self.shown.cpu = cpu;
 
-                rprintln!("game over");
-                return;
+        self.draw_hud();
+    }
+
+    fn draw_hud(&mut self) {
+        self.hud
+            .set(0, format_args!("SCORE: {}", self.logic.get_score()));
+        self.hud
+            .set(1, format_args!("LIVES: {}", self.logic.get_lives()));
+        self.hud
+            .set(2, format_args!("LEVEL: {}", self.logic.get_level()));
+        self.hud.draw(self.display).unwrap();
+    }
+
+    // remove balls and put a new one into play
+    fn serve(&mut self) {
+        let mut ball = new_ball();
+
+        self.logic.serve(&mut ball, self.racket);
+        self.balls.clear();
+        self.balls.add(ball);
+    }
+
+    // one physics step of the game, returns true when the last ball is missed
+    fn update(&mut self, intent: Intent) -> bool {
+        let racket = &mut *self.racket;
+        let balls = &mut *self.balls;
+        let vp = self.vp;
+
+        for b in balls.iter_mut() {
+            b.step();
+        }
+
+        racket.step(intent);
+
+        let _racket_bounce = Racket::<f32>::bounce(racket, 0.0, vp.world_width());
+
+        // no top wall in pong mode
+        let ceiling = if self.ai.is_some() {
+            f32::MAX
+        } else {
+            vp.world_height() - BALL_R
+        };
+        let mut returns = 0;
+
+        if balls.collide() > 0 {
+            rprintln!("balls collided");
+        }
+
+        for b in balls.iter_mut() {
+            if Particle::<f32>::bounce(b, 0.0, vp.world_width(), f32::MIN, ceiling) {
+                rprintln!("ball bounced: ({}, {})", b.get_x(), b.get_y());
+            }
+
+            if Racket::<f32>::collide(racket, b, SPIN, DRAG) {
+                rprintln!("ball returned: ({}, {})", b.get_vx(), b.get_vy());
+                returns += 1;
             }
         }
 
-        cx.schedule
-            .step_task(cx.scheduled + STEP_PERIOD.cycles())
-            .unwrap();
+        let cpu_missed = match self.ai.as_mut() {
+            Some(ai) => update_cpu(vp, self.cpu, ai, balls),
+            None => 0,
+        };
+
+        let mut player_missed = 0;
+
+        for i in 0..MAX_BALLS {
+            if let Some(b) = balls.get(i) {
+                if missed(b, racket) {
+                    balls.remove(i);
+                    player_missed += 1;
+                }
+            }
+        }
+
+        for _ in 0..returns {
+            if self.logic.on_return(racket) {
+                rprintln!("level up: {}", self.logic.get_level());
+
+                for b in balls.iter_mut() {
+                    self.logic.speedup(b);
+                }
+
+                let mut ball = new_ball();
+
+                self.logic.serve(&mut ball, racket);
+                balls.add(ball);
+            }
+        }
+
+        for _ in 0..cpu_missed {
+            rprintln!("ball missed by cpu");
+            self.logic.on_win();
+        }
+
+        if !self.balls.is_empty() {
+            return false;
+        }
+
+        if player_missed > 0 {
+            return true;
+        }
+
+        // last ball missed by the opponent
+        self.serve();
+
+        false
     }
+}
 
-    // needed for RTIC timer queue and task management
-    extern "C" {
-        fn EXTI2();
+impl<'a> Hooks for Scene<'a> {
+    fn enter(&mut self, s: State) {
+        rprintln!("enter {:?}", s);
+
+        match s {
+            State::Title => {
+                let screen = self.vp.rotation().size();
+                let title = Point::new(
+                    (screen.width - TITLE.width) as i32 / 2,
+                    (screen.height - TITLE.height) as i32 / 2,
+                );
+
+                self.clear();
+                TITLE.draw(self.display, title).unwrap();
+                self.draw_mode(mode_line(self.vp));
+                self.draw_scores(title.y + TITLE.height as i32 + 16, None);
+            }
+            State::Paused => self.banner("PAUSED", Rgb565::WHITE),
+            State::GameOver => {
+                let y = self.vp.to_screen(0.0, self.vp.world_height() / 2.0).y + 24;
+                let score = self.logic.get_score();
+
+                self.clear_field();
+                self.banner("GAME OVER", Rgb565::YELLOW);
+
+                if self.scores.qualifies(score) {
+                    let editor = Initials::new();
+
+                    *self.editor = Some(editor);
+                    self.draw_initials(y, &editor);
+                } else {
+                    self.draw_scores(y, None);
+                }
+            }
+            State::Playing => {}
+        }
     }
-};
 
-fn ball_square(p: &Particle<f32>) -> (Point, Point) {
-    (
-        Point::new(
-            (p.get_x() - p.get_r()) as i32,
-            (p.get_y() - p.get_r()) as i32,
-        ),
-        Point::new(
-            (p.get_x() + p.get_r()) as i32,
-            (p.get_y() + p.get_r()) as i32,
-        ),
-    )
+    fn exit(&mut self, s: State) {
+        match s {
+            State::Title => {
+                // new game
+                *self.logic = new_logic();
+                *self.racket = new_racket();
+                *self.cpu = new_cpu(self.vp);
+                self.balls.clear();
+
+                self.clear();
+                self.hud.clear(self.display).unwrap();
+                self.serve();
+                self.redraw();
+            }
+            State::Paused => {
+                self.banner("      ", Rgb565::BLACK);
+                self.redraw();
+            }
+            State::Playing | State::GameOver => {}
+        }
+    }
 }
 
-fn racket_square(r: &Racket<f32>) -> (Point, Point) {
-    (
-        Point::new(
-            (r.get_cx() - r.get_hw()) as i32,
-            (r.get_cy() - r.get_hh()) as i32,
-        ),
-        Point::new(
-            (r.get_cx() + r.get_hw()) as i32,
-            (r.get_cy() + r.get_hh()) as i32,
-        ),
-    )
+fn new_logic() -> Squash<f32> {
+    Squash::new(RULES, 1.0, 15.0)
 }
 
-fn score(b: &Particle<f32>, r: &Racket<f32>) -> bool {
-    if (b.get_y() < (r.get_cy() + r.get_hh() + b.get_r()))
-        && ((b.get_x() < (r.get_cx() - r.get_hw())) || (b.get_x() > (r.get_cx() + r.get_hw())))
-    {
-        return true;
+fn new_ball() -> Particle<f32> {
+    Particle::<f32>::new(120.0, 160.0, 10.0, 5.0, BALL_R, 0.1, ParticleColor::Blue)
+}
+
+fn new_racket() -> Racket<f32> {
+    Racket::<f32>::new(120.0, 5.0, 15.0, 5.0, KINEMATICS)
+}
+
+// racket sprite matches the initial racket size only, narrower rackets are plain boxes
+fn draw_racket(display: &mut DisplayType, vp: &Viewport<f32>, r: &Racket<f32>) {
+    let (tl, br) = racket_square(vp, r);
+
+    if (br.x - tl.x + 1) as u32 == RACKET.width {
+        RACKET.draw(display, tl).unwrap();
+    } else {
+        Rectangle::new(tl, br)
+            .into_styled(PrimitiveStyle::with_fill(Rgb565::GREEN))
+            .draw(display)
+            .unwrap();
     }
+}
+
+fn text(display: &mut DisplayType, s: &str, x: i32, y: i32, color: Rgb565) {
+    let style = TextStyleBuilder::new(Font6x8)
+        .text_color(color)
+        .background_color(Rgb565::BLACK)
+        .build();
+
+    Text::new(s, Point::new(x, y))
+        .into_styled(style)
+        .draw(display)
+        .unwrap();
+}
+
+fn new_cpu(vp: &Viewport<f32>) -> Racket<f32> {
+    Racket::<f32>::new(120.0, vp.world_height() - 5.0, 15.0, 5.0, KINEMATICS)
+}
+
+// opponent move in pong mode: the opponent follows the nearest ball coming to it,
+// returns number of balls missed by the opponent, these balls are removed
+fn update_cpu(
+    vp: &Viewport<f32>,
+    cpu: &mut Racket<f32>,
+    ai: &mut Ai<f32>,
+    balls: &mut Balls<f32, MAX_BALLS>,
+) -> usize {
+    let target = balls
+        .iter()
+        .filter(|b| b.get_vy() > 0.0)
+        .fold(None, |t: Option<&Particle<f32>>, b| match t {
+            Some(t) if t.get_y() >= b.get_y() => Some(t),
+            _ => Some(b),
+        })
+        .or_else(|| balls.iter().next())
+        .copied();
+
+    if let Some(b) = target {
+        cpu.step(ai.step(&b, cpu, 0.0, vp.world_width()));
+    }
+
+    let _racket_bounce = Racket::<f32>::bounce(cpu, 0.0, vp.world_width());
+
+    for b in balls.iter_mut() {
+        if Racket::<f32>::collide(cpu, b, SPIN, DRAG) {
+            rprintln!("ball returned by cpu: ({}, {})", b.get_vx(), b.get_vy());
+        }
+    }
+
+    let mut n = 0;
+
+    for i in 0..MAX_BALLS {
+        if let Some(b) = balls.get(i) {
+            if b.get_y() > cpu.get_cy() + cpu.get_hh() {
+                balls.remove(i);
+                n += 1;
+            }
+        }
+    }
+
+    n
+}
+
+// mode selection line above the title
+fn mode_line(vp: &Viewport<f32>) -> i32 {
+    (vp.rotation().size().height as i32 - TITLE.height as i32) / 2 - 24
+}
+
+fn overlap(a: (Point, Point), b: (Point, Point)) -> bool {
+    a.0.x <= b.1.x && b.0.x <= a.1.x && a.0.y <= b.1.y && b.0.y <= a.1.y
+}
+
+fn ball_square(vp: &Viewport<f32>, p: &Particle<f32>) -> (Point, Point) {
+    vp.to_rect(p.get_x(), p.get_y(), p.get_r(), p.get_r())
+}
+
+fn racket_square(vp: &Viewport<f32>, r: &Racket<f32>) -> (Point, Point) {
+    vp.to_rect(r.get_cx(), r.get_cy(), r.get_hw(), r.get_hh())
+}
 
-    false
+// ball has passed the racket and can not be returned anymore
+fn missed(b: &Particle<f32>, r: &Racket<f32>) -> bool {
+    b.get_y() < r.get_cy() - r.get_hh()
 }
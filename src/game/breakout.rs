@@ -0,0 +1,226 @@
+use crate::phys::rng::XorShift;
+
+pub const COLS: usize = 10;
+pub const ROWS: usize = 6;
+
+// brick hit points by row and column, 0 for no brick
+pub type Layout = [[u8; COLS]; ROWS];
+
+pub const LEVELS: [Layout; 3] = [
+    [
+        [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+        [1, 1, 1, 1, 1, 1, 1, 1, 1, 1],
+        [1, 1, 1, 1, 1, 1, 1, 1, 1, 1],
+        [1, 1, 1, 1, 1, 1, 1, 1, 1, 1],
+        [1, 1, 1, 1, 1, 1, 1, 1, 1, 1],
+        [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+    ],
+    [
+        [2, 2, 2, 2, 2, 2, 2, 2, 2, 2],
+        [1, 0, 1, 0, 1, 1, 0, 1, 0, 1],
+        [1, 1, 1, 1, 1, 1, 1, 1, 1, 1],
+        [1, 0, 1, 0, 1, 1, 0, 1, 0, 1],
+        [2, 2, 2, 2, 2, 2, 2, 2, 2, 2],
+        [0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+    ],
+    [
+        [3, 3, 3, 3, 3, 3, 3, 3, 3, 3],
+        [3, 2, 2, 2, 2, 2, 2, 2, 2, 3],
+        [3, 2, 1, 1, 1, 1, 1, 1, 2, 3],
+        [3, 2, 1, 0, 0, 0, 0, 1, 2, 3],
+        [3, 2, 1, 0, 0, 0, 0, 1, 2, 3],
+        [3, 3, 3, 0, 0, 0, 0, 3, 3, 3],
+    ],
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Power {
+    // wider racket for a while
+    Wide,
+    // extra balls
+    Multi,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Rules {
+    // lives at the start of the game
+    pub lives: u32,
+    // points for a brick hit, multiplied by the level number
+    pub points: u32,
+    // points for a destroyed brick, multiplied by the level number
+    pub bonus: u32,
+    // one of that many destroyed bricks drops a power-up on average
+    pub drop: u32,
+}
+
+// breakout game logic: score, lives, levels and power-up drops, no drawing
+#[derive(Debug, Clone, Copy)]
+pub struct Breakout {
+    rules: Rules,
+    score: u32,
+    lives: u32,
+    level: u32,
+    rng: XorShift,
+}
+
+impl Breakout {
+    pub fn new(rules: Rules, seed: u32) -> Breakout {
+        Breakout {
+            rules,
+            score: 0,
+            lives: rules.lives,
+            level: 1,
+            rng: XorShift::new(seed),
+        }
+    }
+
+    pub fn get_score(&self) -> u32 {
+        self.score
+    }
+
+    pub fn get_lives(&self) -> u32 {
+        self.lives
+    }
+
+    pub fn get_level(&self) -> u32 {
+        self.level
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.lives == 0
+    }
+
+    // brick layout of the current level, layouts repeat after the last one
+    pub fn layout(&self) -> &'static Layout {
+        &LEVELS[(self.level as usize - 1) % LEVELS.len()]
+    }
+
+    // brick hit, hp is hit points left: returns power-up dropped by the brick
+    pub fn on_brick(&mut self, hp: u8) -> Option<Power> {
+        self.score += self.rules.points * self.level;
+
+        if hp > 0 {
+            return None;
+        }
+
+        self.score += self.rules.bonus * self.level;
+
+        if self.rules.drop == 0 {
+            return None;
+        }
+
+        let dice = self.rng.random() % self.rules.drop;
+
+        if dice != 0 {
+            return None;
+        }
+
+        if self.rng.random() & 1 == 0 {
+            Some(Power::Wide)
+        } else {
+            Some(Power::Multi)
+        }
+    }
+
+    // last ball missed: returns true when no lives left
+    pub fn on_miss(&mut self) -> bool {
+        if self.lives > 0 {
+            self.lives -= 1;
+        }
+
+        self.is_over()
+    }
+
+    pub fn next_level(&mut self) {
+        self.level += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::phys::bricks::Bricks;
+
+    const RULES: Rules = Rules {
+        lives: 3,
+        points: 1,
+        bonus: 10,
+        drop: 5,
+    };
+
+    #[test]
+    fn layouts() {
+        for layout in LEVELS.iter() {
+            let mut b = Bricks::<f32, COLS, ROWS>::new(0.0, 0.0, 1.0, 1.0);
+            b.load(layout);
+
+            assert!(!b.is_clear());
+            assert!(layout.iter().flatten().all(|hp| *hp <= 3));
+        }
+
+        let counts: Vec<usize> = LEVELS
+            .iter()
+            .map(|l| l.iter().flatten().filter(|hp| **hp > 0).count())
+            .collect();
+        assert_eq!(counts, [40, 42, 48]);
+    }
+
+    #[test]
+    fn levels_repeat() {
+        let mut game = Breakout::new(RULES, 1);
+        assert_eq!(game.get_level(), 1);
+        assert_eq!(game.layout(), &LEVELS[0]);
+
+        for _ in 0..LEVELS.len() {
+            game.next_level();
+        }
+
+        assert_eq!(game.get_level(), 4);
+        assert_eq!(game.layout(), &LEVELS[0]);
+    }
+
+    #[test]
+    fn score() {
+        let mut game = Breakout::new(Rules { drop: 0, ..RULES }, 1);
+
+        assert_eq!(game.on_brick(1), None);
+        assert_eq!(game.get_score(), 1);
+        assert_eq!(game.on_brick(0), None);
+        assert_eq!(game.get_score(), 1 + 1 + 10);
+
+        // points are multiplied by the level
+        game.next_level();
+        game.on_brick(0);
+        assert_eq!(game.get_score(), 12 + 2 * 11);
+    }
+
+    #[test]
+    fn power_ups() {
+        // every destroyed brick drops a power-up, damaged ones never
+        let mut game = Breakout::new(Rules { drop: 1, ..RULES }, 7);
+        let drops: Vec<Option<Power>> = (0..100).map(|_| game.on_brick(0)).collect();
+
+        assert!(drops.iter().all(|d| d.is_some()));
+        assert!(drops.contains(&Some(Power::Wide)));
+        assert!(drops.contains(&Some(Power::Multi)));
+        assert_eq!(game.on_brick(1), None);
+
+        // one of drop bricks on average
+        let mut game = Breakout::new(RULES, 7);
+        let n = (0..1000).filter(|_| game.on_brick(0).is_some()).count();
+        assert!((150..=250).contains(&n), "{}", n);
+    }
+
+    #[test]
+    fn lives() {
+        let mut game = Breakout::new(RULES, 0);
+
+        assert!(!game.on_miss());
+        assert!(!game.on_miss());
+        assert_eq!(game.get_lives(), 1);
+        assert!(game.on_miss());
+        assert!(game.is_over());
+        assert!(game.on_miss());
+        assert_eq!(game.get_lives(), 0);
+    }
+}
@@ -0,0 +1,194 @@
+// Fixed timestep game loop clock driven by a free running cycle counter,
+// e.g. DWT CYCCNT. Every frame the time since the previous frame is added
+// to the accumulator and then simulated in whole physics steps, so the game
+// speed does not depend on how long rendering takes. If rendering is so slow
+// that more than max_steps steps are due, the extra steps are skipped: every
+// frame is still rendered, but the game runs slower than real time.
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Stats {
+    // rendered frames
+    pub frames: u32,
+    // simulated physics steps
+    pub steps: u32,
+    // physics steps skipped, not simulated, because frames took too long
+    pub skipped: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Clock {
+    // cycles per second, cycles per physics step
+    rate: u32,
+    step: u32,
+    max_steps: u32,
+    last: u32,
+    acc: u32,
+    start: u32,
+    cur: Stats,
+    stats: Stats,
+    fresh: bool,
+}
+
+impl Clock {
+    pub fn new(rate: u32, step: u32, max_steps: u32, now: u32) -> Clock {
+        Clock {
+            rate,
+            step,
+            max_steps,
+            last: now,
+            acc: 0,
+            start: now,
+            cur: Stats::default(),
+            stats: Stats::default(),
+            fresh: false,
+        }
+    }
+
+    // start a new frame: returns number of physics steps to run before rendering
+    pub fn tick(&mut self, now: u32) -> u32 {
+        self.acc = self.acc.saturating_add(now.wrapping_sub(self.last));
+        self.last = now;
+
+        let mut steps = self.acc / self.step;
+
+        self.acc -= steps * self.step;
+
+        if steps > self.max_steps {
+            self.cur.skipped += steps - self.max_steps;
+            steps = self.max_steps;
+        }
+
+        self.cur.frames += 1;
+        self.cur.steps += steps;
+
+        if now.wrapping_sub(self.start) >= self.rate {
+            self.stats = self.cur;
+            self.cur = Stats::default();
+            self.start = now;
+            self.fresh = true;
+        }
+
+        steps
+    }
+
+    // drop accumulated time and the partial second of counters, e.g. after a pause
+    pub fn reset(&mut self, now: u32) {
+        self.last = now;
+        self.acc = 0;
+        self.start = now;
+        self.cur = Stats::default();
+    }
+
+    pub fn get_step(&self) -> u32 {
+        self.step
+    }
+
+    // time accumulated towards the next step, in cycles
+    pub fn get_lag(&self) -> u32 {
+        self.acc
+    }
+
+    // counters for the last full second
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    // counters for the last full second, once per second
+    pub fn take_stats(&mut self) -> Option<Stats> {
+        if self.fresh {
+            self.fresh = false;
+            Some(self.stats)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 1000 cycles per second, 10 cycles per step, at most 4 steps per frame
+    fn clock(now: u32) -> Clock {
+        Clock::new(1000, 10, 4, now)
+    }
+
+    #[test]
+    fn steps_and_lag() {
+        let mut c = clock(0);
+
+        assert_eq!(c.tick(25), 2);
+        assert_eq!(c.get_lag(), 5);
+        assert_eq!(c.tick(30), 1);
+        assert_eq!(c.get_lag(), 0);
+        assert_eq!(c.tick(39), 0);
+        assert_eq!(c.get_lag(), 9);
+    }
+
+    #[test]
+    fn max_steps() {
+        let mut c = clock(0);
+
+        // 7 steps due, 4 simulated, 3 skipped, remainder kept
+        assert_eq!(c.tick(73), 4);
+        assert_eq!(c.get_lag(), 3);
+        assert_eq!(c.tick(1000), 4);
+
+        let stats = c.take_stats().unwrap();
+        assert_eq!(stats.frames, 2);
+        assert_eq!(stats.steps, 8);
+        assert_eq!(stats.skipped, 3 + 89);
+    }
+
+    #[test]
+    fn wraparound() {
+        let mut c = clock(u32::MAX - 14);
+
+        assert_eq!(c.tick(5), 2);
+        assert_eq!(c.get_lag(), 0);
+        assert_eq!(c.tick(u32::MAX / 2), 4);
+        assert_eq!(c.get_lag(), (u32::MAX / 2 - 5) % 10);
+    }
+
+    #[test]
+    fn take_stats_once_per_second() {
+        let mut c = clock(0);
+
+        for now in (10..1000).step_by(10) {
+            c.tick(now);
+            assert_eq!(c.take_stats(), None);
+        }
+
+        c.tick(1000);
+        let stats = Stats {
+            frames: 100,
+            steps: 100,
+            skipped: 0,
+        };
+        assert_eq!(c.take_stats(), Some(stats));
+        assert_eq!(c.take_stats(), None);
+        assert_eq!(c.stats(), stats);
+
+        c.tick(1010);
+        assert_eq!(c.take_stats(), None);
+        assert_eq!(c.stats(), stats);
+    }
+
+    #[test]
+    fn reset() {
+        let mut c = clock(0);
+
+        c.tick(15);
+        c.reset(900);
+        assert_eq!(c.get_lag(), 0);
+        assert_eq!(c.tick(920), 2);
+
+        // the second restarts at reset
+        c.tick(1500);
+        assert_eq!(c.take_stats(), None);
+        c.tick(1900);
+        let stats = c.take_stats().unwrap();
+        assert_eq!(stats.frames, 3);
+        assert_eq!(stats.skipped, 54 + 36);
+    }
+}
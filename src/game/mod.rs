@@ -0,0 +1,6 @@
+pub mod breakout;
+pub mod clock;
+pub mod pong;
+pub mod scores;
+pub mod squash;
+pub mod state;
@@ -0,0 +1,177 @@
+use crate::phys::particles::Particle;
+use core::ops::{Add, Div, Mul, Neg, Sub};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Player {
+    // racket at the bottom edge of the field
+    Bottom,
+    // racket at the top edge of the field
+    Top,
+}
+
+impl Player {
+    pub fn other(self) -> Player {
+        match self {
+            Player::Bottom => Player::Top,
+            Player::Top => Player::Bottom,
+        }
+    }
+
+    fn index(self) -> usize {
+        match self {
+            Player::Bottom => 0,
+            Player::Top => 1,
+        }
+    }
+}
+
+// two player pong logic: score and serve, no drawing
+#[derive(Debug, Clone, Copy)]
+pub struct Pong<N>
+where
+    N: Sub<Output = N> + Div<Output = N> + Mul<Output = N> + Add<Output = N> + Neg<Output = N>,
+    N: Default + Copy + Clone + PartialOrd,
+{
+    // points needed to win the game
+    points: u32,
+    score: [u32; 2],
+    // player receiving the next serve
+    receiver: Player,
+    vx: N,
+    vy: N,
+}
+
+impl<N> Pong<N>
+where
+    N: Sub<Output = N> + Div<Output = N> + Mul<Output = N> + Add<Output = N> + Neg<Output = N>,
+    N: Default + Copy + Clone + PartialOrd,
+{
+    // vx and vy: serve velocity, positive values
+    pub fn new(points: u32, vx: N, vy: N) -> Pong<N> {
+        Pong {
+            points,
+            score: [0, 0],
+            receiver: Player::Bottom,
+            vx,
+            vy,
+        }
+    }
+
+    pub fn get_score(&self, p: Player) -> u32 {
+        self.score[p.index()]
+    }
+
+    pub fn get_receiver(&self) -> Player {
+        self.receiver
+    }
+
+    pub fn winner(&self) -> Option<Player> {
+        if self.score[0] >= self.points {
+            Some(Player::Bottom)
+        } else if self.score[1] >= self.points {
+            Some(Player::Top)
+        } else {
+            None
+        }
+    }
+
+    // put the ball at (x, y) and send it towards the receiver,
+    // horizontal direction alternates from serve to serve
+    pub fn serve(&self, ball: &mut Particle<N>, x: N, y: N) {
+        let vx = if (self.score[0] + self.score[1]) & 1 == 0 {
+            self.vx
+        } else {
+            -self.vx
+        };
+        let vy = match self.receiver {
+            Player::Bottom => -self.vy,
+            Player::Top => self.vy,
+        };
+
+        ball.set_position(x, y);
+        ball.set_velocity(vx, vy);
+    }
+
+    // ball missed by the player: point goes to the opponent, the player receives
+    // the next serve; returns true when the game is won
+    pub fn on_miss(&mut self, p: Player) -> bool {
+        self.score[p.other().index()] += 1;
+        self.receiver = p;
+
+        self.winner().is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::phys::particles::ParticleColor;
+
+    fn ball() -> Particle<f32> {
+        Particle::new(0.0, 0.0, 0.0, 0.0, 3.0, 0.1, ParticleColor::White)
+    }
+
+    #[test]
+    fn point_to_opponent() {
+        let mut game = Pong::new(3, 2.0, 4.0);
+
+        assert!(!game.on_miss(Player::Bottom));
+        assert_eq!(game.get_score(Player::Top), 1);
+        assert_eq!(game.get_score(Player::Bottom), 0);
+
+        assert!(!game.on_miss(Player::Top));
+        assert_eq!(game.get_score(Player::Top), 1);
+        assert_eq!(game.get_score(Player::Bottom), 1);
+    }
+
+    #[test]
+    fn receiver() {
+        let mut game = Pong::new(3, 2.0, 4.0);
+        let mut b = ball();
+
+        // first serve goes to the bottom player, down the field
+        assert_eq!(game.get_receiver(), Player::Bottom);
+        game.serve(&mut b, 50.0, 60.0);
+        assert_eq!((b.get_x(), b.get_y()), (50.0, 60.0));
+        assert!(b.get_vy() < 0.0);
+
+        // the player who missed receives the next serve
+        game.on_miss(Player::Top);
+        assert_eq!(game.get_receiver(), Player::Top);
+        game.serve(&mut b, 50.0, 60.0);
+        assert!(b.get_vy() > 0.0);
+
+        game.on_miss(Player::Top);
+        assert_eq!(game.get_receiver(), Player::Top);
+    }
+
+    #[test]
+    fn serve_alternates() {
+        let mut game = Pong::new(10, 2.0, 4.0);
+        let mut b = ball();
+        let mut vx = Vec::new();
+
+        for _ in 0..4 {
+            game.serve(&mut b, 0.0, 0.0);
+            vx.push(b.get_vx());
+            game.on_miss(Player::Bottom);
+        }
+
+        assert_eq!(vx, [2.0, -2.0, 2.0, -2.0]);
+    }
+
+    #[test]
+    fn win() {
+        let mut game = Pong::new(3, 2.0, 4.0);
+
+        assert!(!game.on_miss(Player::Top));
+        assert!(!game.on_miss(Player::Bottom));
+        assert!(!game.on_miss(Player::Top));
+        assert_eq!(game.winner(), None);
+
+        assert!(game.on_miss(Player::Top));
+        assert_eq!(game.winner(), Some(Player::Bottom));
+        assert_eq!(game.get_score(Player::Bottom), 3);
+        assert_eq!(game.get_score(Player::Top), 1);
+    }
+}
@@ -0,0 +1,262 @@
+pub const TABLE_LEN: usize = 10;
+
+// serialized table: number of entries followed by 3 initials and u32 score per entry
+pub const TABLE_BYTES: usize = 1 + TABLE_LEN * ENTRY_BYTES;
+
+const ENTRY_BYTES: usize = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Entry {
+    pub initials: [u8; 3],
+    pub score: u32,
+}
+
+impl Default for Entry {
+    fn default() -> Entry {
+        Entry {
+            initials: *b"AAA",
+            score: 0,
+        }
+    }
+}
+
+impl Entry {
+    pub fn name(&self) -> &str {
+        core::str::from_utf8(&self.initials).unwrap_or("???")
+    }
+}
+
+// top scores, best first
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HighScores {
+    entries: [Entry; TABLE_LEN],
+    len: usize,
+}
+
+impl HighScores {
+    pub fn new() -> HighScores {
+        HighScores {
+            entries: [Entry::default(); TABLE_LEN],
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Entry> {
+        self.entries[..self.len].iter()
+    }
+
+    pub fn qualifies(&self, score: u32) -> bool {
+        score > 0 && (self.len < TABLE_LEN || score > self.entries[TABLE_LEN - 1].score)
+    }
+
+    // returns table position of the new entry, None if the score is too low
+    pub fn insert(&mut self, e: Entry) -> Option<usize> {
+        if !self.qualifies(e.score) {
+            return None;
+        }
+
+        // equal scores: older entry stays above
+        let pos = self.entries[..self.len]
+            .iter()
+            .position(|x| x.score < e.score)
+            .unwrap_or(self.len);
+
+        if self.len < TABLE_LEN {
+            self.len += 1;
+        }
+
+        for i in (pos + 1..self.len).rev() {
+            self.entries[i] = self.entries[i - 1];
+        }
+
+        self.entries[pos] = e;
+
+        Some(pos)
+    }
+
+    pub fn to_bytes(&self, buf: &mut [u8; TABLE_BYTES]) {
+        buf.iter_mut().for_each(|b| *b = 0);
+        buf[0] = self.len as u8;
+
+        for (i, e) in self.iter().enumerate() {
+            let p = 1 + i * ENTRY_BYTES;
+
+            buf[p..p + 3].copy_from_slice(&e.initials);
+            buf[p + 3..p + 7].copy_from_slice(&e.score.to_le_bytes());
+        }
+    }
+
+    // returns None for malformed data
+    pub fn from_bytes(buf: &[u8]) -> Option<HighScores> {
+        let mut table = HighScores::new();
+
+        if buf.len() != TABLE_BYTES || buf[0] as usize > TABLE_LEN {
+            return None;
+        }
+
+        for i in 0..buf[0] as usize {
+            let p = 1 + i * ENTRY_BYTES;
+            let mut e = Entry::default();
+
+            e.initials.copy_from_slice(&buf[p..p + 3]);
+            e.score = u32::from_le_bytes([buf[p + 3], buf[p + 4], buf[p + 5], buf[p + 6]]);
+
+            if !e.initials.iter().all(|c| c.is_ascii_uppercase()) {
+                return None;
+            }
+
+            table.entries[i] = e;
+        }
+
+        table.len = buf[0] as usize;
+
+        Some(table)
+    }
+}
+
+// three letter initials entered with up/down and next buttons
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Initials {
+    letters: [u8; 3],
+    pos: usize,
+}
+
+impl Initials {
+    pub fn new() -> Initials {
+        Initials {
+            letters: *b"AAA",
+            pos: 0,
+        }
+    }
+
+    pub fn get(&self) -> [u8; 3] {
+        self.letters
+    }
+
+    pub fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.letters).unwrap_or("???")
+    }
+
+    // letter currently being edited
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    pub fn up(&mut self) {
+        let c = &mut self.letters[self.pos];
+        *c = if *c == b'Z' { b'A' } else { *c + 1 };
+    }
+
+    pub fn down(&mut self) {
+        let c = &mut self.letters[self.pos];
+        *c = if *c == b'A' { b'Z' } else { *c - 1 };
+    }
+
+    // move to the next letter, returns true when all the letters are entered
+    pub fn advance(&mut self) -> bool {
+        if self.pos < 2 {
+            self.pos += 1;
+            false
+        } else {
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hw::eeprom::tests::Ram;
+    use crate::hw::eeprom::Store;
+
+    fn entry(initials: &[u8; 3], score: u32) -> Entry {
+        Entry {
+            initials: *initials,
+            score,
+        }
+    }
+
+    fn names(table: &HighScores) -> Vec<&str> {
+        table.iter().map(|e| e.name()).collect()
+    }
+
+    #[test]
+    fn insert_order() {
+        let mut table = HighScores::new();
+
+        assert_eq!(table.insert(entry(b"BBB", 20)), Some(0));
+        assert_eq!(table.insert(entry(b"AAA", 30)), Some(0));
+        assert_eq!(table.insert(entry(b"CCC", 10)), Some(2));
+        assert_eq!(table.insert(entry(b"DDD", 25)), Some(1));
+
+        assert_eq!(names(&table), ["AAA", "DDD", "BBB", "CCC"]);
+        assert_eq!(table.insert(entry(b"ZZZ", 0)), None);
+    }
+
+    #[test]
+    fn ties() {
+        let mut table = HighScores::new();
+
+        table.insert(entry(b"OLD", 20));
+        table.insert(entry(b"LOW", 10));
+
+        // equal score goes below the older entry
+        assert_eq!(table.insert(entry(b"NEW", 20)), Some(1));
+        assert_eq!(names(&table), ["OLD", "NEW", "LOW"]);
+    }
+
+    #[test]
+    fn full_table() {
+        let mut table = HighScores::new();
+
+        for i in 0..TABLE_LEN as u32 {
+            assert_eq!(table.insert(entry(b"AAA", 100 - i * 10)), Some(i as usize));
+        }
+
+        // lowest entry is 10: equal or lower scores do not qualify
+        assert!(!table.qualifies(10));
+        assert_eq!(table.insert(entry(b"LOW", 10)), None);
+
+        assert_eq!(table.insert(entry(b"TOP", 95)), Some(1));
+        assert_eq!(table.len(), TABLE_LEN);
+
+        let scores: Vec<u32> = table.iter().map(|e| e.score).collect();
+        assert_eq!(scores, [100, 95, 90, 80, 70, 60, 50, 40, 30, 20]);
+    }
+
+    #[test]
+    fn saved_table() {
+        let mut table = HighScores::new();
+        table.insert(entry(b"ABC", 300));
+        table.insert(entry(b"XYZ", 200));
+
+        let mut buf = [0u8; TABLE_BYTES];
+        table.to_bytes(&mut buf);
+
+        let mut store = Store::mount(Ram::new()).unwrap();
+        store.write(1, &buf).unwrap();
+
+        let mut store = Store::mount(store.release()).unwrap();
+        let mut read = [0u8; TABLE_BYTES];
+        let n = store.read(1, &mut read).unwrap();
+        let loaded = HighScores::from_bytes(&read[..n]).unwrap();
+
+        assert_eq!(names(&loaded), ["ABC", "XYZ"]);
+        assert_eq!(loaded.iter().map(|e| e.score).sum::<u32>(), 500);
+
+        // malformed data
+        assert!(HighScores::from_bytes(&read[..n - 1]).is_none());
+        read[1] = b'a';
+        assert!(HighScores::from_bytes(&read).is_none());
+        read[0] = TABLE_LEN as u8 + 1;
+        assert!(HighScores::from_bytes(&read).is_none());
+    }
+}
@@ -0,0 +1,238 @@
+use crate::phys::particles::Particle;
+use crate::phys::racket::Racket;
+use core::ops::{Add, Div, Mul, Neg, Sub};
+
+#[derive(Debug, Clone, Copy)]
+pub struct Rules<N> {
+    // lives at the start of the game
+    pub lives: u32,
+    // points for a return, multiplied by the level number
+    pub points: u32,
+    // points for a ball missed by the opponent, multiplied by the level number
+    pub bonus: u32,
+    // returns needed to reach the next level
+    pub returns: u32,
+    // ball speed multiplier applied on each new level
+    pub speedup: N,
+    // racket half-width shrinks by this step on each new level down to the minimum
+    pub shrink: N,
+    pub hw_min: N,
+}
+
+// squash game logic: score, lives and difficulty, no drawing
+#[derive(Debug, Clone, Copy)]
+pub struct Squash<N>
+where
+    N: Sub<Output = N> + Div<Output = N> + Mul<Output = N> + Add<Output = N> + Neg<Output = N>,
+    N: Default + Copy + Clone + PartialOrd,
+{
+    rules: Rules<N>,
+    score: u32,
+    lives: u32,
+    level: u32,
+    hits: u32,
+    speed: N,
+    hw: N,
+}
+
+impl<N> Squash<N>
+where
+    N: Sub<Output = N> + Div<Output = N> + Mul<Output = N> + Add<Output = N> + Neg<Output = N>,
+    N: Default + Copy + Clone + PartialOrd,
+{
+    // speed and hw are the initial ball speed multiplier and racket half-width
+    pub fn new(rules: Rules<N>, speed: N, hw: N) -> Squash<N> {
+        Squash {
+            rules,
+            score: 0,
+            lives: rules.lives,
+            level: 1,
+            hits: 0,
+            speed,
+            hw,
+        }
+    }
+
+    pub fn get_score(&self) -> u32 {
+        self.score
+    }
+
+    pub fn get_lives(&self) -> u32 {
+        self.lives
+    }
+
+    pub fn get_level(&self) -> u32 {
+        self.level
+    }
+
+    pub fn get_speed(&self) -> N {
+        self.speed
+    }
+
+    pub fn get_hw(&self) -> N {
+        self.hw
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.lives == 0
+    }
+
+    // put a new ball into play: its velocity is scaled by the current level speed
+    pub fn serve(&self, ball: &mut Particle<N>, racket: &mut Racket<N>) {
+        ball.set_velocity(ball.get_vx() * self.speed, ball.get_vy() * self.speed);
+        racket.set_hw(self.hw);
+    }
+
+    // ball returned by the racket: returns true on a new level
+    pub fn on_return(&mut self, racket: &mut Racket<N>) -> bool {
+        self.score += self.rules.points * self.level;
+        self.hits += 1;
+
+        if self.hits < self.rules.returns {
+            return false;
+        }
+
+        self.hits = 0;
+        self.level += 1;
+        self.speed = self.speed * self.rules.speedup;
+        self.hw = if self.hw - self.rules.shrink > self.rules.hw_min {
+            self.hw - self.rules.shrink
+        } else {
+            self.rules.hw_min
+        };
+
+        racket.set_hw(self.hw);
+
+        true
+    }
+
+    // speed up the ball in play on a new level
+    pub fn speedup(&self, ball: &mut Particle<N>) {
+        ball.set_velocity(
+            ball.get_vx() * self.rules.speedup,
+            ball.get_vy() * self.rules.speedup,
+        );
+    }
+
+    // ball missed by the opponent in pong mode
+    pub fn on_win(&mut self) {
+        self.score += self.rules.bonus * self.level;
+    }
+
+    // ball missed: returns true when no lives left
+    pub fn on_miss(&mut self) -> bool {
+        if self.lives > 0 {
+            self.lives -= 1;
+        }
+
+        self.is_over()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::phys::particles::ParticleColor;
+    use crate::phys::racket::Kinematics;
+
+    const RULES: Rules<f32> = Rules {
+        lives: 3,
+        points: 10,
+        bonus: 50,
+        returns: 5,
+        speedup: 1.25,
+        shrink: 3.0,
+        hw_min: 7.0,
+    };
+
+    fn racket() -> Racket<f32> {
+        Racket::new(120.0, 300.0, 15.0, 3.0, Kinematics::default())
+    }
+
+    // returns until the next level
+    fn level_up(game: &mut Squash<f32>, racket: &mut Racket<f32>) {
+        for _ in 1..RULES.returns {
+            assert!(!game.on_return(racket));
+        }
+        assert!(game.on_return(racket));
+    }
+
+    #[test]
+    fn level_up_after_returns() {
+        let mut game = Squash::new(RULES, 1.0, 15.0);
+        let mut r = racket();
+
+        level_up(&mut game, &mut r);
+        assert_eq!(game.get_level(), 2);
+        assert_eq!(game.get_score(), 5 * 10);
+
+        // points are multiplied by the level
+        assert!(!game.on_return(&mut r));
+        assert_eq!(game.get_score(), 5 * 10 + 20);
+    }
+
+    #[test]
+    fn speedup() {
+        let mut game = Squash::new(RULES, 1.0, 15.0);
+        let mut r = racket();
+        let mut ball = Particle::new(120.0, 160.0, 8.0, -4.0, 3.0, 0.1, ParticleColor::Blue);
+
+        level_up(&mut game, &mut r);
+        level_up(&mut game, &mut r);
+        assert_eq!(game.get_speed(), 1.25 * 1.25);
+
+        // new ball is served at the level speed, the ball in play speeds up by one step
+        game.serve(&mut ball, &mut r);
+        assert_eq!((ball.get_vx(), ball.get_vy()), (12.5, -6.25));
+
+        game.speedup(&mut ball);
+        assert_eq!((ball.get_vx(), ball.get_vy()), (15.625, -7.8125));
+    }
+
+    #[test]
+    fn racket_shrinks_to_minimum() {
+        let mut game = Squash::new(RULES, 1.0, 15.0);
+        let mut r = racket();
+        let mut widths = Vec::new();
+
+        for _ in 0..5 {
+            level_up(&mut game, &mut r);
+            widths.push(game.get_hw());
+            assert_eq!(r.get_hw(), game.get_hw());
+        }
+
+        assert_eq!(widths, [12.0, 9.0, 7.0, 7.0, 7.0]);
+    }
+
+    #[test]
+    fn bonus() {
+        let mut game = Squash::new(RULES, 1.0, 15.0);
+        let mut r = racket();
+
+        game.on_win();
+        assert_eq!(game.get_score(), 50);
+
+        level_up(&mut game, &mut r);
+        game.on_win();
+        assert_eq!(game.get_score(), 50 + 5 * 10 + 2 * 50);
+    }
+
+    #[test]
+    fn lives() {
+        let mut game = Squash::new(RULES, 1.0, 15.0);
+        assert_eq!(game.get_lives(), 3);
+
+        assert!(!game.on_miss());
+        assert!(!game.on_miss());
+        assert_eq!(game.get_lives(), 1);
+        assert!(!game.is_over());
+
+        assert!(game.on_miss());
+        assert_eq!(game.get_lives(), 0);
+        assert!(game.is_over());
+
+        // no wrap below zero
+        assert!(game.on_miss());
+        assert_eq!(game.get_lives(), 0);
+    }
+}
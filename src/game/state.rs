@@ -0,0 +1,192 @@
+// Game flow: Title -> Playing <-> Paused -> GameOver -> Title
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum State {
+    Title,
+    Playing,
+    Paused,
+    GameOver,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event {
+    // select button: start, pause, resume, back to title
+    Select,
+    // quit button: give up paused game. Ignored in the other states, so a
+    // stray press can not end a running game, and game over already goes
+    // back to title with select once the score is shown.
+    Quit,
+    // reported by the game logic
+    Lost,
+}
+
+// drawing and game setup on state changes
+pub trait Hooks {
+    fn enter(&mut self, _s: State) {}
+    fn exit(&mut self, _s: State) {}
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Machine {
+    state: State,
+}
+
+impl Default for Machine {
+    fn default() -> Machine {
+        Machine::new()
+    }
+}
+
+impl Machine {
+    pub fn new() -> Machine {
+        Machine {
+            state: State::Title,
+        }
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    // transition table, None for events ignored in the state
+    pub fn next(s: State, e: Event) -> Option<State> {
+        match (s, e) {
+            (State::Title, Event::Select) => Some(State::Playing),
+            (State::Playing, Event::Select) => Some(State::Paused),
+            (State::Playing, Event::Lost) => Some(State::GameOver),
+            (State::Paused, Event::Select) => Some(State::Playing),
+            (State::Paused, Event::Quit) => Some(State::GameOver),
+            (State::GameOver, Event::Select) => Some(State::Title),
+            _ => None,
+        }
+    }
+
+    // returns true if event caused state change
+    pub fn handle<H: Hooks>(&mut self, e: Event, hooks: &mut H) -> bool {
+        match Machine::next(self.state, e) {
+            Some(s) => {
+                hooks.exit(self.state);
+                self.state = s;
+                hooks.enter(s);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // run enter hook for the current state, e.g. to draw initial screen
+    pub fn start<H: Hooks>(&self, hooks: &mut H) {
+        hooks.enter(self.state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATES: [State; 4] = [State::Title, State::Playing, State::Paused, State::GameOver];
+    const EVENTS: [Event; 3] = [Event::Select, Event::Quit, Event::Lost];
+
+    #[derive(Default)]
+    struct Log {
+        calls: Vec<(&'static str, State)>,
+    }
+
+    impl Hooks for Log {
+        fn enter(&mut self, s: State) {
+            self.calls.push(("enter", s));
+        }
+
+        fn exit(&mut self, s: State) {
+            self.calls.push(("exit", s));
+        }
+    }
+
+    #[test]
+    fn transitions() {
+        let table = [
+            (State::Title, Event::Select, State::Playing),
+            (State::Playing, Event::Select, State::Paused),
+            (State::Playing, Event::Lost, State::GameOver),
+            (State::Paused, Event::Select, State::Playing),
+            (State::Paused, Event::Quit, State::GameOver),
+            (State::GameOver, Event::Select, State::Title),
+        ];
+
+        for s in STATES.iter() {
+            for e in EVENTS.iter() {
+                let expected = table
+                    .iter()
+                    .find(|(from, on, _)| from == s && on == e)
+                    .map(|(_, _, to)| *to);
+
+                assert_eq!(Machine::next(*s, *e), expected, "{:?} {:?}", s, e);
+            }
+        }
+    }
+
+    #[test]
+    fn quit_only_when_paused() {
+        assert_eq!(Machine::next(State::Title, Event::Quit), None);
+        assert_eq!(Machine::next(State::Playing, Event::Quit), None);
+        assert_eq!(Machine::next(State::GameOver, Event::Quit), None);
+    }
+
+    #[test]
+    fn hooks() {
+        let mut m = Machine::new();
+        let mut log = Log::default();
+
+        m.start(&mut log);
+        assert_eq!(log.calls, [("enter", State::Title)]);
+
+        log.calls.clear();
+        assert!(m.handle(Event::Select, &mut log));
+        assert_eq!(m.state(), State::Playing);
+        assert_eq!(
+            log.calls,
+            [("exit", State::Title), ("enter", State::Playing)]
+        );
+
+        // ignored events call no hooks
+        log.calls.clear();
+        assert!(!m.handle(Event::Quit, &mut log));
+        assert_eq!(m.state(), State::Playing);
+        assert!(log.calls.is_empty());
+    }
+
+    #[test]
+    fn full_game() {
+        let mut m = Machine::default();
+        let mut log = Log::default();
+        let events = [
+            Event::Select,
+            Event::Select,
+            Event::Select,
+            Event::Lost,
+            Event::Select,
+            Event::Select,
+            Event::Select,
+            Event::Quit,
+        ];
+        let mut states = Vec::new();
+
+        for e in events.iter() {
+            m.handle(*e, &mut log);
+            states.push(m.state());
+        }
+
+        assert_eq!(
+            states,
+            [
+                State::Playing,
+                State::Paused,
+                State::Playing,
+                State::GameOver,
+                State::Title,
+                State::Playing,
+                State::Paused,
+                State::GameOver,
+            ]
+        );
+    }
+}
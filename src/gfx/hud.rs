@@ -0,0 +1,292 @@
+use core::fmt;
+use core::fmt::Write;
+use embedded_graphics::fonts::{Font, Font6x8, Text};
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::style::{PrimitiveStyle, TextStyleBuilder};
+
+pub const FIELD_LEN: usize = 16;
+
+const CW: i32 = Font6x8::CHARACTER_SIZE.width as i32;
+const CH: i32 = Font6x8::CHARACTER_SIZE.height as i32;
+
+// fixed size text buffer: core::fmt output without allocation, extra text is truncated
+#[derive(Debug, Clone, Copy)]
+pub struct TextBuf<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> Default for TextBuf<N> {
+    fn default() -> TextBuf<N> {
+        TextBuf::new()
+    }
+}
+
+impl<const N: usize> TextBuf<N> {
+    pub fn new() -> TextBuf<N> {
+        TextBuf {
+            buf: [0; N],
+            len: 0,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.buf[..self.len]).unwrap_or("")
+    }
+}
+
+impl<const N: usize> Write for TextBuf<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            let n = c.len_utf8();
+
+            if self.len + n > N {
+                break;
+            }
+
+            c.encode_utf8(&mut self.buf[self.len..]);
+            self.len += n;
+        }
+
+        Ok(())
+    }
+}
+
+impl<const N: usize> PartialEq for TextBuf<N> {
+    fn eq(&self, other: &TextBuf<N>) -> bool {
+        self.buf[..self.len] == other.buf[..other.len]
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Field {
+    x: i32,
+    text: TextBuf<FIELD_LEN>,
+    drawn: usize,
+    dirty: bool,
+}
+
+// screen band with F text fields, only changed fields are redrawn
+pub struct Hud<const F: usize> {
+    top: i32,
+    width: i32,
+    fg: Rgb565,
+    bg: Rgb565,
+    fields: [Field; F],
+}
+
+impl<const F: usize> Hud<F> {
+    // band starts at row top and spans whole screen width, cols are x offsets of fields
+    pub fn new(top: i32, width: i32, cols: [i32; F], fg: Rgb565, bg: Rgb565) -> Hud<F> {
+        let mut fields = [Field::default(); F];
+
+        for (f, x) in fields.iter_mut().zip(cols.iter()) {
+            f.x = *x;
+        }
+
+        Hud {
+            top,
+            width,
+            fg,
+            bg,
+            fields,
+        }
+    }
+
+    pub fn height() -> i32 {
+        CH + 2
+    }
+
+    pub fn top(&self) -> i32 {
+        self.top
+    }
+
+    pub fn bottom(&self) -> i32 {
+        self.top + Hud::<F>::height()
+    }
+
+    // format field text, returns true if it has changed since the last update
+    pub fn set(&mut self, n: usize, args: fmt::Arguments) -> bool {
+        let mut text = TextBuf::<FIELD_LEN>::new();
+        let f = &mut self.fields[n];
+
+        text.write_fmt(args).ok();
+
+        if text == f.text {
+            return false;
+        }
+
+        f.text = text;
+        f.dirty = true;
+
+        true
+    }
+
+    // fill the whole band with background and schedule all the fields for redraw
+    pub fn clear<D>(&mut self, display: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Rgb565>,
+    {
+        Rectangle::new(
+            Point::new(0, self.top),
+            Point::new(self.width - 1, self.bottom() - 1),
+        )
+        .into_styled(PrimitiveStyle::with_fill(self.bg))
+        .draw(display)?;
+
+        for f in self.fields.iter_mut() {
+            f.drawn = 0;
+            f.dirty = true;
+        }
+
+        Ok(())
+    }
+
+    pub fn draw<D>(&mut self, display: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Rgb565>,
+    {
+        let style = TextStyleBuilder::new(Font6x8)
+            .text_color(self.fg)
+            .background_color(self.bg)
+            .build();
+        let y = self.top + 1;
+
+        for f in self.fields.iter_mut().filter(|f| f.dirty) {
+            Text::new(f.text.as_str(), Point::new(f.x, y))
+                .into_styled(style)
+                .draw(display)?;
+
+            // wipe the tail of the previous longer text
+            if f.drawn > f.text.len() {
+                Rectangle::new(
+                    Point::new(f.x + CW * f.text.len() as i32, y),
+                    Point::new(f.x + CW * f.drawn as i32 - 1, y + CH - 1),
+                )
+                .into_styled(PrimitiveStyle::with_fill(self.bg))
+                .draw(display)?;
+            }
+
+            f.drawn = f.text.len();
+            f.dirty = false;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // records drawn pixels
+    #[derive(Default)]
+    struct Screen {
+        pixels: Vec<Point>,
+    }
+
+    impl DrawTarget<Rgb565> for Screen {
+        type Error = core::convert::Infallible;
+
+        fn draw_pixel(&mut self, p: Pixel<Rgb565>) -> Result<(), Self::Error> {
+            self.pixels.push(p.0);
+            Ok(())
+        }
+
+        fn size(&self) -> Size {
+            Size::new(240, 320)
+        }
+    }
+
+    impl Screen {
+        fn take(&mut self) -> Vec<Point> {
+            core::mem::take(&mut self.pixels)
+        }
+    }
+
+    fn text<const N: usize>(s: &str) -> TextBuf<N> {
+        let mut t = TextBuf::<N>::new();
+        t.write_str(s).unwrap();
+        t
+    }
+
+    #[test]
+    fn truncation() {
+        assert_eq!(text::<4>("abc").as_str(), "abc");
+        assert_eq!(text::<4>("abcdef").as_str(), "abcd");
+
+        // multibyte characters are never split
+        assert_eq!(text::<4>("ab\u{b5}\u{b5}").as_str(), "ab\u{b5}");
+        assert_eq!(text::<4>("abc\u{b5}").as_str(), "abc");
+
+        // formatting continues into the same buffer until it is full
+        let mut t = text::<8>("x:");
+        write!(t, "{}", 1234567).unwrap();
+        assert_eq!(t.as_str(), "x:123456");
+        assert_eq!(t.len(), 8);
+
+        t.clear();
+        assert!(t.is_empty());
+        assert_eq!(t, TextBuf::new());
+    }
+
+    #[test]
+    fn clear_band() {
+        let mut hud = Hud::new(10, 240, [0, 120], Rgb565::WHITE, Rgb565::BLUE);
+        let mut screen = Screen::default();
+
+        hud.clear(&mut screen).unwrap();
+
+        let px = screen.take();
+        assert_eq!(px.len(), 240 * Hud::<2>::height() as usize);
+        assert!(px.iter().all(|p| p.x >= 0 && p.x < 240));
+        assert!(px.iter().all(|p| p.y >= hud.top() && p.y < hud.bottom()));
+        assert!(hud.fields.iter().all(|f| f.dirty));
+    }
+
+    #[test]
+    fn dirty_fields() {
+        let mut hud = Hud::new(0, 240, [0, 120], Rgb565::WHITE, Rgb565::BLUE);
+        let mut screen = Screen::default();
+
+        assert!(hud.set(0, format_args!("A:{}", 12)));
+        assert!(hud.set(1, format_args!("B")));
+        hud.draw(&mut screen).unwrap();
+        assert!(hud.fields.iter().all(|f| !f.dirty));
+        assert_eq!(screen.take().len(), 5 * (CW * CH) as usize);
+
+        // same text does not schedule a redraw
+        assert!(!hud.set(0, format_args!("A:12")));
+        hud.draw(&mut screen).unwrap();
+        assert!(screen.take().is_empty());
+
+        // only the changed field is redrawn
+        assert!(hud.set(1, format_args!("BB")));
+        assert!(!hud.fields[0].dirty && hud.fields[1].dirty);
+        hud.draw(&mut screen).unwrap();
+        let px = screen.take();
+        assert_eq!(px.len(), 2 * (CW * CH) as usize);
+        assert!(px.iter().all(|p| p.x >= 120 && p.x < 120 + 2 * CW));
+
+        // shorter text wipes the tail of the longer one
+        assert!(hud.set(0, format_args!("A")));
+        hud.draw(&mut screen).unwrap();
+        let px = screen.take();
+        assert_eq!(px.len(), 4 * (CW * CH) as usize);
+        assert!(px.iter().all(|p| p.x < 4 * CW));
+    }
+}
@@ -0,0 +1,5 @@
+pub mod hud;
+pub mod scroll;
+pub mod sprite;
+pub mod trail;
+pub mod viewport;
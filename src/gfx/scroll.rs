@@ -0,0 +1,136 @@
+use crate::gfx::viewport::Rotation;
+use display_interface::WriteOnlyDataCommand;
+use embedded_hal::digital::v2::OutputPin;
+use ili9341::{Ili9341, Scroller};
+
+// number of panel lines: hardware scrolling always runs along the long side
+const PANEL_LINES: u16 = 320;
+
+// Hardware vertical scrolling: the panel has a scroll area between fixed top and
+// bottom bands, and the scroll pointer selects which frame memory line is shown
+// first in that area. Scrolling is done by moving the pointer, not by redrawing.
+//
+// The scroll area is defined once. The ili9341 scroller only moves forward and
+// never wraps back exactly to the start of the area, so a second scroller is
+// kept at the start and offset 0 is set with it.
+pub struct ScrollRegion {
+    scroller: Scroller,
+    start: Scroller,
+    rot: Rotation,
+    top: u16,
+    bottom: u16,
+    offset: u16,
+    // offset the scroller was moved to, the start scroller is used for offset 0
+    pointer: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Error<E> {
+    Display(E),
+    // fixed bands leave no lines to scroll
+    Bands,
+}
+
+impl<E> From<E> for Error<E> {
+    fn from(e: E) -> Error<E> {
+        Error::Display(e)
+    }
+}
+
+impl ScrollRegion {
+    // top and bottom are the sizes of fixed bands in panel lines
+    pub fn new<IFACE, RESET, PinE>(
+        display: &mut Ili9341<IFACE, RESET>,
+        rot: Rotation,
+        top: u16,
+        bottom: u16,
+    ) -> Result<ScrollRegion, Error<ili9341::Error<PinE>>>
+    where
+        IFACE: WriteOnlyDataCommand,
+        RESET: OutputPin<Error = PinE>,
+    {
+        if top.saturating_add(bottom) >= PANEL_LINES {
+            return Err(Error::Bands);
+        }
+
+        let scroller = display.configure_vertical_scroll(top, bottom)?;
+        let start = display.configure_vertical_scroll(top, bottom)?;
+        let mut region = ScrollRegion {
+            scroller,
+            start,
+            rot,
+            top,
+            bottom,
+            offset: 0,
+            pointer: 0,
+        };
+
+        region.set_offset(display, 0)?;
+
+        Ok(region)
+    }
+
+    // number of lines in the scroll area
+    pub fn lines(&self) -> u16 {
+        PANEL_LINES - self.top - self.bottom
+    }
+
+    pub fn offset(&self) -> u16 {
+        self.offset
+    }
+
+    // set scroll pointer to absolute offset within the scroll area
+    pub fn set_offset<IFACE, RESET, PinE>(
+        &mut self,
+        display: &mut Ili9341<IFACE, RESET>,
+        offset: u16,
+    ) -> Result<(), ili9341::Error<PinE>>
+    where
+        IFACE: WriteOnlyDataCommand,
+        RESET: OutputPin<Error = PinE>,
+    {
+        let lines = self.lines();
+        self.offset = offset % lines;
+
+        if self.offset == 0 {
+            // the start scroller does not move
+            return display.scroll_vertically(&mut self.start, 0);
+        }
+
+        // move forward, past the end of the scroll area the scroller wraps to the offset
+        let n = (self.offset + lines - self.pointer) % lines;
+        self.pointer = self.offset;
+
+        display.scroll_vertically(&mut self.scroller, n)
+    }
+
+    // scroll content by n lines
+    pub fn scroll<IFACE, RESET, PinE>(
+        &mut self,
+        display: &mut Ili9341<IFACE, RESET>,
+        n: u16,
+    ) -> Result<(), ili9341::Error<PinE>>
+    where
+        IFACE: WriteOnlyDataCommand,
+        RESET: OutputPin<Error = PinE>,
+    {
+        let offset = (self.offset + n % self.lines()) % self.lines();
+        self.set_offset(display, offset)
+    }
+
+    // frame memory line currently shown at the given line of the scroll area
+    pub fn row(&self, line: u16) -> u16 {
+        self.top + (self.offset + line) % self.lines()
+    }
+
+    // screen coordinate of a frame memory line for the current orientation:
+    // y in portrait modes, x in landscape modes
+    pub fn to_screen(&self, row: u16) -> i32 {
+        match self.rot {
+            Rotation::Portrait | Rotation::Landscape => row as i32,
+            Rotation::PortraitFlipped | Rotation::LandscapeFlipped => {
+                (PANEL_LINES - 1 - row) as i32
+            }
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use embedded_graphics::pixelcolor::raw::RawU16;
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::style::PrimitiveStyle;
+
+// Palettised RLE image: data is a sequence of (count, palette index) pairs,
+// runs never cross row boundaries. Pixels with the key index are transparent.
+#[derive(Debug, Clone, Copy)]
+pub struct Sprite {
+    pub width: u32,
+    pub height: u32,
+    pub palette: &'static [u16],
+    pub key: Option<u8>,
+    pub data: &'static [u8],
+}
+
+impl Sprite {
+    pub fn size(&self) -> Size {
+        Size::new(self.width, self.height)
+    }
+
+    // draw sprite with its top-left corner at p, each run is a single filled line
+    pub fn draw<D>(&self, display: &mut D, p: Point) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Rgb565>,
+    {
+        let mut x: u32 = 0;
+        let mut y: u32 = 0;
+
+        for run in self.data.chunks(2) {
+            let (n, idx) = (run[0] as u32, run[1]);
+
+            if self.key != Some(idx) {
+                let color = Rgb565::from(RawU16::new(self.palette[idx as usize]));
+                let start = p + Point::new(x as i32, y as i32);
+
+                Rectangle::new(start, start + Point::new(n as i32 - 1, 0))
+                    .into_styled(PrimitiveStyle::with_fill(color))
+                    .draw(display)?;
+            }
+
+            x += n;
+            if x >= self.width {
+                x = 0;
+                y += 1;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// sprites converted from assets/*.bmp by build script
+pub mod assets {
+    use super::Sprite;
+
+    include!(concat!(env!("OUT_DIR"), "/sprites.rs"));
+}
@@ -0,0 +1,168 @@
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::style::PrimitiveStyle;
+
+// blend two colors: num/den of fg, the rest is bg
+pub fn fade(fg: Rgb565, bg: Rgb565, num: u32, den: u32) -> Rgb565 {
+    let mix = |f: u8, b: u8| -> u8 { ((f as u32 * num + b as u32 * (den - num)) / den) as u8 };
+
+    Rgb565::new(
+        mix(fg.r(), bg.r()),
+        mix(fg.g(), bg.g()),
+        mix(fg.b(), bg.b()),
+    )
+}
+
+// ring buffer of the last L positions of a moving object
+#[derive(Debug, Clone, Copy)]
+pub struct Trail<const L: usize> {
+    pos: [Point; L],
+    head: usize,
+    len: usize,
+}
+
+impl<const L: usize> Default for Trail<L> {
+    fn default() -> Trail<L> {
+        Trail::new()
+    }
+}
+
+impl<const L: usize> Trail<L> {
+    pub fn new() -> Trail<L> {
+        Trail {
+            pos: [Point::zero(); L],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.head = 0;
+        self.len = 0;
+    }
+
+    // add new position, return the oldest one if it has been pushed out
+    pub fn push(&mut self, p: Point) -> Option<Point> {
+        if L == 0 {
+            return Some(p);
+        }
+
+        let old = if self.len == L {
+            Some(self.pos[self.head])
+        } else {
+            self.len += 1;
+            None
+        };
+
+        self.pos[self.head] = p;
+        self.head = (self.head + 1) % L;
+
+        old
+    }
+
+    // iterate positions starting from the oldest one
+    pub fn iter(&self) -> impl Iterator<Item = Point> + '_ {
+        let start = (self.head + L - self.len) % L.max(1);
+
+        (0..self.len).map(move |i| self.pos[(start + i) % L])
+    }
+
+    // draw positions as dots fading from fg (newest) towards bg (oldest)
+    pub fn draw<D>(&self, display: &mut D, fg: Rgb565, bg: Rgb565, dot: u32) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Rgb565>,
+    {
+        for (k, p) in self.iter().enumerate() {
+            let color = fade(fg, bg, k as u32 + 1, L as u32 + 1);
+            Trail::<L>::dot(p, dot)
+                .into_styled(PrimitiveStyle::with_fill(color))
+                .draw(display)?;
+        }
+
+        Ok(())
+    }
+
+    // wipe a single dot, e.g. the one returned by push
+    pub fn erase<D>(display: &mut D, p: Point, bg: Rgb565, dot: u32) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Rgb565>,
+    {
+        Trail::<L>::dot(p, dot)
+            .into_styled(PrimitiveStyle::with_fill(bg))
+            .draw(display)
+    }
+
+    fn dot(p: Point, dot: u32) -> Rectangle {
+        let d = (dot / 2) as i32;
+
+        Rectangle::new(p - Point::new(d, d), p + Point::new(d, d))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points(t: &Trail<3>) -> Vec<Point> {
+        t.iter().collect()
+    }
+
+    #[test]
+    fn ring_wraparound() {
+        let mut t = Trail::<3>::new();
+        assert!(t.is_empty());
+
+        for i in 0..3 {
+            assert_eq!(t.push(Point::new(i, 0)), None);
+        }
+        assert_eq!(t.len(), 3);
+        assert_eq!(
+            points(&t),
+            [Point::new(0, 0), Point::new(1, 0), Point::new(2, 0)]
+        );
+
+        // full trail pushes out the oldest position, iteration still starts from it
+        for i in 3..8 {
+            assert_eq!(t.push(Point::new(i, 0)), Some(Point::new(i - 3, 0)));
+            assert_eq!(t.len(), 3);
+        }
+        assert_eq!(
+            points(&t),
+            [Point::new(5, 0), Point::new(6, 0), Point::new(7, 0)]
+        );
+
+        t.clear();
+        assert!(t.is_empty());
+        assert_eq!(points(&t), []);
+        assert_eq!(t.push(Point::new(9, 0)), None);
+        assert_eq!(points(&t), [Point::new(9, 0)]);
+    }
+
+    #[test]
+    fn disabled() {
+        let mut t = Trail::<0>::new();
+
+        assert_eq!(t.push(Point::new(1, 2)), Some(Point::new(1, 2)));
+        assert!(t.is_empty());
+        assert_eq!(t.iter().count(), 0);
+    }
+
+    #[test]
+    fn fade_colors() {
+        let (fg, bg) = (Rgb565::WHITE, Rgb565::new(1, 2, 3));
+
+        assert_eq!(fade(fg, bg, 4, 4), fg);
+        assert_eq!(fade(fg, bg, 0, 4), bg);
+        assert_eq!(fade(fg, Rgb565::BLACK, 1, 2), Rgb565::new(15, 31, 15));
+        assert_eq!(fade(fg, bg, 1, 4), Rgb565::new(8, 17, 10));
+    }
+}
@@ -0,0 +1,277 @@
+use core::ops::{Add, Div, Mul, Neg, Sub};
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+use fixed::types::extra::LeEqU32;
+use fixed::FixedI32;
+use ili9341::Orientation;
+
+// ILI9341 panel size in its native portrait orientation
+const PANEL_W: u32 = 240;
+const PANEL_H: u32 = 320;
+
+// conversion between world coordinates and screen pixels
+pub trait Coord: Copy {
+    fn to_px(self) -> i32;
+    fn from_px(p: i32) -> Self;
+}
+
+impl Coord for i32 {
+    fn to_px(self) -> i32 {
+        self
+    }
+
+    fn from_px(p: i32) -> i32 {
+        p
+    }
+}
+
+impl Coord for f32 {
+    fn to_px(self) -> i32 {
+        self as i32
+    }
+
+    fn from_px(p: i32) -> f32 {
+        p as f32
+    }
+}
+
+impl<F: LeEqU32> Coord for FixedI32<F> {
+    fn to_px(self) -> i32 {
+        self.to_num::<i32>()
+    }
+
+    fn from_px(p: i32) -> FixedI32<F> {
+        FixedI32::<F>::from_num(p)
+    }
+}
+
+// ili9341::Orientation is neither Copy nor Clone, so keep our own copy around
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rotation {
+    Portrait,
+    PortraitFlipped,
+    Landscape,
+    LandscapeFlipped,
+}
+
+impl Rotation {
+    pub fn orientation(self) -> Orientation {
+        match self {
+            Rotation::Portrait => Orientation::Portrait,
+            Rotation::PortraitFlipped => Orientation::PortraitFlipped,
+            Rotation::Landscape => Orientation::Landscape,
+            Rotation::LandscapeFlipped => Orientation::LandscapeFlipped,
+        }
+    }
+
+    // screen size as seen by the viewer in this orientation
+    pub fn size(self) -> Size {
+        match self {
+            Rotation::Portrait | Rotation::PortraitFlipped => Size::new(PANEL_W, PANEL_H),
+            Rotation::Landscape | Rotation::LandscapeFlipped => Size::new(PANEL_H, PANEL_W),
+        }
+    }
+}
+
+// Maps world coordinates onto a screen area: world origin is the bottom-left
+// corner of the area, x axis points right and y axis points up in any orientation.
+#[derive(Debug, Clone, Copy)]
+pub struct Viewport<N>
+where
+    N: Sub<Output = N> + Div<Output = N> + Mul<Output = N> + Add<Output = N> + Neg<Output = N>,
+    N: Default + Copy + Clone + PartialOrd + Coord,
+{
+    rot: Rotation,
+    area: Rectangle,
+    size: Size,
+    scale: N,
+}
+
+impl<N> Viewport<N>
+where
+    N: Sub<Output = N> + Div<Output = N> + Mul<Output = N> + Add<Output = N> + Neg<Output = N>,
+    N: Default + Copy + Clone + PartialOrd + Coord,
+{
+    // screen area is given by its top-left corner and size, scale is pixels per world unit
+    pub fn new(rot: Rotation, top_left: Point, size: Size, scale: N) -> Viewport<N> {
+        Viewport {
+            rot,
+            area: Rectangle::new(
+                top_left,
+                top_left + Point::new(size.width as i32 - 1, size.height as i32 - 1),
+            ),
+            size,
+            scale,
+        }
+    }
+
+    // fit world of size (ww, wh) into the whole screen keeping aspect ratio
+    pub fn fit(rot: Rotation, ww: N, wh: N) -> Viewport<N> {
+        let screen = rot.size();
+        let sw = N::from_px(screen.width as i32);
+        let sh = N::from_px(screen.height as i32);
+        let scale = if sw / ww < sh / wh { sw / ww } else { sh / wh };
+        let size = Size::new((ww * scale).to_px() as u32, (wh * scale).to_px() as u32);
+        let top_left = Point::new(
+            (screen.width - size.width) as i32 / 2,
+            (screen.height - size.height) as i32 / 2,
+        );
+
+        Viewport::new(rot, top_left, size, scale)
+    }
+
+    pub fn rotation(&self) -> Rotation {
+        self.rot
+    }
+
+    pub fn orientation(&self) -> Orientation {
+        self.rot.orientation()
+    }
+
+    pub fn area(&self) -> Rectangle {
+        self.area
+    }
+
+    pub fn world_width(&self) -> N {
+        N::from_px(self.size.width as i32) / self.scale
+    }
+
+    pub fn world_height(&self) -> N {
+        N::from_px(self.size.height as i32) / self.scale
+    }
+
+    // world length to pixels
+    pub fn to_len(&self, l: N) -> i32 {
+        (l * self.scale).to_px()
+    }
+
+    pub fn to_screen(&self, x: N, y: N) -> Point {
+        Point::new(
+            self.area.top_left.x + (x * self.scale).to_px(),
+            self.area.bottom_right.y - (y * self.scale).to_px(),
+        )
+    }
+
+    pub fn to_world(&self, p: Point) -> (N, N) {
+        (
+            N::from_px(p.x - self.area.top_left.x) / self.scale,
+            N::from_px(self.area.bottom_right.y - p.y) / self.scale,
+        )
+    }
+
+    // screen corners of a world box with center (cx, cy) and half sizes (hw, hh)
+    pub fn to_rect(&self, cx: N, cy: N, hw: N, hh: N) -> (Point, Point) {
+        (
+            self.to_screen(cx - hw, cy + hh),
+            self.to_screen(cx + hw, cy - hh),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROTATIONS: [Rotation; 4] = [
+        Rotation::Portrait,
+        Rotation::PortraitFlipped,
+        Rotation::Landscape,
+        Rotation::LandscapeFlipped,
+    ];
+
+    #[test]
+    fn size() {
+        for rot in ROTATIONS.iter() {
+            let s = rot.size();
+
+            match rot {
+                Rotation::Portrait | Rotation::PortraitFlipped => assert!(s.width < s.height),
+                Rotation::Landscape | Rotation::LandscapeFlipped => assert!(s.width > s.height),
+            }
+            assert_eq!(s.width * s.height, 240 * 320);
+        }
+    }
+
+    #[test]
+    fn to_screen_round_trip() {
+        for rot in ROTATIONS.iter() {
+            let vp = Viewport::new(*rot, Point::new(10, 20), Size::new(100, 50), 2.0f32);
+            assert_eq!((vp.world_width(), vp.world_height()), (50.0, 25.0));
+
+            // world origin is the bottom-left corner, y axis points up
+            assert_eq!(vp.to_screen(0.0, 0.0), Point::new(10, 69));
+            assert_eq!(vp.to_screen(3.0, 4.0), Point::new(16, 61));
+
+            for x in 0..50 {
+                for y in 0..25 {
+                    let (wx, wy) = (x as f32, y as f32);
+                    assert_eq!(vp.to_world(vp.to_screen(wx, wy)), (wx, wy));
+                }
+            }
+
+            let vp = Viewport::new(*rot, Point::new(10, 20), Size::new(100, 50), 1);
+            let area = vp.area();
+
+            for x in area.top_left.x..=area.bottom_right.x {
+                for y in area.top_left.y..=area.bottom_right.y {
+                    let (wx, wy) = vp.to_world(Point::new(x, y));
+                    assert!(wx >= 0 && wy >= 0);
+                    assert_eq!(vp.to_screen(wx, wy), Point::new(x, y));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn to_rect() {
+        let vp = Viewport::new(Rotation::Portrait, Point::new(0, 0), Size::new(240, 320), 1);
+
+        assert_eq!(
+            vp.to_rect(10, 20, 3, 2),
+            (Point::new(7, 319 - 22), Point::new(13, 319 - 18))
+        );
+        assert_eq!(vp.to_len(7), 7);
+    }
+
+    #[test]
+    fn fit() {
+        // wide world: full screen width, centered vertically
+        let expected = [
+            (Point::new(0, 115), Size::new(240, 90), 1.5),
+            (Point::new(0, 115), Size::new(240, 90), 1.5),
+            (Point::new(0, 60), Size::new(320, 120), 2.0),
+            (Point::new(0, 60), Size::new(320, 120), 2.0),
+        ];
+
+        for (rot, (tl, size, scale)) in ROTATIONS.iter().zip(expected.iter()) {
+            let vp = Viewport::fit(*rot, 160.0f32, 60.0);
+
+            assert_eq!(vp.rotation(), *rot);
+            assert_eq!(vp.area().top_left, *tl);
+            assert_eq!(
+                vp.area().bottom_right,
+                *tl + Point::new(size.width as i32 - 1, size.height as i32 - 1)
+            );
+            assert_eq!((vp.world_width(), vp.world_height()), (160.0, 60.0));
+            assert_eq!(vp.to_len(10.0), (10.0 * scale) as i32);
+        }
+
+        // tall world: full screen height, centered horizontally
+        let expected = [
+            (Point::new(60, 0), Size::new(120, 320)),
+            (Point::new(60, 0), Size::new(120, 320)),
+            (Point::new(115, 0), Size::new(90, 240)),
+            (Point::new(115, 0), Size::new(90, 240)),
+        ];
+
+        for (rot, (tl, size)) in ROTATIONS.iter().zip(expected.iter()) {
+            let vp = Viewport::fit(*rot, 60.0f32, 160.0);
+
+            assert_eq!(vp.area().top_left, *tl);
+            assert_eq!(
+                vp.area().bottom_right,
+                *tl + Point::new(size.width as i32 - 1, size.height as i32 - 1)
+            );
+        }
+    }
+}
@@ -0,0 +1,277 @@
+use core::sync::atomic::{self, Ordering};
+use cortex_m::peripheral::NVIC;
+use embedded_hal::adc::Channel;
+use stm32f1xx_hal::adc::{Adc, AdcDma, Align, ChannelTimeSequence, SampleTime, Scan, SetChannels};
+use stm32f1xx_hal::dma::{dma1::C1, Event, TransferPayload};
+use stm32f1xx_hal::pac::adc1::cr2::EXTSEL_A;
+use stm32f1xx_hal::pac::{Interrupt, ADC1, TIM3};
+use stm32f1xx_hal::rcc::Clocks;
+use stm32f1xx_hal::timer::Timer;
+
+// Scope front end: ADC1 conversions are started by TIM3 update events (TRGO)
+// at the selected sample rate, DMA1 channel 1 moves samples into a ping-pong
+// buffer in circular mode. Half transfer and transfer complete interrupts
+// mark the end of the first and the second half, so one half can be
+// processed while DMA fills the other one.
+
+// samples in each half of the buffer
+pub const BUF_LEN: usize = 512;
+
+pub type Buffer = [[u16; BUF_LEN]; 2];
+
+// ADC sample time: 7.5 + 12.5 cycles per conversion, with 12MHz ADC clock
+// this is fast enough for the highest sample rate
+const SAMPLE_TIME: SampleTime = SampleTime::T_7;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SampleRate {
+    Hz100,
+    Hz200,
+    Hz500,
+    KHz1,
+    KHz2,
+    KHz5,
+    KHz10,
+    KHz20,
+    KHz50,
+    KHz100,
+    KHz200,
+    KHz500,
+}
+
+const RATES: [SampleRate; 12] = [
+    SampleRate::Hz100,
+    SampleRate::Hz200,
+    SampleRate::Hz500,
+    SampleRate::KHz1,
+    SampleRate::KHz2,
+    SampleRate::KHz5,
+    SampleRate::KHz10,
+    SampleRate::KHz20,
+    SampleRate::KHz50,
+    SampleRate::KHz100,
+    SampleRate::KHz200,
+    SampleRate::KHz500,
+];
+
+impl SampleRate {
+    pub fn hz(self) -> u32 {
+        match self {
+            SampleRate::Hz100 => 100,
+            SampleRate::Hz200 => 200,
+            SampleRate::Hz500 => 500,
+            SampleRate::KHz1 => 1_000,
+            SampleRate::KHz2 => 2_000,
+            SampleRate::KHz5 => 5_000,
+            SampleRate::KHz10 => 10_000,
+            SampleRate::KHz20 => 20_000,
+            SampleRate::KHz50 => 50_000,
+            SampleRate::KHz100 => 100_000,
+            SampleRate::KHz200 => 200_000,
+            SampleRate::KHz500 => 500_000,
+        }
+    }
+
+    // next faster rate, the fastest one is kept
+    pub fn faster(self) -> SampleRate {
+        let i = self.index();
+        RATES[if i + 1 < RATES.len() { i + 1 } else { i }]
+    }
+
+    // next slower rate, the slowest one is kept
+    pub fn slower(self) -> SampleRate {
+        let i = self.index();
+        RATES[if i > 0 { i - 1 } else { i }]
+    }
+
+    fn index(self) -> usize {
+        RATES.iter().position(|r| *r == self).unwrap_or(0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Error {
+    // no completed half is waiting to be read
+    NotReady,
+    // DMA has overwritten the data before it was read
+    Overrun,
+}
+
+// analog input pin wrapper: configures ADC1 regular sequence for the pin channel
+pub struct Probe<PIN>(pub PIN);
+
+impl<PIN> SetChannels<Probe<PIN>> for Adc<ADC1>
+where
+    PIN: Channel<ADC1, ID = u8>,
+{
+    fn set_samples(&mut self) {
+        self.set_channel_sample_time(PIN::channel(), SAMPLE_TIME);
+    }
+
+    fn set_sequence(&mut self) {
+        self.set_regular_sequence(&[PIN::channel()]);
+    }
+}
+
+pub struct Capture<PIN> {
+    dma: AdcDma<Probe<PIN>, Scan>,
+    tim: TIM3,
+    clk: u32,
+    buf: &'static mut Buffer,
+    rate: SampleRate,
+    overruns: u32,
+}
+
+impl<PIN> Capture<PIN>
+where
+    PIN: Channel<ADC1, ID = u8>,
+{
+    pub fn new(
+        mut adc: Adc<ADC1>,
+        pin: PIN,
+        ch: C1,
+        tim: Timer<TIM3>,
+        clocks: &Clocks,
+        buf: &'static mut Buffer,
+        rate: SampleRate,
+    ) -> Capture<PIN> {
+        // timer is programmed directly to get exact sample rates
+        let tim = tim.release();
+        tim.cr2.modify(|_, w| w.mms().update());
+
+        adc.set_align(Align::Right);
+        adc.set_external_trigger(EXTSEL_A::TIM3TRGO);
+
+        let mut dma = adc.with_scan_dma(Probe(pin), ch);
+
+        dma.channel
+            .set_peripheral_address(unsafe { &(*ADC1::ptr()).dr as *const _ as u32 }, false);
+        dma.channel.set_memory_address(buf.as_ptr() as u32, true);
+        dma.channel.set_transfer_length(2 * BUF_LEN);
+
+        atomic::compiler_fence(Ordering::Release);
+
+        dma.channel.ch().cr.modify(|_, w| {
+            w.mem2mem()
+                .clear_bit()
+                .pl()
+                .high()
+                .msize()
+                .bits16()
+                .psize()
+                .bits16()
+                .circ()
+                .set_bit()
+                .dir()
+                .clear_bit()
+        });
+
+        dma.channel.listen(Event::HalfTransfer);
+        dma.channel.listen(Event::TransferComplete);
+        dma.start();
+
+        let mut capture = Capture {
+            dma,
+            tim,
+            clk: clocks.pclk1_tim().0,
+            buf,
+            rate,
+            overruns: 0,
+        };
+
+        capture.start_timer();
+        capture
+    }
+
+    pub fn get_rate(&self) -> SampleRate {
+        self.rate
+    }
+
+    // restart capture from the beginning of the buffer at the new rate,
+    // so completed buffers never mix samples taken at different rates
+    pub fn set_rate(&mut self, rate: SampleRate) {
+        self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+
+        self.dma.channel.stop();
+
+        // drop flags and the pending interrupt of the interrupted buffer, otherwise
+        // read would pass a half of old samples or report a false overrun
+        self.dma
+            .channel
+            .ifcr()
+            .write(|w| w.chtif1().set_bit().ctcif1().set_bit().cgif1().set_bit());
+        NVIC::unpend(Interrupt::DMA1_CHANNEL1);
+
+        self.dma.channel.set_transfer_length(2 * BUF_LEN);
+        self.dma.channel.start();
+
+        self.rate = rate;
+        self.start_timer();
+    }
+
+    pub fn get_overruns(&self) -> u32 {
+        self.overruns
+    }
+
+    // Call from DMA1_CHANNEL1 interrupt: passes the completed half to f.
+    // Overrun is reported when the next half has been completed as well,
+    // either before the call or while f was running.
+    pub fn read<R, F>(&mut self, f: F) -> Result<R, Error>
+    where
+        F: FnOnce(&[u16]) -> R,
+    {
+        let isr = self.dma.channel.isr();
+        let first = isr.htif1().bit_is_set();
+        let second = isr.tcif1().bit_is_set();
+
+        self.dma
+            .channel
+            .ifcr()
+            .write(|w| w.chtif1().set_bit().ctcif1().set_bit().cgif1().set_bit());
+
+        let half = match (first, second) {
+            (true, false) => 0,
+            (false, true) => 1,
+            (true, true) => {
+                self.overruns += 1;
+                return Err(Error::Overrun);
+            }
+            (false, false) => return Err(Error::NotReady),
+        };
+
+        atomic::compiler_fence(Ordering::Acquire);
+
+        let res = f(&self.buf[half]);
+
+        atomic::compiler_fence(Ordering::Acquire);
+
+        // DMA went on to the half that has just been read
+        let isr = self.dma.channel.isr();
+        let next = if half == 0 {
+            isr.tcif1().bit_is_set()
+        } else {
+            isr.htif1().bit_is_set()
+        };
+
+        if next {
+            self.overruns += 1;
+            return Err(Error::Overrun);
+        }
+
+        Ok(res)
+    }
+
+    fn start_timer(&mut self) {
+        // update event every ticks timer clocks: ticks = (psc + 1) * (arr + 1)
+        let ticks = self.clk / self.rate.hz();
+        let psc = (ticks - 1) / (1 << 16);
+        let arr = ticks / (psc + 1) - 1;
+
+        self.tim.psc.write(|w| w.psc().bits(psc as u16));
+        self.tim.arr.write(|w| w.arr().bits(arr as u16));
+
+        // load the new prescaler value
+        self.tim.egr.write(|w| w.ug().set_bit());
+        self.tim.cr1.modify(|_, w| w.cen().set_bit());
+    }
+}
@@ -0,0 +1,609 @@
+use stm32f1xx_hal::flash;
+use stm32f1xx_hal::flash::{FlashSize, SectorSize};
+
+// EEPROM emulation in the last flash pages, see memory.x
+pub const BASE: u32 = 60 * 1024;
+pub const PAGE_SIZE: u32 = 1024;
+pub const PAGES: u32 = 4;
+
+// max record payload size
+pub const MAX_DATA: usize = 128;
+
+const MAGIC: u16 = 0xa55a;
+const EMPTY: u16 = 0xffff;
+const PAGE_HDR: u32 = 4;
+const REC_HDR: u32 = 4;
+const REC_CRC: u32 = 2;
+
+// raw access to storage pages, offsets are relative to the storage start
+pub trait Flash {
+    type Error;
+
+    fn read(&mut self, offset: u32, buf: &mut [u8]) -> Result<(), Self::Error>;
+    fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), Self::Error>;
+    fn erase(&mut self, offset: u32) -> Result<(), Self::Error>;
+}
+
+impl Flash for flash::Parts {
+    type Error = flash::Error;
+
+    fn read(&mut self, offset: u32, buf: &mut [u8]) -> Result<(), flash::Error> {
+        let writer = self.writer(SectorSize::Sz1K, FlashSize::Sz64K);
+        buf.copy_from_slice(writer.read(BASE + offset, buf.len())?);
+        Ok(())
+    }
+
+    fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), flash::Error> {
+        let mut writer = self.writer(SectorSize::Sz1K, FlashSize::Sz64K);
+        writer.write(BASE + offset, data)
+    }
+
+    fn erase(&mut self, offset: u32) -> Result<(), flash::Error> {
+        let mut writer = self.writer(SectorSize::Sz1K, FlashSize::Sz64K);
+        writer.page_erase(BASE + offset)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Error<E> {
+    Flash(E),
+    NotFound,
+    TooLong,
+    NoSpace,
+}
+
+impl<E> From<E> for Error<E> {
+    fn from(e: E) -> Error<E> {
+        Error::Flash(e)
+    }
+}
+
+pub fn crc16(crc: u16, data: &[u8]) -> u16 {
+    let mut crc = crc;
+
+    for b in data {
+        crc ^= (*b as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+
+    crc
+}
+
+// record header: key, data length and crc check result
+type Record = (u16, u32, bool);
+
+fn padded(len: u32) -> u32 {
+    (len + 1) & !1
+}
+
+// Log-structured key/value store. Records are appended to the active page:
+//   key (u16), len (u16), data (padded to u16), crc16 of key, len and data.
+// The last valid record for a key wins. When the active page is full, latest
+// records are copied to the next page in round-robin order and the page header
+// (magic, sequence number) is written last, so an interrupted copy is ignored.
+pub struct Store<F: Flash> {
+    flash: F,
+    page: u32,
+    seq: u16,
+    end: u32,
+}
+
+impl<F: Flash> Store<F> {
+    pub fn mount(mut flash: F) -> Result<Store<F>, Error<F::Error>> {
+        let mut active: Option<(u32, u16)> = None;
+
+        for page in 0..PAGES {
+            let mut hdr = [0u8; PAGE_HDR as usize];
+            flash.read(page * PAGE_SIZE, &mut hdr)?;
+
+            let magic = u16::from_le_bytes([hdr[0], hdr[1]]);
+            let seq = u16::from_le_bytes([hdr[2], hdr[3]]);
+
+            if magic != MAGIC {
+                continue;
+            }
+
+            active = match active {
+                Some((_, s)) if (seq.wrapping_sub(s) as i16) <= 0 => active,
+                _ => Some((page, seq)),
+            };
+        }
+
+        let mut store = Store {
+            flash,
+            page: 0,
+            seq: 0,
+            end: PAGE_HDR,
+        };
+
+        match active {
+            Some((page, seq)) => {
+                store.page = page;
+                store.seq = seq;
+                store.end = store.scan()?;
+            }
+            None => store.format()?,
+        }
+
+        Ok(store)
+    }
+
+    pub fn release(self) -> F {
+        self.flash
+    }
+
+    // erase all the pages and start from scratch
+    pub fn format(&mut self) -> Result<(), Error<F::Error>> {
+        for page in 0..PAGES {
+            self.flash.erase(page * PAGE_SIZE)?;
+        }
+
+        self.page = 0;
+        self.seq = 0;
+        self.end = PAGE_HDR;
+        self.write_header(0, 0)
+    }
+
+    // read the latest value of the key into buf, returns value length
+    pub fn read(&mut self, key: u16, buf: &mut [u8]) -> Result<usize, Error<F::Error>> {
+        let mut found: Option<(u32, u32)> = None;
+        let mut offset = PAGE_HDR;
+
+        while let Some((k, len, valid)) = self.record(self.page, offset)? {
+            if valid && k == key {
+                found = Some((offset, len));
+            }
+            offset += REC_HDR + padded(len) + REC_CRC;
+        }
+
+        let (offset, len) = found.ok_or(Error::NotFound)?;
+
+        if len as usize > buf.len() {
+            return Err(Error::TooLong);
+        }
+
+        let base = self.page * PAGE_SIZE + offset + REC_HDR;
+        self.flash.read(base, &mut buf[..len as usize])?;
+
+        Ok(len as usize)
+    }
+
+    pub fn write(&mut self, key: u16, data: &[u8]) -> Result<(), Error<F::Error>> {
+        let size = REC_HDR + padded(data.len() as u32) + REC_CRC;
+
+        if key == EMPTY || data.len() > MAX_DATA || size > PAGE_SIZE - PAGE_HDR {
+            return Err(Error::TooLong);
+        }
+
+        if self.end + size > PAGE_SIZE {
+            self.compact()?;
+        }
+
+        if self.end + size > PAGE_SIZE {
+            return Err(Error::NoSpace);
+        }
+
+        let offset = self.page * PAGE_SIZE + self.end;
+        self.append(offset, key, data)?;
+        self.end += size;
+
+        Ok(())
+    }
+
+    fn append(&mut self, offset: u32, key: u16, data: &[u8]) -> Result<(), Error<F::Error>> {
+        let mut hdr = [0u8; REC_HDR as usize];
+        let mut buf = [0xffu8; MAX_DATA + 1];
+        let len = padded(data.len() as u32) as usize;
+
+        hdr[0..2].copy_from_slice(&key.to_le_bytes());
+        hdr[2..4].copy_from_slice(&(data.len() as u16).to_le_bytes());
+        buf[..data.len()].copy_from_slice(data);
+
+        let crc = crc16(crc16(0xffff, &hdr), data);
+
+        self.flash.write(offset, &hdr)?;
+        self.flash.write(offset + REC_HDR, &buf[..len])?;
+        self.flash
+            .write(offset + REC_HDR + len as u32, &crc.to_le_bytes())?;
+
+        Ok(())
+    }
+
+    fn write_header(&mut self, page: u32, seq: u16) -> Result<(), Error<F::Error>> {
+        let mut hdr = [0u8; PAGE_HDR as usize];
+
+        hdr[0..2].copy_from_slice(&MAGIC.to_le_bytes());
+        hdr[2..4].copy_from_slice(&seq.to_le_bytes());
+        self.flash.write(page * PAGE_SIZE, &hdr)?;
+
+        Ok(())
+    }
+
+    // parse record header at the offset: (key, len, crc is valid), None at the end of records
+    fn record(&mut self, page: u32, offset: u32) -> Result<Option<Record>, Error<F::Error>> {
+        if offset + REC_HDR + REC_CRC > PAGE_SIZE {
+            return Ok(None);
+        }
+
+        let base = page * PAGE_SIZE + offset;
+        let mut hdr = [0u8; REC_HDR as usize];
+        self.flash.read(base, &mut hdr)?;
+
+        let key = u16::from_le_bytes([hdr[0], hdr[1]]);
+        let len = u16::from_le_bytes([hdr[2], hdr[3]]) as u32;
+
+        if key == EMPTY {
+            return Ok(None);
+        }
+
+        if len as usize > MAX_DATA || offset + REC_HDR + padded(len) + REC_CRC > PAGE_SIZE {
+            // broken header: treat the rest of the page as used
+            return Ok(None);
+        }
+
+        let mut buf = [0u8; MAX_DATA];
+        let mut tail = [0u8; REC_CRC as usize];
+        self.flash.read(base + REC_HDR, &mut buf[..len as usize])?;
+        self.flash.read(base + REC_HDR + padded(len), &mut tail)?;
+
+        let crc = crc16(crc16(0xffff, &hdr), &buf[..len as usize]);
+
+        Ok(Some((key, len, crc == u16::from_le_bytes(tail))))
+    }
+
+    // find the end of records in the active page
+    fn scan(&mut self) -> Result<u32, Error<F::Error>> {
+        let mut offset = PAGE_HDR;
+
+        loop {
+            match self.record(self.page, offset)? {
+                Some((_, len, _)) => offset += REC_HDR + padded(len) + REC_CRC,
+                None => {
+                    let mut hdr = [0u8; 2];
+
+                    if offset + 2 <= PAGE_SIZE {
+                        self.flash.read(self.page * PAGE_SIZE + offset, &mut hdr)?;
+                    }
+
+                    // broken record header: no more appends to this page
+                    if u16::from_le_bytes(hdr) != EMPTY {
+                        return Ok(PAGE_SIZE);
+                    }
+
+                    return Ok(offset);
+                }
+            }
+        }
+    }
+
+    // copy the latest valid records to the next page
+    fn compact(&mut self) -> Result<(), Error<F::Error>> {
+        let next = (self.page + 1) % PAGES;
+        let mut buf = [0u8; MAX_DATA];
+        let mut src = PAGE_HDR;
+        let mut dst = PAGE_HDR;
+
+        self.flash.erase(next * PAGE_SIZE)?;
+
+        while let Some((key, len, valid)) = self.record(self.page, src)? {
+            let size = REC_HDR + padded(len) + REC_CRC;
+
+            if valid && self.is_latest(key, src + size)? {
+                let base = self.page * PAGE_SIZE + src + REC_HDR;
+                self.flash.read(base, &mut buf[..len as usize])?;
+                self.append(next * PAGE_SIZE + dst, key, &buf[..len as usize])?;
+                dst += size;
+            }
+
+            src += size;
+        }
+
+        self.seq = self.seq.wrapping_add(1);
+        self.write_header(next, self.seq)?;
+        self.flash.erase(self.page * PAGE_SIZE)?;
+        self.page = next;
+        self.end = dst;
+
+        Ok(())
+    }
+
+    // no valid records for the key after the offset
+    fn is_latest(&mut self, key: u16, offset: u32) -> Result<bool, Error<F::Error>> {
+        let mut offset = offset;
+
+        while let Some((k, len, valid)) = self.record(self.page, offset)? {
+            if valid && k == key {
+                return Ok(false);
+            }
+            offset += REC_HDR + padded(len) + REC_CRC;
+        }
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    const SIZE: usize = (PAGES * PAGE_SIZE) as usize;
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum RamError {
+        // half-word is not erased or write is not half-word aligned
+        Program,
+        // write budget is used up
+        PowerLoss,
+    }
+
+    // RAM-backed flash: erased bytes read as 0xff, only erased half-words can be
+    // written. Power loss is emulated by a budget of bytes written before failure.
+    pub struct Ram {
+        pub mem: Vec<u8>,
+        pub budget: Option<usize>,
+    }
+
+    impl Default for Ram {
+        fn default() -> Ram {
+            Ram::new()
+        }
+    }
+
+    impl Ram {
+        pub fn new() -> Ram {
+            Ram {
+                mem: vec![0xff; SIZE],
+                budget: None,
+            }
+        }
+    }
+
+    impl Flash for Ram {
+        type Error = RamError;
+
+        fn read(&mut self, offset: u32, buf: &mut [u8]) -> Result<(), RamError> {
+            let offset = offset as usize;
+            buf.copy_from_slice(&self.mem[offset..offset + buf.len()]);
+            Ok(())
+        }
+
+        fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), RamError> {
+            let offset = offset as usize;
+            let target = &mut self.mem[offset..offset + data.len()];
+
+            if (offset | data.len()) & 1 != 0 || target.iter().any(|b| *b != 0xff) {
+                return Err(RamError::Program);
+            }
+
+            for (t, d) in target.iter_mut().zip(data) {
+                match self.budget {
+                    Some(0) => return Err(RamError::PowerLoss),
+                    Some(n) => self.budget = Some(n - 1),
+                    None => {}
+                }
+                *t = *d;
+            }
+
+            Ok(())
+        }
+
+        fn erase(&mut self, offset: u32) -> Result<(), RamError> {
+            let offset = (offset - offset % PAGE_SIZE) as usize;
+            self.mem[offset..offset + PAGE_SIZE as usize]
+                .iter_mut()
+                .for_each(|b| *b = 0xff);
+            Ok(())
+        }
+    }
+
+    fn read(store: &mut Store<Ram>, key: u16) -> Result<Vec<u8>, Error<RamError>> {
+        let mut buf = [0u8; MAX_DATA];
+        let len = store.read(key, &mut buf)?;
+        Ok(buf[..len].to_vec())
+    }
+
+    // pages with a valid header
+    fn active(ram: &Ram) -> Vec<u32> {
+        (0..PAGES)
+            .filter(|p| {
+                let o = (p * PAGE_SIZE) as usize;
+                u16::from_le_bytes([ram.mem[o], ram.mem[o + 1]]) == MAGIC
+            })
+            .collect()
+    }
+
+    // unmount and mount again from the flash contents
+    fn remount(store: Store<Ram>) -> Store<Ram> {
+        let mut ram = store.release();
+        ram.budget = None;
+        Store::mount(ram).unwrap()
+    }
+
+    #[test]
+    fn format_empty_flash() {
+        let mut store = Store::mount(Ram::new()).unwrap();
+
+        assert_eq!(read(&mut store, 1), Err(Error::NotFound));
+        assert_eq!(active(&store.release()), [0]);
+    }
+
+    #[test]
+    fn latest_record_wins() {
+        let mut store = Store::mount(Ram::new()).unwrap();
+
+        store.write(1, b"one").unwrap();
+        store.write(2, b"two").unwrap();
+        store.write(1, b"three").unwrap();
+
+        assert_eq!(read(&mut store, 1).unwrap(), b"three");
+        assert_eq!(read(&mut store, 2).unwrap(), b"two");
+
+        let mut store = remount(store);
+        assert_eq!(read(&mut store, 1).unwrap(), b"three");
+
+        let mut short = [0u8; 2];
+        assert_eq!(store.read(1, &mut short), Err(Error::TooLong));
+        assert_eq!(store.write(EMPTY, b"x"), Err(Error::TooLong));
+        assert_eq!(store.write(1, &[0; MAX_DATA + 1]), Err(Error::TooLong));
+    }
+
+    #[test]
+    fn corrupted_record_is_skipped() {
+        let mut store = Store::mount(Ram::new()).unwrap();
+
+        store.write(1, b"aa").unwrap();
+        store.write(1, b"bb").unwrap();
+
+        // second record: page header, first record, its own header
+        let mut ram = store.release();
+        ram.mem[(PAGE_HDR + 8 + REC_HDR) as usize] = b'x';
+
+        let mut store = Store::mount(ram).unwrap();
+        assert_eq!(read(&mut store, 1).unwrap(), b"aa");
+
+        // appends continue after the corrupted record
+        store.write(1, b"cc").unwrap();
+        assert_eq!(read(&mut remount(store), 1).unwrap(), b"cc");
+    }
+
+    #[test]
+    fn compaction_rotates_pages() {
+        let mut store = Store::mount(Ram::new()).unwrap();
+        let mut pages = vec![0];
+
+        store.write(100, b"kept").unwrap();
+
+        let mut i = 0u32;
+
+        // until the store comes back to the first page
+        while pages.len() < 5 {
+            store.write(1 + (i % 2) as u16, &i.to_le_bytes()).unwrap();
+            i += 1;
+
+            let mut ram = store.release();
+            let now = active(&ram);
+            assert_eq!(now.len(), 1);
+
+            if now[0] != *pages.last().unwrap() {
+                pages.push(now[0]);
+            }
+
+            ram.budget = None;
+            store = Store::mount(ram).unwrap();
+        }
+
+        assert_eq!(pages, [0, 1, 2, 3, 0]);
+        assert_eq!(read(&mut store, 100).unwrap(), b"kept");
+        // even values go to key 1, odd ones to key 2
+        let last = i - 1;
+        assert_eq!(
+            read(&mut store, 1).unwrap(),
+            (last - last % 2).to_le_bytes()
+        );
+        assert_eq!(
+            read(&mut store, 2).unwrap(),
+            (last - 1 + last % 2).to_le_bytes()
+        );
+    }
+
+    #[test]
+    fn full_page_of_live_records() {
+        let mut store = Store::mount(Ram::new()).unwrap();
+        let data = [0x55; MAX_DATA];
+        let mut key = 0;
+
+        // nothing to drop on compaction
+        let err = loop {
+            match store.write(key, &data) {
+                Ok(()) => key += 1,
+                Err(e) => break e,
+            }
+        };
+
+        assert_eq!(err, Error::NoSpace);
+        assert_eq!(
+            key,
+            ((PAGE_SIZE - PAGE_HDR) / (REC_HDR + MAX_DATA as u32 + REC_CRC)) as u16
+        );
+        assert_eq!(read(&mut store, 0).unwrap(), &data[..]);
+    }
+
+    #[test]
+    fn interrupted_record_header() {
+        let mut store = Store::mount(Ram::new()).unwrap();
+        store.write(1, b"old").unwrap();
+
+        // only the key of the new record is written
+        let mut ram = store.release();
+        ram.budget = Some(2);
+        let mut store = Store::mount(ram).unwrap();
+        assert_eq!(
+            store.write(1, b"new"),
+            Err(Error::Flash(RamError::PowerLoss))
+        );
+
+        let mut store = remount(store);
+        assert_eq!(read(&mut store, 1).unwrap(), b"old");
+
+        // the page is treated as full, the next write moves to the next page
+        store.write(2, b"two").unwrap();
+        assert_eq!(active(&store.release()), [1]);
+    }
+
+    #[test]
+    fn interrupted_record_tail() {
+        let mut store = Store::mount(Ram::new()).unwrap();
+        store.write(1, b"old").unwrap();
+
+        // header and part of the data are written, the CRC is erased
+        let mut ram = store.release();
+        ram.budget = Some(6);
+        let mut store = Store::mount(ram).unwrap();
+        assert_eq!(
+            store.write(1, b"new"),
+            Err(Error::Flash(RamError::PowerLoss))
+        );
+
+        let mut store = remount(store);
+        assert_eq!(read(&mut store, 1).unwrap(), b"old");
+
+        store.write(1, b"again").unwrap();
+        assert_eq!(read(&mut remount(store), 1).unwrap(), b"again");
+    }
+
+    #[test]
+    fn interrupted_compaction() {
+        let mut store = Store::mount(Ram::new()).unwrap();
+        let mut i = 0u32;
+
+        // fill the first page up to the compaction
+        while store.end + 10 <= PAGE_SIZE {
+            store.write(1, &i.to_le_bytes()).unwrap();
+            i += 1;
+        }
+
+        // power lost while copying, before the page header is written
+        let mut ram = store.release();
+        ram.budget = Some(4);
+        let mut store = Store::mount(ram).unwrap();
+        assert!(store.write(2, b"two").is_err());
+
+        let mut store = remount(store);
+        assert_eq!(read(&mut store, 1).unwrap(), (i - 1).to_le_bytes());
+
+        store.write(2, b"two").unwrap();
+        let mut ram = store.release();
+        assert_eq!(active(&ram), [1]);
+
+        ram.budget = None;
+        let mut store = Store::mount(ram).unwrap();
+        assert_eq!(read(&mut store, 1).unwrap(), (i - 1).to_le_bytes());
+        assert_eq!(read(&mut store, 2).unwrap(), b"two");
+    }
+}
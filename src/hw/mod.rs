@@ -1 +1,4 @@
+pub mod capture;
 pub mod delay_timer;
+pub mod eeprom;
+pub mod siggen;
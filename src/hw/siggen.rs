@@ -0,0 +1,303 @@
+use fixed::types::U24F8;
+use stm32f1xx_hal::gpio::gpioa::PA8;
+use stm32f1xx_hal::gpio::{Alternate, PushPull};
+use stm32f1xx_hal::pac::TIM1;
+use stm32f1xx_hal::rcc::Clocks;
+use stm32f1xx_hal::timer::Timer;
+
+// Test signal generator on TIM1 channel 1 output (PA8). Square wave is plain
+// PWM with the timer period set to the signal period. Sine and triangle are
+// made by direct digital synthesis: 8-bit PWM carrier at timer clock / 256,
+// the repetition counter raises update interrupt every REPEAT carrier periods
+// and the interrupt loads the next sample from the phase accumulator. The
+// output needs an RC low-pass filter well below the carrier frequency.
+
+// PWM carrier steps and carrier periods per DDS sample
+const STEPS: u32 = 256;
+const REPEAT: u32 = 8;
+
+// sin(2 * pi * k / 256) * 127 for the first quarter of the period
+const QUARTER: [u8; 65] = [
+    0, 3, 6, 9, 12, 16, 19, 22, 25, 28, 31, 34, 37, 40, 43, 46, 49, 51, 54, 57, 60, 63, 65, 68, 71,
+    73, 76, 78, 81, 83, 85, 88, 90, 92, 94, 96, 98, 100, 102, 104, 106, 107, 109, 111, 112, 113,
+    115, 116, 117, 118, 120, 121, 122, 122, 123, 124, 125, 125, 126, 126, 126, 127, 127, 127, 127,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Waveform {
+    Square,
+    Sine,
+    Triangle,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Config {
+    pub waveform: Waveform,
+    // Hz
+    pub freq: u32,
+    // high part of the period for square wave, percent
+    pub duty: u32,
+}
+
+pub struct SigGen {
+    tim: TIM1,
+    _pin: PA8<Alternate<PushPull>>,
+    clk: u32,
+    cfg: Config,
+    running: bool,
+    // DDS phase accumulator and its increment per sample
+    phase: u32,
+    step: u32,
+}
+
+impl SigGen {
+    pub fn new(
+        tim: Timer<TIM1>,
+        pin: PA8<Alternate<PushPull>>,
+        clocks: &Clocks,
+        cfg: Config,
+    ) -> SigGen {
+        // timer is programmed directly: PWM mode 1 with preloaded compare value
+        let tim = tim.release();
+
+        tim.ccmr1_output_mut()
+            .modify(|_, w| w.oc1m().pwm_mode1().oc1pe().set_bit());
+        tim.ccer
+            .modify(|_, w| w.cc1p().clear_bit().cc1e().set_bit());
+        tim.cr1.modify(|_, w| w.arpe().set_bit().urs().set_bit());
+
+        // advanced timer outputs need main output enable
+        tim.bdtr.modify(|_, w| w.moe().set_bit());
+
+        let mut siggen = SigGen {
+            tim,
+            _pin: pin,
+            clk: clocks.pclk2_tim().0,
+            cfg,
+            running: false,
+            phase: 0,
+            step: 0,
+        };
+
+        siggen.start();
+        siggen
+    }
+
+    pub fn get_config(&self) -> Config {
+        self.cfg
+    }
+
+    pub fn set_config(&mut self, cfg: Config) {
+        self.cfg = cfg;
+
+        if self.running {
+            self.start();
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    pub fn start(&mut self) {
+        self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+        self.tim.dier.modify(|_, w| w.uie().clear_bit());
+
+        let (psc, arr, rep, ccr) = match self.cfg.waveform {
+            Waveform::Square => {
+                let (psc, arr, ccr) = square(self.clk, self.cfg.freq, self.cfg.duty);
+
+                (psc, arr, 0, ccr)
+            }
+            Waveform::Sine | Waveform::Triangle => {
+                // at least four samples per period
+                let rate = self.dds_rate();
+                let freq = self.cfg.freq.min(rate / 4);
+
+                self.step = (((freq as u64) << 32) / rate as u64) as u32;
+                self.phase = 0;
+
+                (0, STEPS - 1, REPEAT - 1, STEPS / 2)
+            }
+        };
+
+        self.tim.psc.write(|w| w.psc().bits(psc as u16));
+        self.tim.arr.write(|w| w.arr().bits(arr as u16));
+        self.tim.rcr.write(|w| unsafe { w.rep().bits(rep as u8) });
+        self.tim.ccr1.write(|w| w.ccr().bits(ccr as u16));
+
+        // load the new values, update interrupt only for DDS samples
+        self.tim.egr.write(|w| w.ug().set_bit());
+        self.tim.sr.modify(|_, w| w.uif().clear_bit());
+
+        if self.cfg.waveform != Waveform::Square {
+            self.tim.dier.modify(|_, w| w.uie().set_bit());
+        }
+
+        self.tim
+            .ccmr1_output_mut()
+            .modify(|_, w| w.oc1m().pwm_mode1());
+        self.tim.cr1.modify(|_, w| w.cen().set_bit());
+
+        self.running = true;
+    }
+
+    // output is forced low
+    pub fn stop(&mut self) {
+        self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+        self.tim.dier.modify(|_, w| w.uie().clear_bit());
+        self.tim
+            .ccmr1_output_mut()
+            .modify(|_, w| w.oc1m().force_inactive());
+
+        self.running = false;
+    }
+
+    // actual output frequency, Hz
+    pub fn get_frequency(&self) -> U24F8 {
+        match self.cfg.waveform {
+            Waveform::Square => {
+                let psc = self.tim.psc.read().psc().bits() as u64 + 1;
+                let arr = self.tim.arr.read().arr().bits() as u64 + 1;
+
+                U24F8::from_bits((((self.clk as u64) << 8) / (psc * arr)) as u32)
+            }
+            Waveform::Sine | Waveform::Triangle => {
+                let rate = self.dds_rate() as u64;
+
+                U24F8::from_bits(((self.step as u64 * rate) >> 24) as u32)
+            }
+        }
+    }
+
+    // DDS samples per second
+    pub fn dds_rate(&self) -> u32 {
+        self.clk / (STEPS * REPEAT)
+    }
+
+    // Call from TIM1_UP interrupt: next DDS sample. The compare value is
+    // preloaded, so it takes effect at the start of the next carrier period.
+    pub fn update(&mut self) {
+        self.tim.sr.modify(|_, w| w.uif().clear_bit());
+
+        self.phase = self.phase.wrapping_add(self.step);
+
+        let v = sample(self.cfg.waveform, self.phase);
+        self.tim.ccr1.write(|w| w.ccr().bits(v));
+    }
+}
+
+// Prescaler, auto-reload and compare values for the square wave. Update event
+// every ticks timer clocks: ticks = (psc + 1) * (arr + 1). The prescaler keeps
+// arr below 0xFFFF, so the compare value for 100% duty, arr + 1, still fits
+// the 16-bit register and holds the output high.
+fn square(clk: u32, freq: u32, duty: u32) -> (u32, u32, u32) {
+    let ticks = (clk / freq.max(1)).max(2);
+    let psc = (ticks - 1) / 0xFFFF;
+    let arr = ticks / (psc + 1) - 1;
+    let duty = duty.min(100);
+
+    (psc, arr, (arr + 1) * duty / 100)
+}
+
+// compare value for the phase, 0..STEPS
+fn sample(waveform: Waveform, phase: u32) -> u16 {
+    match waveform {
+        Waveform::Square => (STEPS / 2) as u16,
+        Waveform::Sine => {
+            let i = (phase >> 24) as usize;
+            let k = i % 64;
+
+            match i / 64 {
+                0 => 128 + QUARTER[k] as u16,
+                1 => 128 + QUARTER[64 - k] as u16,
+                2 => 128 - QUARTER[k] as u16,
+                _ => 128 - QUARTER[64 - k] as u16,
+            }
+        }
+        Waveform::Triangle => {
+            let t = (phase >> 23) as u16;
+
+            if t < 256 {
+                t
+            } else {
+                511 - t
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLK: u32 = 72_000_000;
+
+    #[test]
+    fn square_timing() {
+        assert_eq!(square(CLK, 1000, 50), (1, 35999, 18000));
+        assert_eq!(square(CLK, 1_000_000, 25), (0, 71, 18));
+
+        // 0 Hz is taken as 1 Hz, the shortest period is two timer clocks
+        assert_eq!(square(CLK, 0, 50).0, 1098);
+        assert_eq!(square(CLK, 2 * CLK, 50), (0, 1, 1));
+
+        // full duty compare value stays within 16 bits for every frequency
+        for freq in 1..=2000 {
+            for duty in [0, 50, 100, 150].iter() {
+                let (psc, arr, ccr) = square(CLK, freq, *duty);
+
+                assert!(psc <= 0xFFFF);
+                assert!(arr < 0xFFFF);
+                assert!(ccr <= arr + 1);
+                assert_eq!(ccr == arr + 1, *duty >= 100);
+            }
+        }
+
+        // 65535 ticks fit a single prescaler step, 65536 ticks do not
+        assert_eq!(square(0xFFFF * 1000, 1000, 100), (0, 0xFFFE, 0xFFFF));
+        assert_eq!(square(0x10000 * 1000, 1000, 100), (1, 0x7FFF, 0x8000));
+    }
+
+    fn phase(i: u32) -> u32 {
+        i << 24
+    }
+
+    #[test]
+    fn sine() {
+        let s: Vec<u16> = (0..256).map(|i| sample(Waveform::Sine, phase(i))).collect();
+
+        assert_eq!((s[0], s[64], s[128], s[192]), (128, 255, 128, 1));
+
+        // quarter table folded into the whole period
+        for k in 0..64 {
+            assert_eq!(s[k] - 128, QUARTER[k] as u16);
+            assert_eq!(s[64 + k], s[64 - k]);
+            assert_eq!(s[128 + k], 256 - s[k]);
+            assert_eq!(s[192 + k], 256 - s[64 + k]);
+        }
+
+        assert!(s.iter().all(|v| (1..=255).contains(v)));
+    }
+
+    #[test]
+    fn triangle() {
+        let s: Vec<u16> = (0..512)
+            .map(|i| sample(Waveform::Triangle, i << 23))
+            .collect();
+
+        assert_eq!((s[0], s[255], s[256], s[511]), (0, 255, 255, 0));
+
+        // rising half folded into the falling one
+        for k in 0..256 {
+            assert_eq!(s[k], k as u16);
+            assert_eq!(s[511 - k], s[k]);
+        }
+    }
+
+    #[test]
+    fn square_sample() {
+        assert_eq!(sample(Waveform::Square, 0), 128);
+        assert_eq!(sample(Waveform::Square, u32::MAX), 128);
+    }
+}
@@ -1,4 +1,7 @@
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 
+pub mod game;
+pub mod gfx;
 pub mod hw;
 pub mod phys;
+pub mod scope;
@@ -0,0 +1,190 @@
+use crate::phys::particles::Particle;
+use crate::phys::racket::{Intent, Racket};
+use crate::phys::rng::XorShift;
+use core::ops::{Add, Div, Mul, Neg, Sub};
+
+#[derive(Debug, Clone, Copy)]
+pub struct Skill<N> {
+    // steps before the racket reacts to the ball coming back
+    pub delay: u32,
+    // racket stops accelerating at this speed
+    pub vmax: N,
+    // racket aims this far off the predicted point on some of the balls
+    pub error: N,
+    // racket brakes when it is that close to the target
+    pub slack: N,
+}
+
+// Computer controlled racket: predicts where the ball crosses the racket line
+// and drives the racket there using the same intents as the buttons.
+#[derive(Debug, Clone, Copy)]
+pub struct Ai<N>
+where
+    N: Sub<Output = N> + Div<Output = N> + Mul<Output = N> + Add<Output = N> + Neg<Output = N>,
+    N: Default + Copy + Clone + PartialOrd,
+{
+    skill: Skill<N>,
+    line: N,
+    home: N,
+    target: N,
+    aim: N,
+    wait: u32,
+    coming: bool,
+    rng: XorShift,
+}
+
+impl<N> Ai<N>
+where
+    N: Sub<Output = N> + Div<Output = N> + Mul<Output = N> + Add<Output = N> + Neg<Output = N>,
+    N: Default + Copy + Clone + PartialOrd,
+{
+    // line: ball center y at the racket face, home: racket x while the ball is away
+    pub fn new(skill: Skill<N>, line: N, home: N, seed: u32) -> Ai<N> {
+        Ai {
+            skill,
+            line,
+            home,
+            target: home,
+            aim: N::default(),
+            wait: 0,
+            coming: false,
+            rng: XorShift::new(seed),
+        }
+    }
+
+    pub fn set_skill(&mut self, skill: Skill<N>) {
+        self.skill = skill;
+    }
+
+    pub fn get_target(&self) -> N {
+        self.target
+    }
+
+    // x of the ball center when it reaches the line y, bouncing between the walls
+    // wmin and wmax; None if the ball is moving away from the line
+    pub fn intercept(p: &Particle<N>, line: N, wmin: N, wmax: N) -> Option<N> {
+        let zero = N::default();
+        let (vy, dy) = (p.get_vy(), line - p.get_y());
+
+        if vy == zero || (vy > zero) != (dy > zero) {
+            return None;
+        }
+
+        let mut x = p.get_x() + p.get_vx() * (dy / vy);
+
+        // unfold wall reflections, every two rounds move x by the double field width
+        for _ in 0..32 {
+            if x > wmax {
+                x = wmax + wmax - x;
+            } else if x < wmin {
+                x = wmin + wmin - x;
+            } else {
+                return Some(x);
+            }
+        }
+
+        None
+    }
+
+    pub fn step(&mut self, p: &Particle<N>, r: &Racket<N>, wmin: N, wmax: N) -> Intent {
+        let zero = N::default();
+
+        match Ai::intercept(p, self.line, wmin, wmax) {
+            Some(x) => {
+                if !self.coming {
+                    // new approach: wait for the reaction delay and pick the aim error
+                    self.coming = true;
+                    self.wait = self.skill.delay;
+                    self.aim = match self.rng.random() % 3 {
+                        0 => -self.skill.error,
+                        1 => self.skill.error,
+                        _ => zero,
+                    };
+                }
+
+                if self.wait > 0 {
+                    self.wait -= 1;
+                } else {
+                    self.target = x + self.aim;
+                }
+            }
+            None => {
+                self.coming = false;
+                self.target = self.home;
+            }
+        }
+
+        let d = self.target - r.get_cx();
+        let v = r.get_vx();
+
+        if d > self.skill.slack {
+            if v >= self.skill.vmax {
+                Intent::Idle
+            } else {
+                Intent::Right
+            }
+        } else if d < -self.skill.slack {
+            if v <= -self.skill.vmax {
+                Intent::Idle
+            } else {
+                Intent::Left
+            }
+        } else {
+            Intent::Brake
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::phys::particles::ParticleColor;
+
+    // field between the walls at x 0 and 100, the racket line at y 10
+    const WMIN: f32 = 0.0;
+    const WMAX: f32 = 100.0;
+    const LINE: f32 = 10.0;
+
+    // ball at (50, 90) reaches the line after 4 time units
+    fn intercept(vx: f32, vy: f32) -> Option<f32> {
+        let p = Particle::new(50.0, 90.0, vx, vy, 3.0, 0.1, ParticleColor::Blue);
+        Ai::intercept(&p, LINE, WMIN, WMAX)
+    }
+
+    #[test]
+    fn straight() {
+        assert_eq!(intercept(10.0, -20.0), Some(90.0));
+        assert_eq!(intercept(-5.0, -20.0), Some(30.0));
+        assert_eq!(intercept(0.0, -20.0), Some(50.0));
+    }
+
+    #[test]
+    fn one_bounce() {
+        assert_eq!(intercept(20.0, -20.0), Some(70.0));
+        assert_eq!(intercept(-20.0, -20.0), Some(30.0));
+    }
+
+    #[test]
+    fn several_bounces() {
+        // 450 and -230 unfold to 50 and 30 over the double field width
+        assert_eq!(intercept(100.0, -20.0), Some(50.0));
+        assert_eq!(intercept(-70.0, -20.0), Some(30.0));
+    }
+
+    #[test]
+    fn moving_away() {
+        assert_eq!(intercept(10.0, 20.0), None);
+        assert_eq!(intercept(10.0, 0.0), None);
+
+        // the same ball moving up reaches a line above it
+        let p = Particle::new(50.0, 90.0, 10.0, 20.0, 3.0, 0.1, ParticleColor::Blue);
+        assert_eq!(Ai::intercept(&p, 170.0, WMIN, WMAX), Some(90.0));
+    }
+
+    #[test]
+    fn reflection_limit() {
+        // 3050 needs 30 reflections, 4050 needs 40
+        assert_eq!(intercept(750.0, -20.0), Some(50.0));
+        assert_eq!(intercept(1000.0, -20.0), None);
+    }
+}
@@ -0,0 +1,222 @@
+use crate::phys::particles::Particle;
+use core::ops::{Add, Div, Mul, Neg, Sub};
+
+// fixed capacity set of balls in play
+#[derive(Debug, Clone, Copy)]
+pub struct Balls<N, const M: usize>
+where
+    N: Sub<Output = N> + Div<Output = N> + Mul<Output = N> + Add<Output = N> + Neg<Output = N>,
+    N: Default + Copy + Clone + PartialOrd,
+{
+    items: [Particle<N>; M],
+    live: [bool; M],
+}
+
+impl<N, const M: usize> Default for Balls<N, M>
+where
+    N: Sub<Output = N> + Div<Output = N> + Mul<Output = N> + Add<Output = N> + Neg<Output = N>,
+    N: Default + Copy + Clone + PartialOrd,
+{
+    fn default() -> Balls<N, M> {
+        Balls::new()
+    }
+}
+
+impl<N, const M: usize> Balls<N, M>
+where
+    N: Sub<Output = N> + Div<Output = N> + Mul<Output = N> + Add<Output = N> + Neg<Output = N>,
+    N: Default + Copy + Clone + PartialOrd,
+{
+    pub fn new() -> Balls<N, M> {
+        Balls {
+            items: [Particle::default(); M],
+            live: [false; M],
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        M
+    }
+
+    pub fn len(&self) -> usize {
+        self.live.iter().filter(|l| **l).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.live.iter().any(|l| *l)
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.live.iter().all(|l| *l)
+    }
+
+    pub fn clear(&mut self) {
+        self.live = [false; M];
+    }
+
+    // returns slot of the new ball, None if there is no free slot
+    pub fn add(&mut self, p: Particle<N>) -> Option<usize> {
+        let i = self.live.iter().position(|l| !*l)?;
+
+        self.items[i] = p;
+        self.live[i] = true;
+
+        Some(i)
+    }
+
+    pub fn remove(&mut self, i: usize) {
+        self.live[i] = false;
+    }
+
+    pub fn get(&self, i: usize) -> Option<&Particle<N>> {
+        if self.live[i] {
+            Some(&self.items[i])
+        } else {
+            None
+        }
+    }
+
+    pub fn get_mut(&mut self, i: usize) -> Option<&mut Particle<N>> {
+        if self.live[i] {
+            Some(&mut self.items[i])
+        } else {
+            None
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Particle<N>> {
+        self.items
+            .iter()
+            .zip(self.live.iter())
+            .filter(|(_, l)| **l)
+            .map(|(p, _)| p)
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Particle<N>> {
+        self.items
+            .iter_mut()
+            .zip(self.live.iter())
+            .filter(|(_, l)| **l)
+            .map(|(p, _)| p)
+    }
+
+    // collide all pairs of balls, returns number of collisions
+    pub fn collide(&mut self) -> usize {
+        let mut n = 0;
+
+        for i in 0..M {
+            for j in i + 1..M {
+                if !self.live[i] || !self.live[j] {
+                    continue;
+                }
+
+                let (a, b) = self.items.split_at_mut(j);
+
+                if Particle::<N>::collide(&mut a[i], &mut b[0]) {
+                    n += 1;
+                }
+            }
+        }
+
+        n
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::phys::particles::ParticleColor;
+
+    fn ball(x: f32, y: f32, vx: f32) -> Particle<f32> {
+        Particle::new(x, y, vx, 0.0, 3.0, 1.0, ParticleColor::White)
+    }
+
+    fn xs(balls: &Balls<f32, 3>) -> Vec<f32> {
+        balls.iter().map(|p| p.get_x()).collect()
+    }
+
+    #[test]
+    fn capacity() {
+        let mut balls = Balls::<f32, 3>::new();
+        assert_eq!(balls.capacity(), 3);
+        assert!(balls.is_empty());
+
+        for i in 0..3 {
+            assert_eq!(balls.add(ball(i as f32, 0.0, 0.0)), Some(i));
+        }
+
+        assert!(balls.is_full());
+        assert_eq!(balls.len(), 3);
+        assert_eq!(balls.add(ball(9.0, 0.0, 0.0)), None);
+        assert_eq!(xs(&balls), [0.0, 1.0, 2.0]);
+
+        balls.clear();
+        assert!(balls.is_empty());
+        assert_eq!(balls.add(ball(9.0, 0.0, 0.0)), Some(0));
+    }
+
+    #[test]
+    fn live_flags() {
+        let mut balls = Balls::<f32, 3>::new();
+
+        for i in 0..3 {
+            balls.add(ball(i as f32, 0.0, 0.0));
+        }
+
+        balls.remove(1);
+        assert_eq!(balls.len(), 2);
+        assert!(!balls.is_full() && !balls.is_empty());
+        assert!(balls.get(1).is_none());
+        assert!(balls.get_mut(1).is_none());
+        assert_eq!(balls.get(2).map(|p| p.get_x()), Some(2.0));
+        assert_eq!(xs(&balls), [0.0, 2.0]);
+
+        for p in balls.iter_mut() {
+            p.set_position(p.get_x() + 10.0, 0.0);
+        }
+        assert_eq!(xs(&balls), [10.0, 12.0]);
+
+        // the free slot is reused, removed ball was not moved
+        assert_eq!(balls.add(ball(5.0, 0.0, 0.0)), Some(1));
+        assert_eq!(xs(&balls), [10.0, 5.0, 12.0]);
+    }
+
+    #[test]
+    fn collisions() {
+        let mut balls = Balls::<f32, 3>::new();
+
+        // a pair moving into each other and a ball far away
+        balls.add(ball(0.0, 0.0, 1.0));
+        balls.add(ball(5.0, 0.0, -1.0));
+        balls.add(ball(50.0, 0.0, 0.0));
+        assert_eq!(balls.collide(), 1);
+
+        let vx: Vec<f32> = balls.iter().map(|p| p.get_vx()).collect();
+        assert_eq!(vx, [-1.0, 1.0, 0.0]);
+
+        // moving apart now
+        assert_eq!(balls.collide(), 0);
+
+        // every touching pair is counted
+        balls.clear();
+        balls.add(ball(0.0, 0.0, 1.0));
+        balls.add(ball(5.0, 0.0, -1.0));
+        balls.add(Particle::new(
+            5.0,
+            5.0,
+            0.0,
+            -1.0,
+            3.0,
+            1.0,
+            ParticleColor::White,
+        ));
+        assert_eq!(balls.collide(), 2);
+
+        // removed balls do not collide
+        balls.clear();
+        balls.add(ball(0.0, 0.0, 1.0));
+        balls.add(ball(5.0, 0.0, -1.0));
+        balls.remove(0);
+        assert_eq!(balls.collide(), 0);
+    }
+}
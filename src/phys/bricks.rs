@@ -0,0 +1,286 @@
+use crate::phys::particles::Particle;
+use crate::phys::{abs, clamp};
+use core::ops::{Add, Div, Mul, Neg, Sub};
+
+// Grid of R rows and C columns of static bricks. Every brick has hit points,
+// the brick is removed when its hit points drop to zero. Row 0 is the top one.
+#[derive(Debug, Clone, Copy)]
+pub struct Bricks<N, const C: usize, const R: usize>
+where
+    N: Sub<Output = N> + Div<Output = N> + Mul<Output = N> + Add<Output = N> + Neg<Output = N>,
+    N: Default + Copy + Clone + PartialOrd,
+{
+    // top left corner of the grid
+    x: N,
+    y: N,
+    // brick half-width and half-height
+    hw: N,
+    hh: N,
+    hp: [[u8; C]; R],
+    count: usize,
+}
+
+impl<N, const C: usize, const R: usize> Bricks<N, C, R>
+where
+    N: Sub<Output = N> + Div<Output = N> + Mul<Output = N> + Add<Output = N> + Neg<Output = N>,
+    N: Default + Copy + Clone + PartialOrd,
+{
+    pub fn new(x: N, y: N, hw: N, hh: N) -> Bricks<N, C, R> {
+        Bricks {
+            x,
+            y,
+            hw,
+            hh,
+            hp: [[0; C]; R],
+            count: 0,
+        }
+    }
+
+    // hit points of every brick, 0 for no brick
+    pub fn load(&mut self, layout: &[[u8; C]; R]) {
+        self.hp = *layout;
+        self.count = layout.iter().flatten().filter(|hp| **hp > 0).count();
+    }
+
+    pub fn get_hp(&self, row: usize, col: usize) -> u8 {
+        self.hp[row][col]
+    }
+
+    pub fn get_hw(&self) -> N {
+        self.hw
+    }
+
+    pub fn get_hh(&self) -> N {
+        self.hh
+    }
+
+    // bricks left
+    pub fn remaining(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_clear(&self) -> bool {
+        self.count == 0
+    }
+
+    pub fn center(&self, row: usize, col: usize) -> (N, N) {
+        let mut cx = self.x + self.hw;
+        let mut cy = self.y - self.hh;
+
+        for _ in 0..col {
+            cx = cx + self.hw + self.hw;
+        }
+
+        for _ in 0..row {
+            cy = cy - self.hh - self.hh;
+        }
+
+        (cx, cy)
+    }
+
+    // Reflect ball from the bricks it touches: vertical velocity is reversed on
+    // top or bottom face hits, horizontal velocity on side hits. Every brick the
+    // ball is moving into loses a hit point and is reported via callback with
+    // its row, column and remaining hit points. Returns number of hit bricks.
+    pub fn collide<F>(&mut self, p: &mut Particle<N>, mut hit: F) -> usize
+    where
+        F: FnMut(usize, usize, u8),
+    {
+        let zero = N::default();
+
+        let (px, py, pr) = (p.get_x(), p.get_y(), p.get_r());
+        let (vx, vy) = (p.get_vx(), p.get_vy());
+        let (mut nx, mut ny) = (px, py);
+        let (mut flip_x, mut flip_y) = (false, false);
+        let mut n = 0;
+        let mut top = self.y;
+
+        for row in 0..R {
+            let bottom = top - self.hh - self.hh;
+
+            if py - pr > top || py + pr < bottom {
+                top = bottom;
+                continue;
+            }
+
+            let mut left = self.x;
+
+            for col in 0..C {
+                let right = left + self.hw + self.hw;
+
+                if self.hp[row][col] == 0 || px + pr < left || px - pr > right {
+                    left = right;
+                    continue;
+                }
+
+                // closest point of the brick to the ball center
+                let dx = px - clamp(px, left, right);
+                let dy = py - clamp(py, bottom, top);
+                let (cx, cy) = (left + self.hw, bottom + self.hh);
+
+                if dx * dx + dy * dy <= pr * pr {
+                    let face = if dx == zero && dy == zero {
+                        // ball center inside the brick: compare relative offsets
+                        abs(py - cy) * self.hw >= abs(px - cx) * self.hh
+                    } else {
+                        abs(dy) >= abs(dx)
+                    };
+
+                    let moving_in = if face {
+                        vy * (py - cy) < zero
+                    } else {
+                        vx * (px - cx) < zero
+                    };
+
+                    if moving_in {
+                        if face {
+                            flip_y = true;
+                            ny = if py > cy { top + pr } else { bottom - pr };
+                        } else {
+                            flip_x = true;
+                            nx = if px > cx { right + pr } else { left - pr };
+                        }
+
+                        self.hp[row][col] -= 1;
+
+                        if self.hp[row][col] == 0 {
+                            self.count -= 1;
+                        }
+
+                        hit(row, col, self.hp[row][col]);
+                        n += 1;
+                    }
+                }
+
+                left = right;
+            }
+
+            top = bottom;
+        }
+
+        if n > 0 {
+            p.set_velocity(if flip_x { -vx } else { vx }, if flip_y { -vy } else { vy });
+            p.set_position(nx, ny);
+        }
+
+        n
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::phys::particles::ParticleColor;
+
+    // 4 columns of 20x10 bricks, row 0 spans y 90..100 and row 1 y 80..90
+    fn bricks(layout: [[u8; 4]; 2]) -> Bricks<f32, 4, 2> {
+        let mut b = Bricks::new(0.0, 100.0, 10.0, 5.0);
+        b.load(&layout);
+        b
+    }
+
+    fn ball(x: f32, y: f32, vx: f32, vy: f32) -> Particle<f32> {
+        Particle::new(x, y, vx, vy, 3.0, 0.1, ParticleColor::Blue)
+    }
+
+    fn hits(b: &mut Bricks<f32, 4, 2>, p: &mut Particle<f32>) -> Vec<(usize, usize, u8)> {
+        let mut hits = Vec::new();
+        assert_eq!(b.collide(p, |r, c, hp| hits.push((r, c, hp))), hits.len());
+        hits
+    }
+
+    #[test]
+    fn geometry() {
+        let b = bricks([[1; 4]; 2]);
+
+        assert_eq!(b.center(0, 0), (10.0, 95.0));
+        assert_eq!(b.center(1, 2), (50.0, 85.0));
+        assert_eq!(b.remaining(), 8);
+    }
+
+    #[test]
+    fn face_hit() {
+        let mut b = bricks([[1; 4]; 2]);
+        let mut p = ball(30.0, 78.0, 1.0, 10.0);
+
+        assert_eq!(hits(&mut b, &mut p), [(1, 1, 0)]);
+        assert_eq!((p.get_vx(), p.get_vy()), (1.0, -10.0));
+        assert_eq!((p.get_x(), p.get_y()), (30.0, 77.0));
+        assert_eq!(b.remaining(), 7);
+    }
+
+    #[test]
+    fn side_hit() {
+        let mut b = bricks([[1; 4]; 2]);
+        let mut p = ball(-2.0, 85.0, 10.0, 1.0);
+
+        assert_eq!(hits(&mut b, &mut p), [(1, 0, 0)]);
+        assert_eq!((p.get_vx(), p.get_vy()), (-10.0, 1.0));
+        assert_eq!((p.get_x(), p.get_y()), (-3.0, 85.0));
+
+        // from the right into the gap left by the removed brick
+        let mut b = bricks([[1, 0, 0, 0], [1, 0, 0, 0]]);
+        let mut p = ball(22.0, 95.0, -10.0, 0.0);
+        assert_eq!(hits(&mut b, &mut p), [(0, 0, 0)]);
+        assert_eq!(p.get_vx(), 10.0);
+    }
+
+    #[test]
+    fn corner_hit() {
+        // bottom left corner of the brick at (20, 80): the larger distance decides the side
+        let mut b = bricks([[0; 4], [0, 1, 0, 0]]);
+        let mut p = ball(18.0, 78.0, 5.0, 5.0);
+        assert_eq!(hits(&mut b, &mut p), [(1, 1, 0)]);
+        assert_eq!((p.get_vx(), p.get_vy()), (5.0, -5.0));
+
+        let mut b = bricks([[0; 4], [0, 1, 0, 0]]);
+        let mut p = ball(17.5, 79.0, 5.0, 5.0);
+        assert_eq!(hits(&mut b, &mut p), [(1, 1, 0)]);
+        assert_eq!((p.get_vx(), p.get_vy()), (-5.0, 5.0));
+
+        // just out of reach of the corner
+        let mut b = bricks([[0; 4], [0, 1, 0, 0]]);
+        let mut p = ball(17.5, 77.5, 5.0, 5.0);
+        assert!(hits(&mut b, &mut p).is_empty());
+    }
+
+    #[test]
+    fn two_bricks() {
+        // ball below the joint of two bricks reflects once and hits both
+        let mut b = bricks([[1; 4]; 2]);
+        let mut p = ball(20.0, 78.0, 5.0, 5.0);
+
+        assert_eq!(hits(&mut b, &mut p), [(1, 0, 0), (1, 1, 0)]);
+        assert_eq!((p.get_vx(), p.get_vy()), (5.0, -5.0));
+        assert_eq!(b.remaining(), 6);
+    }
+
+    #[test]
+    fn moving_away() {
+        let mut b = bricks([[1; 4]; 2]);
+        let mut p = ball(30.0, 78.0, 1.0, -10.0);
+
+        assert!(hits(&mut b, &mut p).is_empty());
+        assert_eq!((p.get_vx(), p.get_vy()), (1.0, -10.0));
+        assert_eq!(b.remaining(), 8);
+    }
+
+    #[test]
+    fn hit_points() {
+        let mut b = bricks([[0; 4], [0, 2, 0, 0]]);
+
+        let mut p = ball(30.0, 78.0, 0.0, 10.0);
+        assert_eq!(hits(&mut b, &mut p), [(1, 1, 1)]);
+        assert_eq!(b.get_hp(1, 1), 1);
+        assert!(!b.is_clear());
+
+        let mut p = ball(30.0, 78.0, 0.0, 10.0);
+        assert_eq!(hits(&mut b, &mut p), [(1, 1, 0)]);
+        assert!(b.is_clear());
+
+        // removed brick lets the ball through
+        let mut p = ball(30.0, 78.0, 0.0, 10.0);
+        assert!(hits(&mut b, &mut p).is_empty());
+        assert_eq!(p.get_vy(), 10.0);
+    }
+}
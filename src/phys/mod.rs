@@ -1,2 +1,34 @@
+pub mod ai;
+pub mod balls;
+pub mod bricks;
 pub mod particles;
 pub mod racket;
+pub mod rng;
+
+use core::ops::Neg;
+
+// abs and clamp for the generic number types, shared by the collision code
+
+pub(crate) fn abs<N>(v: N) -> N
+where
+    N: Neg<Output = N> + Default + Copy + PartialOrd,
+{
+    if v < N::default() {
+        -v
+    } else {
+        v
+    }
+}
+
+pub(crate) fn clamp<N>(v: N, min: N, max: N) -> N
+where
+    N: Copy + PartialOrd,
+{
+    if v < min {
+        min
+    } else if v > max {
+        max
+    } else {
+        v
+    }
+}
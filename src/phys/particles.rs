@@ -61,6 +61,24 @@ where
         self.r
     }
 
+    pub fn get_vx(&self) -> N {
+        self.vx
+    }
+
+    pub fn get_vy(&self) -> N {
+        self.vy
+    }
+
+    pub fn set_position(&mut self, px: N, py: N) {
+        self.px = px;
+        self.py = py;
+    }
+
+    pub fn set_velocity(&mut self, vx: N, vy: N) {
+        self.vx = vx;
+        self.vy = vy;
+    }
+
     pub fn get_color(&self) -> ParticleColor {
         self.c
     }
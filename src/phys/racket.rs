@@ -1,5 +1,28 @@
+use crate::phys::particles::Particle;
+use crate::phys::{abs, clamp};
 use core::ops::{Add, Div, Mul, Neg, Sub};
 
+// player input driving the racket
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Intent {
+    Left,
+    Right,
+    Brake,
+    Idle,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Kinematics<N> {
+    // acceleration while a button is held
+    pub acc: N,
+    // max speed
+    pub vmax: N,
+    // fraction of velocity lost every step
+    pub friction: N,
+    // time step
+    pub dt: N,
+}
+
 #[derive(Debug, Clone, Copy, Default)]
 pub struct Racket<N>
 where
@@ -10,6 +33,8 @@ where
     cy: N,
     hw: N,
     hh: N,
+    vx: N,
+    k: Kinematics<N>,
 }
 
 impl<N> Racket<N>
@@ -17,12 +42,52 @@ where
     N: Sub<Output = N> + Div<Output = N> + Mul<Output = N> + Add<Output = N> + Neg<Output = N>,
     N: Default + Copy + Clone + PartialOrd,
 {
-    pub fn new(cx: N, cy: N, hw: N, hh: N) -> Racket<N> {
-        Racket { cx, cy, hw, hh }
+    pub fn new(cx: N, cy: N, hw: N, hh: N, k: Kinematics<N>) -> Racket<N> {
+        Racket {
+            cx,
+            cy,
+            hw,
+            hh,
+            vx: N::default(),
+            k,
+        }
     }
 
-    pub fn step(&mut self, dx: N) {
-        self.cx = self.cx + dx;
+    pub fn set_kinematics(&mut self, k: Kinematics<N>) {
+        self.k = k;
+    }
+
+    pub fn step(&mut self, intent: Intent) {
+        let zero = N::default();
+        let dv = self.k.acc * self.k.dt;
+
+        match intent {
+            Intent::Left => self.vx = self.vx - dv,
+            Intent::Right => self.vx = self.vx + dv,
+            Intent::Brake => {
+                // decelerate, but do not reverse
+                self.vx = if self.vx > dv {
+                    self.vx - dv
+                } else if self.vx < -dv {
+                    self.vx + dv
+                } else {
+                    zero
+                }
+            }
+            Intent::Idle => {}
+        }
+
+        self.vx = self.vx - self.vx * self.k.friction;
+
+        if self.vx > self.k.vmax {
+            self.vx = self.k.vmax;
+        }
+
+        if self.vx < -self.k.vmax {
+            self.vx = -self.k.vmax;
+        }
+
+        self.cx = self.cx + self.vx * self.k.dt;
     }
 
     pub fn get_cx(&self) -> N {
@@ -41,19 +106,255 @@ where
         self.hh
     }
 
+    pub fn get_vx(&self) -> N {
+        self.vx
+    }
+
+    pub fn set_hw(&mut self, hw: N) {
+        self.hw = hw;
+    }
+
     pub fn bounce(r: &mut Racket<N>, xmin: N, xmax: N) -> bool {
         let mut res = false;
 
         if r.cx >= xmax {
             r.cx = xmax;
+            r.vx = N::default();
             res = true;
         }
 
         if r.cx <= xmin {
             r.cx = xmin;
+            r.vx = N::default();
             res = true;
         }
 
         res
     }
+
+    // Reflect ball from the racket box. Outgoing angle depends on the hit position:
+    // horizontal velocity gets extra spin * |vy| at the racket edges and nothing
+    // in the center. Fraction drag of the racket velocity is passed to the ball.
+    pub fn collide(r: &Racket<N>, p: &mut Particle<N>, spin: N, drag: N) -> bool {
+        let zero = N::default();
+
+        let (px, py, pr) = (p.get_x(), p.get_y(), p.get_r());
+        let (vx, vy) = (p.get_vx(), p.get_vy());
+
+        // closest point of the racket box to the ball center
+        let qx = clamp(px, r.cx - r.hw, r.cx + r.hw);
+        let qy = clamp(py, r.cy - r.hh, r.cy + r.hh);
+        let dx = px - qx;
+        let dy = py - qy;
+
+        if dx * dx + dy * dy > pr * pr {
+            return false;
+        }
+
+        if abs(dy) >= abs(dx) {
+            // top or bottom face: skip if ball is already moving away
+            if vy * (py - r.cy) >= zero {
+                return false;
+            }
+
+            let offset = (px - r.cx) / r.hw;
+            let ny = if py > r.cy {
+                r.cy + r.hh + pr
+            } else {
+                r.cy - r.hh - pr
+            };
+
+            p.set_velocity(vx + spin * offset * abs(vy) + drag * r.vx, -vy);
+            p.set_position(px, ny);
+        } else {
+            // left or right side
+            if vx * (px - r.cx) >= zero {
+                return false;
+            }
+
+            let nx = if px > r.cx {
+                r.cx + r.hw + pr
+            } else {
+                r.cx - r.hw - pr
+            };
+
+            p.set_velocity(-vx + drag * r.vx, vy);
+            p.set_position(nx, py);
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::phys::particles::ParticleColor;
+
+    const SPIN: f32 = 0.5;
+
+    const K: Kinematics<f32> = Kinematics {
+        acc: 10.0,
+        vmax: 25.0,
+        friction: 0.5,
+        dt: 1.0,
+    };
+
+    // 20x4 racket centered at (50, 10)
+    fn racket() -> Racket<f32> {
+        Racket::new(50.0, 10.0, 10.0, 2.0, K)
+    }
+
+    fn ball(x: f32, y: f32, vx: f32, vy: f32) -> Particle<f32> {
+        Particle::new(x, y, vx, vy, 3.0, 0.1, ParticleColor::White)
+    }
+
+    #[test]
+    fn center_hit() {
+        let r = racket();
+        let mut b = ball(50.0, 14.0, 1.0, -4.0);
+
+        assert!(Racket::collide(&r, &mut b, SPIN, 0.5));
+        assert_eq!((b.get_vx(), b.get_vy()), (1.0, 4.0));
+        // pushed out of the racket on the hit side
+        assert_eq!((b.get_x(), b.get_y()), (50.0, 15.0));
+
+        let mut b = ball(50.0, 6.0, 0.0, 4.0);
+        assert!(Racket::collide(&r, &mut b, SPIN, 0.5));
+        assert_eq!((b.get_vy(), b.get_y()), (-4.0, 5.0));
+    }
+
+    #[test]
+    fn edge_hit() {
+        let r = racket();
+
+        let mut b = ball(60.0, 14.0, 0.0, -4.0);
+        assert!(Racket::collide(&r, &mut b, SPIN, 0.0));
+        assert_eq!((b.get_vx(), b.get_vy()), (2.0, 4.0));
+
+        let mut b = ball(45.0, 14.0, 0.0, -4.0);
+        assert!(Racket::collide(&r, &mut b, SPIN, 0.0));
+        assert_eq!((b.get_vx(), b.get_vy()), (-1.0, 4.0));
+    }
+
+    #[test]
+    fn side_hit() {
+        let r = racket();
+        let mut b = ball(62.0, 10.0, -4.0, 1.0);
+
+        assert!(Racket::collide(&r, &mut b, SPIN, 0.0));
+        assert_eq!((b.get_vx(), b.get_vy()), (4.0, 1.0));
+        assert_eq!((b.get_x(), b.get_y()), (63.0, 10.0));
+    }
+
+    #[test]
+    fn moving_away() {
+        let r = racket();
+
+        for (x, y, vx, vy) in [(50.0, 14.0, 0.0, 4.0), (62.0, 10.0, 4.0, 0.0)].iter() {
+            let mut b = ball(*x, *y, *vx, *vy);
+
+            assert!(!Racket::collide(&r, &mut b, SPIN, 0.5));
+            assert_eq!((b.get_x(), b.get_y()), (*x, *y));
+            assert_eq!((b.get_vx(), b.get_vy()), (*vx, *vy));
+        }
+
+        // out of reach
+        let mut b = ball(50.0, 16.0, 0.0, -4.0);
+        assert!(!Racket::collide(&r, &mut b, SPIN, 0.5));
+    }
+
+    #[test]
+    fn drag() {
+        let mut r = racket();
+        r.set_kinematics(Kinematics { friction: 0.0, ..K });
+        r.step(Intent::Right);
+        assert_eq!((r.get_cx(), r.get_vx()), (60.0, 10.0));
+
+        let mut b = ball(60.0, 14.0, 1.0, -4.0);
+        assert!(Racket::collide(&r, &mut b, SPIN, 0.5));
+        assert_eq!((b.get_vx(), b.get_vy()), (1.0 + 5.0, 4.0));
+
+        // side hits get the drag too
+        let mut b = ball(72.0, 10.0, -4.0, 0.0);
+        assert!(Racket::collide(&r, &mut b, SPIN, 0.5));
+        assert_eq!(b.get_vx(), 4.0 + 5.0);
+    }
+
+    #[test]
+    fn acceleration() {
+        let mut r = racket();
+        r.set_kinematics(Kinematics { friction: 0.0, ..K });
+
+        r.step(Intent::Right);
+        r.step(Intent::Right);
+        assert_eq!((r.get_vx(), r.get_cx()), (20.0, 50.0 + 10.0 + 20.0));
+
+        r.step(Intent::Idle);
+        assert_eq!((r.get_vx(), r.get_cx()), (20.0, 100.0));
+
+        r.step(Intent::Left);
+        assert_eq!((r.get_vx(), r.get_cx()), (10.0, 110.0));
+    }
+
+    #[test]
+    fn vmax() {
+        let mut r = racket();
+        r.set_kinematics(Kinematics { friction: 0.0, ..K });
+
+        for _ in 0..5 {
+            r.step(Intent::Right);
+        }
+        assert_eq!(r.get_vx(), 25.0);
+
+        for _ in 0..10 {
+            r.step(Intent::Left);
+        }
+        assert_eq!(r.get_vx(), -25.0);
+    }
+
+    #[test]
+    fn friction() {
+        let mut r = racket();
+
+        // half of the velocity is lost every step
+        r.step(Intent::Right);
+        assert_eq!(r.get_vx(), 5.0);
+        r.step(Intent::Idle);
+        assert_eq!(r.get_vx(), 2.5);
+        r.step(Intent::Idle);
+        assert_eq!(r.get_vx(), 1.25);
+
+        // friction limits the speed below vmax: v = (v + acc) / 2 converges to acc
+        for _ in 0..50 {
+            r.step(Intent::Right);
+        }
+        assert!(r.get_vx() <= 10.0 && r.get_vx() > 9.99);
+    }
+
+    #[test]
+    fn brake() {
+        let mut r = racket();
+        r.set_kinematics(Kinematics { friction: 0.0, ..K });
+
+        r.step(Intent::Right);
+        r.step(Intent::Right);
+        r.step(Intent::Brake);
+        assert_eq!(r.get_vx(), 10.0);
+
+        // below one step of deceleration the racket stops instead of reversing
+        r.set_kinematics(Kinematics {
+            acc: 15.0,
+            friction: 0.0,
+            ..K
+        });
+        r.step(Intent::Brake);
+        assert_eq!(r.get_vx(), 0.0);
+        r.step(Intent::Brake);
+        assert_eq!(r.get_vx(), 0.0);
+
+        r.step(Intent::Left);
+        r.step(Intent::Brake);
+        assert_eq!(r.get_vx(), 0.0);
+    }
 }
@@ -0,0 +1,43 @@
+// xorshift32 pseudo random numbers for game decisions, not for anything
+// that needs good statistics
+#[derive(Debug, Clone, Copy)]
+pub struct XorShift {
+    state: u32,
+}
+
+impl XorShift {
+    // zero state would only ever produce zeros
+    pub fn new(seed: u32) -> XorShift {
+        XorShift {
+            state: if seed == 0 { 1 } else { seed },
+        }
+    }
+
+    pub fn random(&mut self) -> u32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        self.state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_seed() {
+        let mut a = XorShift::new(0);
+        let mut b = XorShift::new(1);
+
+        // zero seed is the same sequence as seed 1 and never gets stuck at zero
+        for _ in 0..1000 {
+            let v = a.random();
+
+            assert_eq!(v, b.random());
+            assert_ne!(v, 0);
+        }
+
+        assert_eq!(XorShift::new(1).random(), 270369);
+    }
+}
@@ -0,0 +1,493 @@
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::style::PrimitiveStyle;
+
+// graticule divisions
+pub const DIVS_X: i32 = 10;
+pub const DIVS_Y: i32 = 8;
+
+// division lines are dotted with this step, center lines have ticks every 1/TICKS of division
+const DOT: i32 = 5;
+const TICKS: i32 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Scale {
+    // samples per horizontal division, timebase
+    pub samples: u32,
+    // ADC counts per vertical division, volts/div
+    pub counts: u32,
+    // ADC count at the center line
+    pub offset: u16,
+}
+
+// rows of the trace in a column, top <= bottom; marked spans are drawn in the mark color.
+// Rows are stored as i16 to keep the per-column state small.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Span {
+    top: i16,
+    bottom: i16,
+    marked: bool,
+}
+
+impl Span {
+    fn new(top: i32, bottom: i32, marked: bool) -> Span {
+        Span {
+            top: top as i16,
+            bottom: bottom as i16,
+            marked,
+        }
+    }
+}
+
+// Waveform area with W columns: graticule of DIVS_X x DIVS_Y square divisions,
+// division size is W / DIVS_X pixels. Trace is drawn column by column: only the
+// part of the previous trace in the same column is erased and the graticule
+// under it is restored, so there is no need to clear the whole area. A band of
+// rows in every column can be drawn under the trace, e.g. a reference trace;
+// it is restored together with the graticule. Area is up to 256 rows high.
+pub struct Plot<const W: usize> {
+    x: i32,
+    y: i32,
+    fg: Rgb565,
+    grid: Rgb565,
+    bg: Rgb565,
+    mark: Rgb565,
+    layer: Rgb565,
+    shown: [Option<Span>; W],
+    under: [Option<(u8, u8)>; W],
+}
+
+impl<const W: usize> Plot<W> {
+    // x, y: top-left corner of the area on the screen
+    pub fn new(x: i32, y: i32, fg: Rgb565, grid: Rgb565, bg: Rgb565) -> Plot<W> {
+        Plot {
+            x,
+            y,
+            fg,
+            grid,
+            bg,
+            mark: Rgb565::RED,
+            layer: Rgb565::CYAN,
+            shown: [None; W],
+            under: [None; W],
+        }
+    }
+
+    pub fn div() -> i32 {
+        W as i32 / DIVS_X
+    }
+
+    pub fn width() -> i32 {
+        Plot::<W>::div() * DIVS_X
+    }
+
+    pub fn height() -> i32 {
+        Plot::<W>::div() * DIVS_Y
+    }
+
+    pub fn get_x(&self) -> i32 {
+        self.x
+    }
+
+    pub fn get_y(&self) -> i32 {
+        self.y
+    }
+
+    pub fn set_color(&mut self, fg: Rgb565) {
+        self.fg = fg;
+    }
+
+    // colors of marked trace columns and of the band under the trace
+    pub fn set_mark_color(&mut self, mark: Rgb565) {
+        self.mark = mark;
+    }
+
+    pub fn set_layer_color(&mut self, layer: Rgb565) {
+        self.layer = layer;
+    }
+
+    // fill the area with background and draw the graticule, previous trace and band are forgotten
+    pub fn draw_grid<D>(&mut self, display: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Rgb565>,
+    {
+        let (w, h) = (Plot::<W>::width(), Plot::<W>::height());
+
+        Rectangle::new(
+            Point::new(self.x, self.y),
+            Point::new(self.x + w - 1, self.y + h - 1),
+        )
+        .into_styled(PrimitiveStyle::with_fill(self.bg))
+        .draw(display)?;
+
+        self.under = [None; W];
+
+        for c in 0..w {
+            self.restore(display, c, 0, h - 1)?;
+        }
+
+        self.shown = [None; W];
+
+        Ok(())
+    }
+
+    // erase the trace restoring the graticule under it
+    pub fn clear<D>(&mut self, display: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Rgb565>,
+    {
+        for c in 0..W {
+            self.update(display, c, None)?;
+        }
+
+        Ok(())
+    }
+
+    // Draw samples as a connected trace starting from the left edge. When a column
+    // covers several samples, it shows their min/max range, so short spikes are
+    // not lost. Columns with no samples are left empty.
+    pub fn draw_trace<D>(
+        &mut self,
+        display: &mut D,
+        samples: &[u16],
+        scale: &Scale,
+    ) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Rgb565>,
+    {
+        self.draw_marked(display, samples, scale, |_| false)
+    }
+
+    // draw the trace, columns with any sample j for which marked(j) is true are in the mark color
+    pub fn draw_marked<D, F>(
+        &mut self,
+        display: &mut D,
+        samples: &[u16],
+        scale: &Scale,
+        marked: F,
+    ) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Rgb565>,
+        F: Fn(usize) -> bool,
+    {
+        let mut prev = None;
+
+        for c in 0..W {
+            let span = self.column(samples, scale, c, &mut prev, &marked);
+            self.update(display, c, span)?;
+        }
+
+        Ok(())
+    }
+
+    // Draw a trace given by the range of values in every column from the left edge,
+    // e.g. decimated by the caller; columns with None are left empty. Ranges of
+    // adjacent columns are joined, so the trace stays connected.
+    pub fn draw_columns<D, I>(
+        &mut self,
+        display: &mut D,
+        columns: I,
+        scale: &Scale,
+    ) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Rgb565>,
+        I: IntoIterator<Item = Option<(u16, u16)>>,
+    {
+        let mut columns = columns.into_iter();
+        let mut prev: Option<(i32, i32)> = None;
+
+        for c in 0..W {
+            let range = columns
+                .next()
+                .flatten()
+                .filter(|_| c < Plot::<W>::width() as usize);
+
+            let span = range.map(|(lo, hi)| {
+                let (top, bottom) = (self.row(hi, scale), self.row(lo, scale));
+                let span = match prev {
+                    Some((t, b)) => Span::new(top.min(b), bottom.max(t), false),
+                    None => Span::new(top, bottom, false),
+                };
+
+                prev = Some((top, bottom));
+                span
+            });
+
+            if span.is_none() {
+                prev = None;
+            }
+
+            self.update(display, c, span)?;
+        }
+
+        Ok(())
+    }
+
+    // Draw the band under the trace: bounds(j) is the range of sample values at
+    // sample j of len samples, a column shows the union of its samples ranges.
+    pub fn draw_under<D, F>(
+        &mut self,
+        display: &mut D,
+        len: usize,
+        scale: &Scale,
+        bounds: F,
+    ) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Rgb565>,
+        F: Fn(usize) -> Option<(u16, u16)>,
+    {
+        for c in 0..W {
+            let (first, last) = Plot::<W>::samples(scale, c);
+            let mut range: Option<(u16, u16)> = None;
+
+            if c < Plot::<W>::width() as usize {
+                for j in first..last.max(first + 1).min(len) {
+                    if let Some((lo, hi)) = bounds(j) {
+                        range = Some(match range {
+                            Some((l, h)) => (l.min(lo), h.max(hi)),
+                            None => (lo, hi),
+                        });
+                    }
+                }
+            }
+
+            let band = range.map(|(lo, hi)| (self.row(hi, scale) as u8, self.row(lo, scale) as u8));
+
+            self.set_under(display, c, band)?;
+        }
+
+        Ok(())
+    }
+
+    // erase the band under the trace
+    pub fn clear_under<D>(&mut self, display: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Rgb565>,
+    {
+        for c in 0..W {
+            self.set_under(display, c, None)?;
+        }
+
+        Ok(())
+    }
+
+    // screen row of the sample value, clipped to the area
+    pub fn row(&self, v: u16, scale: &Scale) -> i32 {
+        let h = Plot::<W>::height();
+        let counts = scale.counts.max(1) as i32;
+        let row = h / 2 - (v as i32 - scale.offset as i32) * Plot::<W>::div() / counts;
+
+        row.max(0).min(h - 1)
+    }
+
+    // screen column of the sample value for XY mode, clipped to the area
+    pub fn col(&self, v: u16, scale: &Scale) -> i32 {
+        let w = Plot::<W>::width();
+        let counts = scale.counts.max(1) as i32;
+        let col = w / 2 + (v as i32 - scale.offset as i32) * Plot::<W>::div() / counts;
+
+        col.max(0).min(w - 1)
+    }
+
+    // single point in the area, c and r are relative to the top-left corner
+    pub fn draw_point<D>(&self, display: &mut D, c: i32, r: i32) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Rgb565>,
+    {
+        Pixel(Point::new(self.x + c, self.y + r), self.fg).draw(display)
+    }
+
+    // erase the point restoring the graticule under it
+    pub fn erase_point<D>(&self, display: &mut D, c: i32, r: i32) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Rgb565>,
+    {
+        Pixel(Point::new(self.x + c, self.y + r), self.background(c, r)).draw(display)
+    }
+
+    // first and last (exclusive) sample of the column c
+    fn samples(scale: &Scale, c: usize) -> (usize, usize) {
+        let div = Plot::<W>::div() as usize;
+
+        // column position in samples: pos / div
+        let pos = c * scale.samples as usize;
+
+        (pos / div, (pos + scale.samples as usize) / div)
+    }
+
+    // trace span in the column c, prev is the row where the trace has left the previous column
+    fn column<F>(
+        &self,
+        samples: &[u16],
+        scale: &Scale,
+        c: usize,
+        prev: &mut Option<i32>,
+        marked: &F,
+    ) -> Option<Span>
+    where
+        F: Fn(usize) -> bool,
+    {
+        let div = Plot::<W>::div() as usize;
+        let len = samples.len();
+        let pos = c * scale.samples as usize;
+        let (first, last) = Plot::<W>::samples(scale, c);
+
+        if first >= len || c >= Plot::<W>::width() as usize {
+            *prev = None;
+            return None;
+        }
+
+        let (lo, hi, end) = if last > first + 1 {
+            let part = &samples[first..last.min(len)];
+            let lo = part.iter().copied().min().unwrap_or(0);
+            let hi = part.iter().copied().max().unwrap_or(0);
+            (lo, hi, part[part.len() - 1])
+        } else {
+            // less than a sample per column: interpolate
+            let s0 = samples[first] as i32;
+            let s1 = if first + 1 < len {
+                samples[first + 1] as i32
+            } else {
+                s0
+            };
+            let v = (s0 + (s1 - s0) * (pos % div) as i32 / div as i32) as u16;
+            (v, v, v)
+        };
+
+        let (mut top, mut bottom) = (self.row(hi, scale), self.row(lo, scale));
+
+        if let Some(p) = *prev {
+            top = top.min(p);
+            bottom = bottom.max(p);
+        }
+
+        *prev = Some(self.row(end, scale));
+
+        let marked = (first..last.max(first + 1).min(len)).any(marked);
+
+        Some(Span::new(top, bottom, marked))
+    }
+
+    // replace the trace in the column c
+    fn update<D>(&mut self, display: &mut D, c: usize, span: Option<Span>) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Rgb565>,
+    {
+        if self.shown[c] == span {
+            return Ok(());
+        }
+
+        if let Some(old) = self.shown[c] {
+            self.vline(display, c as i32, old, self.bg)?;
+            self.restore(display, c as i32, old.top as i32, old.bottom as i32)?;
+        }
+
+        if let Some(new) = span {
+            self.vline(display, c as i32, new, self.color(new))?;
+        }
+
+        self.shown[c] = span;
+
+        Ok(())
+    }
+
+    // replace the band under the trace in the column c, the trace stays on top
+    fn set_under<D>(
+        &mut self,
+        display: &mut D,
+        c: usize,
+        band: Option<(u8, u8)>,
+    ) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Rgb565>,
+    {
+        let old = self.under[c];
+
+        if old == band {
+            return Ok(());
+        }
+
+        self.under[c] = band;
+
+        for (top, bottom) in [old, band].iter().flatten() {
+            let (top, bottom) = (*top as i32, *bottom as i32);
+
+            self.vline(display, c as i32, Span::new(top, bottom, false), self.bg)?;
+            self.restore(display, c as i32, top, bottom)?;
+        }
+
+        if let Some(span) = self.shown[c] {
+            self.vline(display, c as i32, span, self.color(span))?;
+        }
+
+        Ok(())
+    }
+
+    fn color(&self, span: Span) -> Rgb565 {
+        if span.marked {
+            self.mark
+        } else {
+            self.fg
+        }
+    }
+
+    fn vline<D>(&self, display: &mut D, c: i32, span: Span, color: Rgb565) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Rgb565>,
+    {
+        Rectangle::new(
+            Point::new(self.x + c, self.y + span.top as i32),
+            Point::new(self.x + c, self.y + span.bottom as i32),
+        )
+        .into_styled(PrimitiveStyle::with_fill(color))
+        .draw(display)
+    }
+
+    // draw graticule and band pixels in the column c between rows top and bottom
+    fn restore<D>(&self, display: &mut D, c: i32, top: i32, bottom: i32) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Rgb565>,
+    {
+        let (x, y, bg) = (self.x, self.y, self.bg);
+
+        display.draw_iter(
+            (top..=bottom)
+                .map(|r| (r, self.background(c, r)))
+                .filter(|(_, color)| *color != bg)
+                .map(|(r, color)| Pixel(Point::new(x + c, y + r), color)),
+        )
+    }
+
+    // color of the area at c, r without the trace
+    fn background(&self, c: i32, r: i32) -> Rgb565 {
+        match self.under[c as usize] {
+            Some((top, bottom)) if r >= top as i32 && r <= bottom as i32 => self.layer,
+            _ if Plot::<W>::is_grid(c, r) => self.grid,
+            _ => self.bg,
+        }
+    }
+
+    fn is_grid(c: i32, r: i32) -> bool {
+        let (w, h, div) = (Plot::<W>::width(), Plot::<W>::height(), Plot::<W>::div());
+        let tick = (div / TICKS).max(1);
+        let (cm, rm) = (c % div, r % div);
+        let (cd, rd) = (c % DOT, r % DOT);
+
+        // border
+        if c == 0 || r == 0 || c == w - 1 || r == h - 1 {
+            return true;
+        }
+
+        // dotted division lines
+        if (cm == 0 && rd == 0) || (rm == 0 && cd == 0) {
+            return true;
+        }
+
+        // ticks on the center lines
+        let ct = c % tick;
+        let rt = r % tick;
+
+        ((r - h / 2).abs() <= 1 && ct == 0) || ((c - w / 2).abs() <= 1 && rt == 0)
+    }
+}
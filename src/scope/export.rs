@@ -0,0 +1,392 @@
+// Captured frames in a compact binary format for streaming to the host.
+// Multi-byte fields are little-endian, samples are 12-bit values packed in
+// pairs into 3 bytes. CRC covers everything after the sync bytes.
+//
+// offset  size  field
+//      0     2  sync: 0xa5 0x5a
+//      2     1  format version
+//      3     1  flags: bit 0 - trigger index is valid
+//      4     2  sequence number, gaps mean lost frames
+//      6     4  sample rate, Hz
+//     10     2  volts/div, mV
+//     12     2  ADC full scale, mV
+//     14     2  ground level, ADC counts
+//     16     2  trigger index
+//     18     2  number of samples N
+//     20     -  (3N + 1) / 2 bytes of samples: s0 = a0 | (a1 & 0xf) << 8, s1 = a1 >> 4 | a2 << 4, ...
+//      -     2  CRC-16/CCITT-FALSE
+
+pub const SYNC: [u8; 2] = [0xa5, 0x5a];
+pub const VERSION: u8 = 1;
+
+pub const HEADER_LEN: usize = 20;
+pub const CRC_LEN: usize = 2;
+
+const TRIGGERED: u8 = 1 << 0;
+
+// encoded frame size for n samples
+pub const fn frame_len(n: usize) -> usize {
+    HEADER_LEN + 3 * n / 2 + n % 2 + CRC_LEN
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Header {
+    pub sequence: u16,
+    pub rate: u32,
+    pub vdiv: u16,
+    pub vref: u16,
+    pub offset: u16,
+    pub trigger: Option<u16>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Error {
+    // output buffer is too small for the frame
+    NoSpace,
+}
+
+pub fn encode(header: &Header, samples: &[u16], out: &mut [u8]) -> Result<usize, Error> {
+    let len = frame_len(samples.len());
+
+    if out.len() < len || samples.len() > u16::MAX as usize {
+        return Err(Error::NoSpace);
+    }
+
+    let flags = if header.trigger.is_some() {
+        TRIGGERED
+    } else {
+        0
+    };
+
+    out[0..2].copy_from_slice(&SYNC);
+    out[2] = VERSION;
+    out[3] = flags;
+    out[4..6].copy_from_slice(&header.sequence.to_le_bytes());
+    out[6..10].copy_from_slice(&header.rate.to_le_bytes());
+    out[10..12].copy_from_slice(&header.vdiv.to_le_bytes());
+    out[12..14].copy_from_slice(&header.vref.to_le_bytes());
+    out[14..16].copy_from_slice(&header.offset.to_le_bytes());
+    out[16..18].copy_from_slice(&header.trigger.unwrap_or(0).to_le_bytes());
+    out[18..20].copy_from_slice(&(samples.len() as u16).to_le_bytes());
+
+    let data = &mut out[HEADER_LEN..len - CRC_LEN];
+
+    for (pair, bytes) in samples.chunks(2).zip(data.chunks_mut(3)) {
+        let a = pair[0] & 0xfff;
+
+        bytes[0] = a as u8;
+
+        if let Some(b) = pair.get(1) {
+            let b = b & 0xfff;
+
+            bytes[1] = (a >> 8) as u8 | (b << 4) as u8;
+            bytes[2] = (b >> 4) as u8;
+        } else {
+            bytes[1] = (a >> 8) as u8;
+        }
+    }
+
+    let crc = crc16(&out[SYNC.len()..len - CRC_LEN]);
+    out[len - CRC_LEN..len].copy_from_slice(&crc.to_le_bytes());
+
+    Ok(len)
+}
+
+// CRC-16/CCITT-FALSE: polynomial 0x1021, initial value 0xffff
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0xffff;
+
+    for b in data {
+        crc ^= (*b as u16) << 8;
+
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+
+    crc
+}
+
+// decoded frame, samples are unpacked on access
+pub struct Frame<'a> {
+    pub header: Header,
+    data: &'a [u8],
+    len: usize,
+}
+
+impl<'a> Frame<'a> {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, i: usize) -> u16 {
+        let b = &self.data[i / 2 * 3..];
+
+        if i & 1 == 0 {
+            b[0] as u16 | (b[1] as u16 & 0xf) << 8
+        } else {
+            (b[1] as u16) >> 4 | (b[2] as u16) << 4
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = u16> + '_ {
+        (0..self.len).map(move |i| self.get(i))
+    }
+}
+
+// Stream decoder: bytes may arrive in arbitrary chunks, frames up to N bytes
+// are reassembled. After garbage or a corrupted frame the decoder looks for
+// the next sync bytes.
+pub struct Decoder<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+    errors: u32,
+}
+
+impl<const N: usize> Default for Decoder<N> {
+    fn default() -> Decoder<N> {
+        Decoder::new()
+    }
+}
+
+impl<const N: usize> Decoder<N> {
+    pub fn new() -> Decoder<N> {
+        Decoder {
+            buf: [0; N],
+            len: 0,
+            errors: 0,
+        }
+    }
+
+    // frames dropped because of bad version, size or CRC
+    pub fn get_errors(&self) -> u32 {
+        self.errors
+    }
+
+    // feed received bytes, f is called for every valid frame
+    pub fn feed<F>(&mut self, data: &[u8], mut f: F)
+    where
+        F: FnMut(&Frame),
+    {
+        for b in data {
+            self.buf[self.len] = *b;
+            self.len += 1;
+            self.scan(&mut f);
+        }
+    }
+
+    fn scan<F>(&mut self, f: &mut F)
+    where
+        F: FnMut(&Frame),
+    {
+        while self.len > 0 {
+            let sync = self.buf[0] == SYNC[0] && (self.len < 2 || self.buf[1] == SYNC[1]);
+
+            if !sync {
+                self.consume(1);
+                continue;
+            }
+
+            if self.len < HEADER_LEN {
+                return;
+            }
+
+            let count = u16::from_le_bytes([self.buf[18], self.buf[19]]) as usize;
+            let len = frame_len(count);
+
+            if self.buf[2] != VERSION || len > N {
+                self.errors += 1;
+                self.consume(1);
+                continue;
+            }
+
+            if self.len < len {
+                return;
+            }
+
+            let crc = u16::from_le_bytes([self.buf[len - 2], self.buf[len - 1]]);
+
+            if crc != crc16(&self.buf[SYNC.len()..len - CRC_LEN]) {
+                self.errors += 1;
+                self.consume(1);
+                continue;
+            }
+
+            f(&self.frame(count, len));
+            self.consume(len);
+        }
+    }
+
+    fn frame(&self, count: usize, len: usize) -> Frame<'_> {
+        let b = &self.buf;
+        let u16_at = |i: usize| u16::from_le_bytes([b[i], b[i + 1]]);
+
+        Frame {
+            header: Header {
+                sequence: u16_at(4),
+                rate: u32::from_le_bytes([b[6], b[7], b[8], b[9]]),
+                vdiv: u16_at(10),
+                vref: u16_at(12),
+                offset: u16_at(14),
+                trigger: if b[3] & TRIGGERED != 0 {
+                    Some(u16_at(16))
+                } else {
+                    None
+                },
+            },
+            data: &b[HEADER_LEN..len - CRC_LEN],
+            len: count,
+        }
+    }
+
+    // remove n bytes from the start of the buffer
+    fn consume(&mut self, n: usize) {
+        self.buf.copy_within(n..self.len, 0);
+        self.len -= n;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // stream recorded for tools/test_capture.py: garbage, frames 7 and 8, corrupted frame 9, frame 11
+    const RECORDED: &[u8] = include_bytes!("../../tools/testdata/capture.bin");
+
+    type Decoded = Vec<(Header, Vec<u16>)>;
+
+    fn header(sequence: u16, trigger: Option<u16>) -> Header {
+        Header {
+            sequence,
+            rate: 50_000,
+            vdiv: 500,
+            vref: 3300,
+            offset: 2048,
+            trigger,
+        }
+    }
+
+    // ramp over the 12-bit range
+    fn samples(n: usize) -> Vec<u16> {
+        (0..n).map(|i| (i * 37 % 4096) as u16).collect()
+    }
+
+    fn frame(header: &Header, samples: &[u16]) -> Vec<u8> {
+        let mut out = vec![0; frame_len(samples.len())];
+        assert_eq!(encode(header, samples, &mut out), Ok(out.len()));
+        out
+    }
+
+    fn decode<const N: usize>(decoder: &mut Decoder<N>, data: &[u8], frames: &mut Decoded) {
+        decoder.feed(data, |f| frames.push((f.header, f.iter().collect())));
+    }
+
+    #[test]
+    fn crc() {
+        assert_eq!(crc16(b"123456789"), 0x29b1);
+    }
+
+    #[test]
+    fn round_trip() {
+        // sample count and frame size
+        for (n, len) in [(0, 22), (1, 24), (2, 25), (3, 27), (300, 472), (301, 474)].iter() {
+            let h = header(*n as u16, Some(*n as u16 / 2));
+            let s = samples(*n);
+            let data = frame(&h, &s);
+
+            assert_eq!(data.len(), *len);
+
+            let mut d = Decoder::<512>::new();
+            let mut frames = Vec::new();
+            decode(&mut d, &data, &mut frames);
+
+            assert_eq!(frames, [(h, s)]);
+            assert_eq!(d.get_errors(), 0);
+        }
+
+        let mut small = [0; 10];
+        assert_eq!(
+            encode(&header(0, None), &[0; 4], &mut small),
+            Err(Error::NoSpace)
+        );
+    }
+
+    #[test]
+    fn split_stream() {
+        let expected = [
+            (header(1, Some(3)), samples(7)),
+            (header(2, None), samples(8)),
+        ];
+        let stream = [
+            frame(&expected[0].0, &expected[0].1),
+            frame(&expected[1].0, &expected[1].1),
+        ]
+        .concat();
+
+        for k in 0..=stream.len() {
+            let mut d = Decoder::<64>::new();
+            let mut frames = Vec::new();
+            decode(&mut d, &stream[..k], &mut frames);
+            decode(&mut d, &stream[k..], &mut frames);
+
+            assert_eq!(frames, expected, "split at {}", k);
+            assert_eq!(d.get_errors(), 0);
+        }
+    }
+
+    #[test]
+    fn leading_garbage() {
+        let h = header(5, None);
+        let s = samples(9);
+        let stream = [&[0x13, 0xa5, 0x00, 0x5a, 0xa5, 0xa5][..], &frame(&h, &s)].concat();
+
+        let mut d = Decoder::<64>::new();
+        let mut frames = Vec::new();
+
+        for b in stream.chunks(1) {
+            decode(&mut d, b, &mut frames);
+        }
+
+        assert_eq!(frames, [(h, s)]);
+        assert_eq!(d.get_errors(), 0);
+    }
+
+    #[test]
+    fn corrupted_frame() {
+        let frames: Decoded = (0..3)
+            .map(|i| (header(i, None), samples(10 + i as usize)))
+            .collect();
+        let mut data: Vec<Vec<u8>> = frames.iter().map(|(h, s)| frame(h, s)).collect();
+        data[1][HEADER_LEN + 4] ^= 0x01;
+
+        let mut d = Decoder::<64>::new();
+        let mut decoded = Vec::new();
+        decode(&mut d, &data.concat(), &mut decoded);
+
+        assert_eq!(decoded, [frames[0].clone(), frames[2].clone()]);
+        assert_eq!(d.get_errors(), 1);
+    }
+
+    #[test]
+    fn recorded() {
+        let mut d = Decoder::<64>::new();
+        let mut frames = Vec::new();
+        decode(&mut d, RECORDED, &mut frames);
+
+        let sequences: Vec<u16> = frames.iter().map(|(h, _)| h.sequence).collect();
+
+        assert_eq!(sequences, [7, 8, 11]);
+        assert_eq!(frames[0].0.trigger, Some(2));
+        assert_eq!(frames[0].1, [2048, 2148, 2248, 1948, 4095]);
+        assert_eq!(frames[2].0.rate, 2000);
+        assert_eq!(d.get_errors(), 1);
+    }
+}
@@ -0,0 +1,340 @@
+use core::fmt;
+use fixed::types::{I16F16, U24F8};
+
+// In-place radix-2 FFT in I16F16 fixed point. Every butterfly stage halves its
+// outputs, so the result is the DFT divided by N and never overflows for ADC
+// samples. Twiddle factors come from a quarter-wave sine table computed once,
+// the same table gives window coefficients.
+
+// largest transform size
+pub const MAX_N: usize = 512;
+
+const QUARTER: usize = MAX_N / 4;
+
+// lowest reported level, dB
+const FLOOR: i32 = -120;
+
+// 10 * log10(2)
+const DB_PER_OCTAVE: I16F16 = I16F16::from_bits(197_283);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Window {
+    Rect,
+    Hann,
+    Hamming,
+}
+
+impl Window {
+    // level of a full scale sine: 20 * log10(2 / (gain * 2048)) for window coherent gain
+    fn reference(self) -> I16F16 {
+        match self {
+            Window::Rect => I16F16::from_bits(-3_945_710),
+            Window::Hann => I16F16::from_bits(-3_551_095),
+            Window::Hamming => I16F16::from_bits(-3_594_943),
+        }
+    }
+}
+
+pub struct Fft<const N: usize> {
+    // sin(2 * pi * k / MAX_N) for k in 0..=MAX_N / 4
+    table: [I16F16; QUARTER + 1],
+}
+
+impl<const N: usize> Default for Fft<N> {
+    fn default() -> Fft<N> {
+        Fft::new()
+    }
+}
+
+impl<const N: usize> Fft<N> {
+    // N is a power of two up to MAX_N
+    pub fn new() -> Fft<N> {
+        assert!(N.is_power_of_two() && (4..=MAX_N).contains(&N));
+
+        let mut table = [I16F16::from_num(0); QUARTER + 1];
+
+        for (k, t) in table.iter_mut().enumerate() {
+            let x = core::f64::consts::PI * 2.0 * k as f64 / MAX_N as f64;
+            *t = I16F16::from_num(sine(x));
+        }
+
+        Fft { table }
+    }
+
+    // sin and cos of 2 * pi * j / N
+    fn sin(&self, j: usize) -> I16F16 {
+        let k = (j * (MAX_N / N)) % MAX_N;
+
+        match k / QUARTER {
+            0 => self.table[k],
+            1 => self.table[2 * QUARTER - k],
+            2 => -self.table[k - 2 * QUARTER],
+            _ => -self.table[4 * QUARTER - k],
+        }
+    }
+
+    fn cos(&self, j: usize) -> I16F16 {
+        self.sin(j + N / 4)
+    }
+
+    pub fn window(&self, w: Window, n: usize) -> I16F16 {
+        let c = self.cos(n);
+
+        match w {
+            Window::Rect => I16F16::from_num(1),
+            Window::Hann => I16F16::from_num(0.5) - c / 2,
+            Window::Hamming => I16F16::from_num(0.54) - c * I16F16::from_num(0.46),
+        }
+    }
+
+    // load the first N samples with mean removed and window applied
+    pub fn load(&self, samples: &[u16], w: Window, re: &mut [I16F16; N], im: &mut [I16F16; N]) {
+        let n = samples.len().min(N);
+        let sum: u32 = samples[..n].iter().map(|s| *s as u32).sum();
+        let mean = I16F16::from_num(sum / n.max(1) as u32);
+
+        for i in 0..N {
+            re[i] = if i < n {
+                (I16F16::from_num(samples[i]) - mean) * self.window(w, i)
+            } else {
+                I16F16::from_num(0)
+            };
+            im[i] = I16F16::from_num(0);
+        }
+    }
+
+    // forward transform, the result is scaled by 1/N
+    pub fn transform(&self, re: &mut [I16F16; N], im: &mut [I16F16; N]) {
+        let bits = N.trailing_zeros();
+
+        for i in 0..N {
+            let j = i.reverse_bits() >> (usize::BITS - bits);
+
+            if j > i {
+                re.swap(i, j);
+                im.swap(i, j);
+            }
+        }
+
+        let mut len = 2;
+
+        while len <= N {
+            let half = len / 2;
+            let step = N / len;
+
+            for start in (0..N).step_by(len) {
+                for k in 0..half {
+                    let (wr, wi) = (self.cos(k * step), -self.sin(k * step));
+                    let (a, b) = (start + k, start + k + half);
+
+                    let tr = re[b] * wr - im[b] * wi;
+                    let ti = re[b] * wi + im[b] * wr;
+
+                    re[b] = (re[a] - tr) / 2;
+                    im[b] = (im[a] - ti) / 2;
+                    re[a] = (re[a] + tr) / 2;
+                    im[a] = (im[a] + ti) / 2;
+                }
+            }
+
+            len *= 2;
+        }
+    }
+}
+
+// bin level in dB relative to a full scale sine of 12-bit ADC
+pub fn db(re: I16F16, im: I16F16, w: Window) -> I16F16 {
+    let (r, i) = (re.to_bits() as i64, im.to_bits() as i64);
+    let power = (r * r + i * i) as u64;
+
+    if power == 0 {
+        return I16F16::from_num(FLOOR);
+    }
+
+    // power bits are scaled by 2^32
+    let level = DB_PER_OCTAVE * (log2(power) - I16F16::from_num(32)) + w.reference();
+
+    level.max(I16F16::from_num(FLOOR))
+}
+
+// highest bin above DC and its position refined by a parabola through the neighbours
+pub fn peak(levels: &[I16F16]) -> Option<(U24F8, I16F16)> {
+    let mut best: Option<(usize, I16F16)> = None;
+
+    for (k, v) in levels.iter().enumerate().skip(1) {
+        match best {
+            Some((_, b)) if *v <= b => {}
+            _ => best = Some((k, *v)),
+        }
+    }
+
+    let (k, top) = best?;
+
+    let mut pos = U24F8::from_num(k);
+
+    if k + 1 < levels.len() {
+        let (a, c) = (levels[k - 1], levels[k + 1]);
+        let den = a - top * 2 + c;
+
+        if den < 0 {
+            let d = ((a - c) / den / 2).to_bits() >> 8;
+            let d = d.clamp(-128, 128);
+
+            pos = if d < 0 {
+                pos - U24F8::from_bits((-d) as u32)
+            } else {
+                pos + U24F8::from_bits(d as u32)
+            };
+        }
+    }
+
+    Some((pos, top))
+}
+
+// frequency of the bin position for the sample rate, Hz
+pub fn frequency(pos: U24F8, rate: u32, n: usize) -> U24F8 {
+    U24F8::from_bits((pos.to_bits() as u64 * rate as u64 / n as u64) as u32)
+}
+
+// log2 with 16 fractional bits: integer part from the highest set bit, fraction by repeated squaring
+fn log2(v: u64) -> I16F16 {
+    let n = 63 - v.leading_zeros();
+
+    // mantissa in [1, 2) with 30 fractional bits
+    let mut m = if n > 30 { v >> (n - 30) } else { v << (30 - n) };
+    let mut frac = 0i32;
+
+    for i in 0..16 {
+        m = (m * m) >> 30;
+
+        if m >= 1 << 31 {
+            m >>= 1;
+            frac |= 1 << (15 - i);
+        }
+    }
+
+    I16F16::from_bits(((n as i32) << 16) | frac)
+}
+
+// level with one decimal
+pub struct Decibels(pub I16F16);
+
+impl fmt::Display for Decibels {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let tenths = (self.0 * 10).round().to_num::<i32>();
+        let sign = if tenths < 0 { "-" } else { "" };
+        let v = tenths.unsigned_abs();
+
+        write!(f, "{}{}.{}dB", sign, v / 10, v % 10)
+    }
+}
+
+// sine by Taylor series, good enough for [0, pi / 2]
+pub fn sine(x: f64) -> f64 {
+    let mut term = x;
+    let mut sum = x;
+
+    for k in 1..8 {
+        term = -term * x * x / ((2 * k) as f64 * (2 * k + 1) as f64);
+        sum += term;
+    }
+
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::f64::consts::PI;
+
+    const WINDOWS: [Window; 3] = [Window::Rect, Window::Hann, Window::Hamming];
+
+    // sine of amplitude a counts at bin position k around the ADC midscale
+    fn tone(n: usize, k: f64, a: f64) -> Vec<u16> {
+        (0..n)
+            .map(|i| (2048.0 + a * (2.0 * PI * k * i as f64 / n as f64).sin()).round() as u16)
+            .collect()
+    }
+
+    // largest difference from the naive DFT divided by N, counts
+    fn error<const N: usize>(w: Window) -> f64 {
+        let fft = Fft::<N>::new();
+        let samples: Vec<u16> = tone(N, 17.3, 1500.0)
+            .iter()
+            .zip(tone(N, 60.0, 300.0))
+            .map(|(a, b)| a + b - 2048)
+            .collect();
+
+        let mut re = [I16F16::from_num(0); N];
+        let mut im = [I16F16::from_num(0); N];
+        fft.load(&samples, w, &mut re, &mut im);
+
+        let input: Vec<f64> = re.iter().map(|v| v.to_num::<f64>()).collect();
+        fft.transform(&mut re, &mut im);
+
+        let mut max: f64 = 0.0;
+
+        for k in 0..N {
+            let (mut r, mut i) = (0.0, 0.0);
+
+            for (j, x) in input.iter().enumerate() {
+                let a = -2.0 * PI * (k * j % N) as f64 / N as f64;
+                r += x * a.cos();
+                i += x * a.sin();
+            }
+
+            max = max
+                .max((re[k].to_num::<f64>() - r / N as f64).abs())
+                .max((im[k].to_num::<f64>() - i / N as f64).abs());
+        }
+
+        max
+    }
+
+    #[test]
+    fn transform_matches_dft() {
+        // rounding of the halved butterfly outputs stays below 0.01 counts
+        for w in WINDOWS.iter() {
+            assert!(error::<256>(*w) < 0.01, "{:?}", w);
+            assert!(error::<512>(*w) < 0.01, "{:?}", w);
+        }
+    }
+
+    #[test]
+    fn full_scale_sine_is_0db() {
+        const N: usize = 256;
+        let fft = Fft::<N>::new();
+        let samples = tone(N, 32.0, 2047.0);
+
+        for w in WINDOWS.iter() {
+            let mut re = [I16F16::from_num(0); N];
+            let mut im = [I16F16::from_num(0); N];
+            fft.load(&samples, *w, &mut re, &mut im);
+            fft.transform(&mut re, &mut im);
+
+            let level = db(re[32], im[32], *w).to_num::<f64>();
+            assert!(level.abs() < 0.1, "{:?}: {}", w, level);
+        }
+    }
+
+    #[test]
+    fn peak_between_bins() {
+        const N: usize = 512;
+        let fft = Fft::<N>::new();
+        let samples = tone(N, 40.3, 1000.0);
+
+        let mut re = [I16F16::from_num(0); N];
+        let mut im = [I16F16::from_num(0); N];
+        fft.load(&samples, Window::Hann, &mut re, &mut im);
+        fft.transform(&mut re, &mut im);
+
+        let levels: Vec<I16F16> = (0..N / 2).map(|k| db(re[k], im[k], Window::Hann)).collect();
+        let (pos, _) = peak(&levels).unwrap();
+
+        assert!((pos.to_num::<f64>() - 40.3).abs() < 0.1, "{}", pos);
+        assert_eq!(
+            frequency(U24F8::from_num(40), 20_000, N),
+            U24F8::from_num(1562.5)
+        );
+    }
+}
@@ -0,0 +1,208 @@
+// Reference trace for overlay and mask test. A frame is decimated to N points,
+// every point keeps min and max of its samples, so short spikes are not lost.
+// Values are stored with 8-bit resolution rounded outwards: the stored range
+// always contains the samples. Mask test checks that every sample of a frame
+// is within the range of its point widened by the tolerance.
+
+// low bits dropped from 12-bit samples
+const SHIFT: u32 = 4;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Envelope<const N: usize> {
+    points: [(u8, u8); N],
+    // samples in the stored frame, empty reference if 0
+    len: usize,
+}
+
+impl<const N: usize> Default for Envelope<N> {
+    fn default() -> Envelope<N> {
+        Envelope::new()
+    }
+}
+
+impl<const N: usize> Envelope<N> {
+    pub const fn new() -> Envelope<N> {
+        Envelope {
+            points: [(0, 0); N],
+            len: 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    // decimate the frame
+    pub fn store(&mut self, samples: &[u16]) {
+        let len = samples.len();
+
+        if len == 0 {
+            self.clear();
+            return;
+        }
+
+        for (i, p) in self.points.iter_mut().enumerate() {
+            let first = i * len / N;
+            let last = ((i + 1) * len / N).max(first + 1);
+            let part = &samples[first..last];
+
+            let lo = part.iter().copied().min().unwrap_or(0);
+            let hi = part.iter().copied().max().unwrap_or(0);
+
+            *p = ((lo >> SHIFT) as u8, (hi.min(0xfff) >> SHIFT) as u8);
+        }
+
+        self.len = len;
+    }
+
+    // range of the sample j of a frame as long as the stored one, widened by tolerance counts
+    pub fn bounds(&self, j: usize, tolerance: u16) -> Option<(u16, u16)> {
+        if j >= self.len {
+            return None;
+        }
+
+        // inverse of the partition in store: the last point whose part starts at the sample or before
+        let (lo, hi) = self.points[((j + 1) * N - 1) / self.len];
+        let lo = (lo as u16) << SHIFT;
+        let hi = (hi as u16) << SHIFT | ((1 << SHIFT) - 1);
+
+        Some((lo.saturating_sub(tolerance), hi.saturating_add(tolerance)))
+    }
+
+    // sample j of the frame is out of the range
+    pub fn fails(&self, samples: &[u16], j: usize, tolerance: u16) -> bool {
+        match (samples.get(j), self.bounds(j, tolerance)) {
+            (Some(s), Some((lo, hi))) => *s < lo || *s > hi,
+            _ => false,
+        }
+    }
+
+    // number of samples out of the range
+    pub fn test(&self, samples: &[u16], tolerance: u16) -> usize {
+        (0..samples.len())
+            .filter(|j| self.fails(samples, *j, tolerance))
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEN: usize = 256;
+
+    // triangle over most of the 12-bit range
+    fn trace(len: usize) -> Vec<u16> {
+        (0..len)
+            .map(|i| {
+                let t = (i * 4096 / len) as u16;
+                if t < 2048 {
+                    200 + t * 3 / 2
+                } else {
+                    200 + (4095 - t) * 3 / 2
+                }
+            })
+            .collect()
+    }
+
+    fn envelope(samples: &[u16]) -> Envelope<64> {
+        let mut e = Envelope::new();
+        e.store(samples);
+        e
+    }
+
+    #[test]
+    fn stored_trace_passes() {
+        let samples = trace(LEN);
+        let e = envelope(&samples);
+
+        assert!(!e.is_empty());
+        assert_eq!(e.test(&samples, 0), 0);
+
+        // bounds contain the samples they were made from
+        for (j, s) in samples.iter().enumerate() {
+            let (lo, hi) = e.bounds(j, 0).unwrap();
+            assert!(lo <= *s && *s <= hi, "{}: {} not in {}..{}", j, s, lo, hi);
+        }
+    }
+
+    #[test]
+    fn length_not_multiple_of_points() {
+        // noise-like frame, neighbouring samples fall into different ranges
+        for len in [100, 300, 63, 65].iter() {
+            let samples: Vec<u16> = (0..*len).map(|i| (i * 2503 % 4096) as u16).collect();
+            let e = envelope(&samples);
+
+            assert_eq!(e.test(&samples, 0), 0, "{} samples", len);
+        }
+
+        let mut e = Envelope::<60>::new();
+        let samples: Vec<u16> = (0..512).map(|i| (i * 2503 % 4096) as u16).collect();
+        e.store(&samples);
+        assert_eq!(e.test(&samples, 0), 0);
+    }
+
+    #[test]
+    fn trace_within_tolerance_passes() {
+        let e = envelope(&trace(LEN));
+        let shifted: Vec<u16> = trace(LEN).iter().map(|s| s + 20).collect();
+
+        assert_eq!(e.test(&shifted, 20), 0);
+        assert!(e.test(&shifted, 0) > 0);
+    }
+
+    #[test]
+    fn single_sample_fails() {
+        let samples = trace(LEN);
+        let e = envelope(&samples);
+
+        let mut glitch = samples.clone();
+        glitch[100] += 200;
+
+        assert_eq!(e.test(&glitch, 50), 1);
+        assert!(e.fails(&glitch, 100, 50));
+        assert!(!e.fails(&glitch, 99, 50));
+
+        glitch[100] = samples[100] - 200;
+        assert_eq!(e.test(&glitch, 50), 1);
+    }
+
+    #[test]
+    fn different_length() {
+        let e = envelope(&trace(LEN));
+
+        // samples beyond the stored frame are not tested
+        let mut longer = trace(LEN);
+        longer.extend([4095; 16].iter());
+        assert_eq!(e.test(&longer, 0), 0);
+        assert_eq!(e.bounds(LEN, 0), None);
+        assert!(!e.fails(&longer, LEN, 0));
+
+        let shorter = &trace(LEN)[..LEN / 2];
+        assert_eq!(e.test(shorter, 0), 0);
+        assert!(!e.fails(shorter, LEN / 2, 0));
+
+        // frame shorter than the envelope, every sample has its own point
+        let short = trace(16);
+        let e = envelope(&short);
+        assert_eq!(e.test(&short, 0), 0);
+        assert_eq!(e.test(&trace(LEN)[..16], 0), 15);
+    }
+
+    #[test]
+    fn empty() {
+        let mut e = envelope(&trace(LEN));
+
+        e.clear();
+        assert!(e.is_empty());
+        assert_eq!(e.bounds(0, 0), None);
+        assert_eq!(e.test(&trace(LEN), 0), 0);
+
+        let e = envelope(&[]);
+        assert!(e.is_empty());
+    }
+}
@@ -0,0 +1,486 @@
+use core::fmt;
+use fixed::types::U24F8;
+
+// Waveform measurements over a frame of ADC samples, integer and fixed-point
+// maths only. Times are in samples with 1/256 sample resolution: edge crossings
+// are interpolated between samples. Edges are found by a Schmitt trigger at
+// 10% and 90% of the signal range, rise and fall times are measured between
+// these levels, period and duty cycle between crossings of the 50% level.
+
+// smallest peak-to-peak range in ADC counts to look for edges
+const MIN_RANGE: u16 = 16;
+
+// zero crossing intervals may differ by 1/JITTER of the period, otherwise autocorrelation is used
+const JITTER: u32 = 8;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Measurements {
+    pub min: u16,
+    pub max: u16,
+    // mean and RMS relative to the ground offset, ADC counts
+    pub mean: i32,
+    pub rms: u32,
+    // period, rise and fall times in samples
+    pub period: Option<U24F8>,
+    pub rise: Option<U24F8>,
+    pub fall: Option<U24F8>,
+    // high part of the period, percent
+    pub duty: Option<U24F8>,
+}
+
+impl Measurements {
+    pub fn vpp(&self) -> u16 {
+        self.max - self.min
+    }
+
+    // frequency in Hz for the sample rate
+    pub fn frequency(&self, rate: u32) -> Option<U24F8> {
+        self.period
+            .filter(|p| *p > 0)
+            .map(|p| U24F8::from_bits((((rate as u64) << 16) / p.to_bits() as u64) as u32))
+    }
+
+    pub fn period_us(&self, rate: u32) -> Option<U24F8> {
+        self.period.map(|t| micros(t, rate))
+    }
+
+    pub fn rise_us(&self, rate: u32) -> Option<U24F8> {
+        self.rise.map(|t| micros(t, rate))
+    }
+
+    pub fn fall_us(&self, rate: u32) -> Option<U24F8> {
+        self.fall.map(|t| micros(t, rate))
+    }
+}
+
+// samples to microseconds for the sample rate
+pub fn micros(t: U24F8, rate: u32) -> U24F8 {
+    U24F8::from_bits((t.to_bits() as u64 * 1_000_000 / rate.max(1) as u64) as u32)
+}
+
+// ADC counts to millivolts: full scale max counts is vref millivolts
+pub fn millivolts(counts: u32, max: u32, vref: u32) -> u32 {
+    counts * vref / max.max(1)
+}
+
+pub fn measure(samples: &[u16], offset: u16) -> Measurements {
+    let mut m = Measurements::default();
+
+    if samples.is_empty() {
+        return m;
+    }
+
+    let mut min = u16::MAX;
+    let mut max = 0;
+    let mut sum: i64 = 0;
+    let mut sq: u64 = 0;
+
+    for s in samples {
+        let v = *s as i64 - offset as i64;
+
+        min = min.min(*s);
+        max = max.max(*s);
+        sum += v;
+        sq += (v * v) as u64;
+    }
+
+    let n = samples.len() as u64;
+
+    m.min = min;
+    m.max = max;
+    m.mean = (sum / n as i64) as i32;
+    m.rms = isqrt(sq / n) as u32;
+
+    if max - min >= MIN_RANGE {
+        edges(samples, min, max, &mut m);
+
+        if m.period.is_none() {
+            m.period = autocorrelation(samples, m.mean + offset as i32);
+        }
+    }
+
+    m
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Level {
+    Unknown,
+    Low,
+    High,
+}
+
+// position where the signal crosses the level between samples i - 1 and i
+fn cross(i: usize, a: u16, b: u16, level: u16) -> U24F8 {
+    let (d, r) = if b > a {
+        (level - a, b - a)
+    } else {
+        (a - level, a - b)
+    };
+
+    U24F8::from_num(i - 1) + U24F8::from_num(d) / U24F8::from_num(r)
+}
+
+fn edges(samples: &[u16], min: u16, max: u16, m: &mut Measurements) {
+    let range = (max - min) as u32;
+    let lo = min + (range / 10) as u16;
+    let mid = min + (range / 2) as u16;
+    let hi = min + (range * 9 / 10) as u16;
+
+    let mut state = Level::Unknown;
+    let zero = U24F8::from_num(0);
+
+    // the latest crossings on the way up and down
+    let (mut up_lo, mut up_mid) = (None, None);
+    let (mut dn_hi, mut dn_mid) = (None, None);
+
+    // rising 50% crossings: first, previous, count and interval range
+    let mut first: Option<U24F8> = None;
+    let mut last = zero;
+    let mut rises = 0u32;
+    let (mut tmin, mut tmax) = (U24F8::MAX, zero);
+
+    // high time within complete periods, the falling 50% crossing after the previous rise
+    let mut high = zero;
+    let mut fall_mid: Option<U24F8> = None;
+
+    let (mut rise_sum, mut rise_n) = (zero, 0u32);
+    let (mut fall_sum, mut fall_n) = (zero, 0u32);
+
+    for i in 1..samples.len() {
+        let (a, b) = (samples[i - 1], samples[i]);
+
+        if a < lo && b >= lo {
+            up_lo = Some(cross(i, a, b, lo));
+        }
+
+        if a < mid && b >= mid {
+            up_mid = Some(cross(i, a, b, mid));
+        }
+
+        if a > hi && b <= hi {
+            dn_hi = Some(cross(i, a, b, hi));
+        }
+
+        if a > mid && b <= mid {
+            dn_mid = Some(cross(i, a, b, mid));
+        }
+
+        match state {
+            Level::Low if b >= hi => {
+                state = Level::High;
+
+                if let Some(t) = up_lo {
+                    rise_sum += cross(i, a, b, hi) - t;
+                    rise_n += 1;
+                }
+
+                if let Some(t) = up_mid {
+                    if first.is_some() {
+                        let dt = t - last;
+
+                        tmin = tmin.min(dt);
+                        tmax = tmax.max(dt);
+
+                        if let Some(f) = fall_mid {
+                            high += f - last;
+                        }
+                    } else {
+                        first = Some(t);
+                    }
+
+                    last = t;
+                    rises += 1;
+                    fall_mid = None;
+                }
+            }
+            Level::High if b <= lo => {
+                state = Level::Low;
+
+                if let Some(t) = dn_hi {
+                    fall_sum += cross(i, a, b, lo) - t;
+                    fall_n += 1;
+                }
+
+                if first.is_some() {
+                    fall_mid = dn_mid;
+                }
+            }
+            Level::Unknown if b <= lo => state = Level::Low,
+            Level::Unknown if b >= hi => state = Level::High,
+            _ => {}
+        }
+    }
+
+    if rise_n > 0 {
+        m.rise = Some(rise_sum / rise_n);
+    }
+
+    if fall_n > 0 {
+        m.fall = Some(fall_sum / fall_n);
+    }
+
+    if let Some(f) = first {
+        if rises >= 2 {
+            let span = last - f;
+            let period = span / (rises - 1);
+
+            if tmax - tmin <= period / JITTER {
+                m.period = Some(period);
+                m.duty = Some(high * 100 / span);
+            }
+        }
+    }
+}
+
+// Period as the lag of the first autocorrelation peak: correlation of the signal
+// with its shifted copy has to drop below zero and then the highest value before
+// it drops again is the period. The peak is refined by a parabola through three lags.
+fn autocorrelation(samples: &[u16], mean: i32) -> Option<U24F8> {
+    let n = samples.len();
+    let corr = |lag: usize| -> i64 {
+        samples[..n - lag]
+            .iter()
+            .zip(samples[lag..].iter())
+            .map(|(a, b)| ((*a as i32 - mean) * (*b as i32 - mean)) as i64)
+            .sum::<i64>()
+            / (n - lag) as i64
+    };
+
+    let mut below = false;
+    let mut best: Option<(usize, i64)> = None;
+
+    // at least two periods in the frame
+    for lag in 1..n / 2 {
+        let r = corr(lag);
+
+        if r < 0 {
+            // end of the first positive lobe after the negative one
+            if best.is_some() {
+                break;
+            }
+            below = true;
+        } else if below {
+            match best {
+                Some((_, b)) if r <= b => {}
+                _ => best = Some((lag, r)),
+            }
+        }
+    }
+
+    let (lag, r) = best?;
+
+    if r <= 0 || lag + 1 >= n / 2 {
+        return None;
+    }
+
+    let (r0, r2) = (corr(lag - 1), corr(lag + 1));
+    let den = 2 * (r0 - 2 * r + r2);
+    let mut t = U24F8::from_num(lag);
+
+    // vertex offset in 1/256 samples, within half a sample
+    if den < 0 {
+        let d = ((r0 - r2) * 256 / den).clamp(-128, 128);
+
+        t = if d < 0 {
+            t - U24F8::from_bits((-d) as u32)
+        } else {
+            t + U24F8::from_bits(d as u32)
+        };
+    }
+
+    Some(t)
+}
+
+fn isqrt(v: u64) -> u64 {
+    if v < 2 {
+        return v;
+    }
+
+    // Newton iterations starting above the root
+    let mut x = v;
+    let mut y = x / 2;
+
+    while y < x {
+        x = y;
+        y = (x + v / x) / 2;
+    }
+
+    x
+}
+
+// status line formatting with units
+
+pub struct Hertz(pub U24F8);
+
+impl fmt::Display for Hertz {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let hz = self.0.to_num::<u32>();
+
+        if hz >= 1_000_000 {
+            write!(f, "{}.{:02}MHz", hz / 1_000_000, hz % 1_000_000 / 10_000)
+        } else if hz >= 1000 {
+            write!(f, "{}.{:02}kHz", hz / 1000, hz % 1000 / 10)
+        } else {
+            write!(f, "{}.{}Hz", hz, (self.0.frac().to_bits() * 10) >> 8)
+        }
+    }
+}
+
+pub struct Micros(pub U24F8);
+
+impl fmt::Display for Micros {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let us = self.0.to_num::<u32>();
+
+        if us >= 1_000_000 {
+            write!(f, "{}.{:02}s", us / 1_000_000, us % 1_000_000 / 10_000)
+        } else if us >= 1000 {
+            write!(f, "{}.{:02}ms", us / 1000, us % 1000 / 10)
+        } else {
+            write!(f, "{}.{}us", us, (self.0.frac().to_bits() * 10) >> 8)
+        }
+    }
+}
+
+pub struct Millivolts(pub i32);
+
+impl fmt::Display for Millivolts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let mv = self.0.unsigned_abs();
+
+        if mv >= 1000 {
+            write!(f, "{}{}.{:02}V", sign, mv / 1000, mv % 1000 / 10)
+        } else {
+            write!(f, "{}{}mV", sign, mv)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::f64::consts::PI;
+
+    const LEN: usize = 512;
+
+    fn near(v: Option<U24F8>, expected: f64, tolerance: f64) -> bool {
+        v.map(|v| (v.to_num::<f64>() - expected).abs() <= tolerance) == Some(true)
+    }
+
+    // mean and RMS of the frame relative to the offset in floating point
+    fn reference(samples: &[u16], offset: u16) -> (f64, f64) {
+        let v: Vec<f64> = samples.iter().map(|s| *s as f64 - offset as f64).collect();
+        let n = v.len() as f64;
+
+        (
+            v.iter().sum::<f64>() / n,
+            (v.iter().map(|x| x * x).sum::<f64>() / n).sqrt(),
+        )
+    }
+
+    // integer results are truncated
+    fn levels(m: &Measurements, samples: &[u16], offset: u16) -> bool {
+        let (mean, rms) = reference(samples, offset);
+
+        (m.mean as f64 - mean).abs() < 1.0 && (m.rms as f64 - rms).abs() < 1.0
+    }
+
+    fn frame<F: FnMut(f64) -> f64>(mut f: F) -> Vec<u16> {
+        (0..LEN).map(|i| f(i as f64).round() as u16).collect()
+    }
+
+    // linear ramps between 1000 and 3000 counts: rise, high, fall and low parts of the period
+    fn trapezoid(t: f64, rise: f64, high: f64, fall: f64, period: f64) -> f64 {
+        let t = t % period;
+
+        let x = if t < rise {
+            t / rise
+        } else if t < rise + high {
+            1.0
+        } else if t < rise + high + fall {
+            1.0 - (t - rise - high) / fall
+        } else {
+            0.0
+        };
+
+        1000.0 + 2000.0 * x
+    }
+
+    #[test]
+    fn sine() {
+        let samples = frame(|t| 2048.0 + 1000.0 * (2.0 * PI * t / 40.5).sin());
+        let m = measure(&samples, 2048);
+
+        assert!(near(m.period, 40.5, 0.05), "{:?}", m.period);
+        assert!(near(m.duty, 50.0, 1.0), "{:?}", m.duty);
+        // 10% to 90% of a sine takes asin(0.8) / pi of the period
+        let edge = 40.5 * (0.8f64).asin() / PI;
+        assert!(near(m.rise, edge, 0.3), "{:?}", m.rise);
+        assert!(near(m.fall, edge, 0.3), "{:?}", m.fall);
+        assert!((1998..=2000).contains(&m.vpp()));
+        // frame is not a whole number of periods
+        assert!(levels(&m, &samples, 2048));
+        assert!(near(m.frequency(20_000), 20_000.0 / 40.5, 0.5));
+    }
+
+    #[test]
+    fn square() {
+        // 25% duty at the 50% level: half of the rise, high part and half of the fall
+        let samples = frame(|t| trapezoid(t + 0.3, 10.0, 5.0, 5.0, 50.0));
+        let m = measure(&samples, 1000);
+
+        assert!(near(m.period, 50.0, 0.05), "{:?}", m.period);
+        assert!(near(m.duty, 25.0, 0.5), "{:?}", m.duty);
+        assert!(near(m.rise, 8.0, 0.1), "{:?}", m.rise);
+        assert!(near(m.fall, 4.0, 0.1), "{:?}", m.fall);
+        assert_eq!((m.min, m.max), (1000, 3000));
+        assert!(levels(&m, &samples, 1000));
+        // 2000 * 12.5 / 50 and sqrt(2000^2 * (5 + 15 / 3) / 50) over whole periods
+        assert!((m.mean - 500).abs() < 20, "{}", m.mean);
+        assert!((m.rms as i32 - 894).abs() < 20, "{}", m.rms);
+    }
+
+    #[test]
+    fn triangle() {
+        let samples = frame(|t| trapezoid(t, 32.0, 0.0, 32.0, 64.0) + 1000.0);
+        let m = measure(&samples, 3000);
+
+        assert!(near(m.period, 64.0, 0.05), "{:?}", m.period);
+        assert!(near(m.duty, 50.0, 0.5), "{:?}", m.duty);
+        assert!(near(m.rise, 25.6, 0.1), "{:?}", m.rise);
+        assert!(near(m.fall, 25.6, 0.1), "{:?}", m.fall);
+        assert_eq!(m.vpp(), 2000);
+        assert!(levels(&m, &samples, 3000));
+        // 1000 / sqrt(3), the frame is 8 periods
+        assert!(m.mean.abs() <= 1);
+        assert!((m.rms as i32 - 577).abs() <= 1, "{}", m.rms);
+    }
+
+    #[test]
+    fn noisy_sine_uses_autocorrelation() {
+        // noise peaks set the 10% and 90% levels, the Schmitt trigger misses periods
+        let mut seed = 12345u32;
+        let samples = frame(|t| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let noise = (seed >> 16) as f64 / 65536.0 - 0.5;
+            2048.0 + 500.0 * (2.0 * PI * t / 37.0).sin() + 500.0 * noise
+        });
+        let m = measure(&samples, 2048);
+
+        // duty is measured between crossings only
+        assert_eq!(m.duty, None);
+        assert!(near(m.period, 37.0, 0.5), "{:?}", m.period);
+    }
+
+    #[test]
+    fn flat() {
+        let samples = frame(|t| 2000.0 + (t % 15.0));
+        let m = measure(&samples, 2000);
+
+        assert_eq!(m.vpp(), MIN_RANGE - 2);
+        assert_eq!((m.period, m.duty, m.rise, m.fall), (None, None, None, None));
+        assert_eq!(m.frequency(20_000), None);
+        assert!(levels(&m, &samples, 2000));
+    }
+}
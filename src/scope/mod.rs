@@ -0,0 +1,8 @@
+pub mod display;
+pub mod export;
+pub mod fft;
+pub mod mask;
+pub mod measure;
+pub mod roll;
+pub mod trigger;
+pub mod xy;
@@ -0,0 +1,184 @@
+// Roll mode for slow timebases: samples are appended continuously and the trace
+// moves from right to left, the newest column is at the right edge. The input
+// is sampled much faster than the columns advance, every column keeps min and
+// max of its samples, so short glitches are not lost. Columns are kept in a
+// ring of W entries. The screen is updated by redrawing changed columns as for
+// triggered frames: in landscape the panel hardware scrolling would move the
+// settings and status bands too, they cross the scroll direction.
+
+#[derive(Debug, Clone, Copy)]
+pub struct Roll<const W: usize> {
+    ring: [(u16, u16); W],
+    // next column in the ring and number of completed columns
+    head: usize,
+    len: usize,
+    // column in progress
+    lo: u16,
+    hi: u16,
+    // column ends when acc reaches samples, every sample adds columns
+    acc: u32,
+    samples: u32,
+    columns: u32,
+}
+
+impl<const W: usize> Default for Roll<W> {
+    fn default() -> Roll<W> {
+        Roll::new()
+    }
+}
+
+impl<const W: usize> Roll<W> {
+    pub const fn new() -> Roll<W> {
+        Roll {
+            ring: [(0, 0); W],
+            head: 0,
+            len: 0,
+            lo: u16::MAX,
+            hi: 0,
+            acc: 0,
+            samples: 1,
+            columns: 1,
+        }
+    }
+
+    // columns per samples of the input, the ring is emptied
+    pub fn set_period(&mut self, samples: u32, columns: u32) {
+        self.samples = samples.max(1);
+        self.columns = columns.clamp(1, self.samples);
+        self.reset();
+    }
+
+    pub fn reset(&mut self) {
+        self.head = 0;
+        self.len = 0;
+        self.lo = u16::MAX;
+        self.hi = 0;
+        self.acc = 0;
+    }
+
+    // number of completed columns, up to W
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // append samples, returns the number of completed columns
+    pub fn feed(&mut self, samples: &[u16]) -> usize {
+        let mut n = 0;
+
+        for s in samples {
+            self.lo = self.lo.min(*s);
+            self.hi = self.hi.max(*s);
+            self.acc += self.columns;
+
+            if self.acc >= self.samples {
+                self.acc -= self.samples;
+                self.push();
+                n += 1;
+            }
+        }
+
+        n
+    }
+
+    // completed columns (min, max) from the oldest one
+    pub fn iter(&self) -> impl Iterator<Item = (u16, u16)> + '_ {
+        let first = (self.head + W - self.len) % W;
+
+        (0..self.len).map(move |i| self.ring[(first + i) % W])
+    }
+
+    fn push(&mut self) {
+        self.ring[self.head] = (self.lo, self.hi);
+        self.head = (self.head + 1) % W;
+        self.len = (self.len + 1).min(W);
+
+        self.lo = u16::MAX;
+        self.hi = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn columns<const W: usize>(r: &Roll<W>) -> Vec<(u16, u16)> {
+        r.iter().collect()
+    }
+
+    #[test]
+    fn min_max() {
+        let mut r = Roll::<8>::new();
+        r.set_period(4, 1);
+
+        assert_eq!(r.feed(&[1, 5, 3, 2, 7, 7, 7, 7]), 2);
+        assert_eq!(columns(&r), [(1, 5), (7, 7)]);
+
+        // column in progress continues across feeds
+        assert_eq!(r.feed(&[9, 4]), 0);
+        assert_eq!(r.feed(&[6, 8]), 1);
+        assert_eq!(columns(&r), [(1, 5), (7, 7), (4, 9)]);
+    }
+
+    #[test]
+    fn fractional_columns() {
+        // 2 columns per 3 samples: columns alternate between 2 samples and 1
+        let mut r = Roll::<8>::new();
+        r.set_period(3, 2);
+
+        assert_eq!(r.feed(&[10, 20, 30, 40, 50, 60]), 4);
+        assert_eq!(columns(&r), [(10, 20), (30, 30), (40, 50), (60, 60)]);
+
+        // columns per samples is at most 1
+        r.set_period(2, 5);
+        assert_eq!(r.feed(&[1, 2, 3]), 3);
+
+        r.set_period(0, 0);
+        assert_eq!(r.feed(&[1, 2, 3]), 3);
+    }
+
+    #[test]
+    fn ring_order() {
+        let mut r = Roll::<4>::new();
+        r.set_period(1, 1);
+        assert!(r.is_empty());
+
+        assert_eq!(r.feed(&[0, 1, 2]), 3);
+        assert_eq!(columns(&r), [(0, 0), (1, 1), (2, 2)]);
+
+        // full ring drops the oldest columns, iteration starts from the oldest one left
+        assert_eq!(r.feed(&[3, 4, 5]), 3);
+        assert_eq!(r.len(), 4);
+        assert_eq!(columns(&r), [(2, 2), (3, 3), (4, 4), (5, 5)]);
+
+        r.reset();
+        assert!(r.is_empty());
+        assert_eq!(columns(&r), []);
+    }
+
+    #[test]
+    fn glitches() {
+        let mut r = Roll::<4>::new();
+        r.set_period(100, 1);
+
+        // single sample spikes anywhere in a column, also at its first and last sample
+        for spike in [0, 50, 99].iter() {
+            let mut samples = [2048u16; 100];
+            samples[*spike] = 4095;
+            samples[99 - *spike] = 0;
+
+            assert_eq!(r.feed(&samples), 1);
+        }
+
+        assert_eq!(columns(&r), [(0, 4095); 3]);
+
+        // partial column is dropped by reset
+        r.feed(&[4095]);
+        r.reset();
+        r.feed(&[2048; 100]);
+        assert_eq!(columns(&r), [(2048, 2048)]);
+    }
+}
@@ -0,0 +1,391 @@
+// Software trigger over a continuous stream of samples. The last N samples
+// are kept in a circular buffer, so a triggered frame contains samples taken
+// before the trigger event as well. Trigger event is an edge crossing the
+// level: the signal has to go beyond the level by hysteresis in the opposite
+// direction first, so noise around the level does not trigger on every sample.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Slope {
+    Rising,
+    Falling,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    // untriggered frame when no trigger event for a timeout
+    Auto,
+    // triggered frames only
+    Normal,
+    // stop after the first triggered frame until armed again
+    Single,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Config {
+    pub slope: Slope,
+    pub mode: Mode,
+    pub level: u16,
+    pub hysteresis: u16,
+    // samples after the end of a frame when trigger events are ignored
+    pub holdoff: u32,
+    // part of the frame before the trigger event, percent
+    pub pre: u32,
+    // samples without trigger event before an untriggered frame in auto mode
+    pub timeout: u32,
+}
+
+// completed frame: N samples in the circular buffer, starting from the oldest one
+pub struct Frame<'a, const N: usize> {
+    ring: &'a [u16; N],
+    start: usize,
+    trigger: Option<usize>,
+}
+
+impl<'a, const N: usize> Frame<'a, N> {
+    pub fn len(&self) -> usize {
+        N
+    }
+
+    pub fn is_empty(&self) -> bool {
+        N == 0
+    }
+
+    pub fn get(&self, i: usize) -> u16 {
+        self.ring[(self.start + i) % N]
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = u16> + '_ {
+        (0..N).map(move |i| self.get(i))
+    }
+
+    // position of the trigger event in the frame, None for untriggered frames
+    pub fn trigger(&self) -> Option<usize> {
+        self.trigger
+    }
+
+    pub fn copy_to(&self, buf: &mut [u16]) {
+        for (i, s) in buf.iter_mut().take(N).enumerate() {
+            *s = self.get(i);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Trigger<const N: usize> {
+    cfg: Config,
+    ring: [u16; N],
+    head: usize,
+    // samples since the end of the previous frame
+    count: u32,
+    // samples to collect after the trigger event
+    post: Option<u32>,
+    // signal went beyond the level by hysteresis, ready for the edge
+    ready: bool,
+    stopped: bool,
+}
+
+impl<const N: usize> Trigger<N> {
+    pub fn new(cfg: Config) -> Trigger<N> {
+        Trigger {
+            cfg,
+            ring: [0; N],
+            head: 0,
+            count: 0,
+            post: None,
+            ready: false,
+            stopped: false,
+        }
+    }
+
+    pub fn get_config(&self) -> Config {
+        self.cfg
+    }
+
+    // new settings apply to the next frame, single mode stays stopped
+    pub fn set_config(&mut self, cfg: Config) {
+        self.stopped = self.stopped && cfg.mode == Mode::Single;
+        self.cfg = cfg;
+        self.reset();
+    }
+
+    // forget collected samples, e.g. after sample rate change
+    pub fn reset(&mut self) {
+        self.count = 0;
+        self.post = None;
+        self.ready = false;
+    }
+
+    // wait for the next trigger event in single mode
+    pub fn arm(&mut self) {
+        self.stopped = false;
+        self.reset();
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stopped
+    }
+
+    // samples in the frame before the trigger event, the trigger sample is always in the frame
+    pub fn get_pre(&self) -> usize {
+        let pre = N * self.cfg.pre as usize / 100;
+        if pre < N {
+            pre
+        } else {
+            N - 1
+        }
+    }
+
+    // feed new samples, f is called for every completed frame
+    pub fn feed<F>(&mut self, samples: &[u16], mut f: F)
+    where
+        F: FnMut(&Frame<N>),
+    {
+        let pre = self.get_pre() as u32;
+        let post = N as u32 - pre;
+
+        for s in samples {
+            if self.stopped {
+                return;
+            }
+
+            self.ring[self.head] = *s;
+            self.head = (self.head + 1) % N;
+            self.count = self.count.saturating_add(1);
+
+            let edge = self.edge(*s);
+
+            let left = match self.post {
+                Some(n) => n - 1,
+                None => {
+                    // need full pre-trigger part of new samples and holdoff after the previous frame
+                    if edge && self.count > pre && self.count > self.cfg.holdoff {
+                        post - 1
+                    } else {
+                        if self.cfg.mode == Mode::Auto
+                            && self.count >= N as u32
+                            && self.count >= self.cfg.timeout
+                        {
+                            self.emit(None, &mut f);
+                        }
+                        continue;
+                    }
+                }
+            };
+
+            if left > 0 {
+                self.post = Some(left);
+                continue;
+            }
+
+            self.emit(Some(pre as usize), &mut f);
+
+            if self.cfg.mode == Mode::Single {
+                self.stopped = true;
+            }
+        }
+    }
+
+    fn emit<F>(&mut self, trigger: Option<usize>, f: &mut F)
+    where
+        F: FnMut(&Frame<N>),
+    {
+        f(&Frame {
+            ring: &self.ring,
+            start: self.head,
+            trigger,
+        });
+
+        self.count = 0;
+        self.post = None;
+    }
+
+    // edge detector with hysteresis
+    fn edge(&mut self, s: u16) -> bool {
+        let level = self.cfg.level;
+        let hyst = self.cfg.hysteresis;
+
+        match self.cfg.slope {
+            Slope::Rising => {
+                if s <= level.saturating_sub(hyst) {
+                    self.ready = true;
+                } else if self.ready && s >= level {
+                    self.ready = false;
+                    return true;
+                }
+            }
+            Slope::Falling => {
+                if s >= level.saturating_add(hyst) {
+                    self.ready = true;
+                } else if self.ready && s <= level {
+                    self.ready = false;
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const N: usize = 16;
+
+    fn config(slope: Slope, mode: Mode) -> Config {
+        Config {
+            slope,
+            mode,
+            level: 500,
+            hysteresis: 50,
+            holdoff: 0,
+            pre: 50,
+            timeout: 0,
+        }
+    }
+
+    // completed frames as (trigger, samples)
+    fn run(trigger: &mut Trigger<N>, samples: &[u16]) -> Vec<(Option<usize>, Vec<u16>)> {
+        let mut frames = Vec::new();
+        trigger.feed(samples, |f| frames.push((f.trigger(), f.iter().collect())));
+        frames
+    }
+
+    // square wave, high samples hold their index so the trigger sample can be located
+    fn square(len: usize, period: usize) -> Vec<u16> {
+        (0..len)
+            .map(|i| {
+                if i % period < period / 2 {
+                    0
+                } else {
+                    1000 + i as u16
+                }
+            })
+            .collect()
+    }
+
+    fn triggers(frames: &[(Option<usize>, Vec<u16>)]) -> Vec<u16> {
+        frames.iter().map(|(t, s)| s[t.unwrap()] - 1000).collect()
+    }
+
+    #[test]
+    fn rising_edge_ignores_noise() {
+        let mut t = Trigger::<N>::new(config(Slope::Rising, Mode::Normal));
+        let mut samples = vec![0; 20];
+        // noise around the level does not go below level - hysteresis
+        samples.extend([470, 530].iter().cycle().take(40));
+        samples.extend([0; 10].iter());
+        samples.extend([700; 10].iter());
+
+        let frames = run(&mut t, &samples);
+
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].0, Some(8));
+        assert_eq!(frames[0].1[7], 470);
+        assert_eq!(frames[0].1[8], 530);
+        assert_eq!(frames[1].0, Some(8));
+        assert_eq!(frames[1].1[7], 0);
+        assert_eq!(frames[1].1[8], 700);
+    }
+
+    #[test]
+    fn falling_edge_ignores_noise() {
+        let mut t = Trigger::<N>::new(config(Slope::Falling, Mode::Normal));
+        let mut samples = vec![1000; 20];
+        // noise around the level does not go above level + hysteresis
+        samples.extend([530, 470].iter().cycle().take(40));
+        samples.extend([1000; 10].iter());
+        samples.extend([300; 10].iter());
+
+        let frames = run(&mut t, &samples);
+
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].1[7], 530);
+        assert_eq!(frames[0].1[8], 470);
+        assert_eq!(frames[1].1[7], 1000);
+        assert_eq!(frames[1].1[8], 300);
+    }
+
+    #[test]
+    fn trigger_position() {
+        let samples: Vec<u16> = (0..64).map(|i| if i < 32 { 0 } else { 1000 + i }).collect();
+
+        for (pre, index) in [(0, 0), (50, 8), (100, N - 1)].iter() {
+            let mut t = Trigger::<N>::new(Config {
+                pre: *pre,
+                ..config(Slope::Rising, Mode::Normal)
+            });
+            let frames = run(&mut t, &samples);
+
+            assert_eq!(t.get_pre(), *index);
+            assert_eq!(frames.len(), 1);
+            assert_eq!(frames[0].0, Some(*index));
+            assert_eq!(frames[0].1[*index], 1032);
+            assert!(frames[0].1[..*index].iter().all(|s| *s == 0));
+            assert!(frames[0].1[*index..].iter().all(|s| *s >= 1032));
+        }
+    }
+
+    #[test]
+    fn holdoff_skips_early_edges() {
+        let samples = square(200, 10);
+        let cfg = Config {
+            pre: 0,
+            ..config(Slope::Rising, Mode::Normal)
+        };
+
+        let mut t = Trigger::<N>::new(cfg);
+        assert_eq!(
+            triggers(&run(&mut t, &samples)),
+            [5, 25, 45, 65, 85, 105, 125, 145, 165]
+        );
+
+        // frames end 15 samples after the trigger, edges within 30 samples after are ignored
+        let mut t = Trigger::<N>::new(Config { holdoff: 30, ..cfg });
+        assert_eq!(triggers(&run(&mut t, &samples)), [35, 85, 135]);
+    }
+
+    #[test]
+    fn auto_timeout() {
+        let cfg = Config {
+            timeout: 40,
+            ..config(Slope::Rising, Mode::Auto)
+        };
+        let mut t = Trigger::<N>::new(cfg);
+        let frames = run(&mut t, &[100; 100]);
+
+        assert_eq!(frames.len(), 2);
+        assert!(frames.iter().all(|(t, _)| t.is_none()));
+
+        // edges still trigger in auto mode
+        let mut t = Trigger::<N>::new(cfg);
+        let frames = run(&mut t, &square(100, 10));
+
+        assert!(!frames.is_empty());
+        assert!(frames.iter().all(|(t, _)| t.is_some()));
+    }
+
+    #[test]
+    fn normal_waits_for_edge() {
+        for level in [100, 1000].iter() {
+            let mut t = Trigger::<N>::new(config(Slope::Rising, Mode::Normal));
+            assert!(run(&mut t, &[*level; 100]).is_empty());
+        }
+    }
+
+    #[test]
+    fn single_stops_until_armed() {
+        let samples = square(100, 10);
+        let mut t = Trigger::<N>::new(config(Slope::Rising, Mode::Single));
+
+        assert_eq!(triggers(&run(&mut t, &samples)), [15]);
+        assert!(t.is_stopped());
+        assert!(run(&mut t, &samples).is_empty());
+
+        t.arm();
+        assert!(!t.is_stopped());
+        assert_eq!(triggers(&run(&mut t, &samples)), [15]);
+        assert!(t.is_stopped());
+    }
+}
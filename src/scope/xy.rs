@@ -0,0 +1,359 @@
+use super::display::{Plot, Scale};
+use super::fft::sine;
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use fixed::types::U24F8;
+
+// XY mode: samples of two streams are X and Y coordinates of points in the
+// waveform area, so two sines make a Lissajous figure. Points of the last P
+// frames stay on the screen: before a new frame is drawn, the oldest one is
+// erased except for the pixels shared with newer frames. With a single analog
+// input, X is a reference sine generated for the sample rate.
+//
+// Every frame is kept sorted, so the pixels shared with other frames are found
+// by a single merge pass over all the frames. A bitmap or reference count per
+// pixel would do the same, but 240 x 192 pixels of it do not fit the RAM left
+// next to the capture, FFT and export buffers.
+//
+// Samples are mapped to pixels by display::Scale and clipped by the plot as
+// for the Y-T trace, so both axes follow the volts/div setting and the
+// graticule. gfx::viewport does not apply: it maps continuous world
+// coordinates at a fixed scale, with no divisions, offset or clipping.
+
+// reference table: phase steps per period, linear interpolation in between
+const STEPS: usize = 256;
+const QUARTER: usize = STEPS / 4;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Reference {
+    // amplitude * sin(2 * pi * k / STEPS) for the first quarter of the period
+    table: [i16; QUARTER + 1],
+    // phase increment per sample, full period is 2^32
+    step: u32,
+    offset: u16,
+}
+
+impl Reference {
+    // sine of amplitude around offset in ADC counts
+    pub fn new(amplitude: u16, offset: u16) -> Reference {
+        let mut table = [0; QUARTER + 1];
+
+        for (k, t) in table.iter_mut().enumerate() {
+            let x = core::f64::consts::PI * 2.0 * k as f64 / STEPS as f64;
+            *t = (sine(x) * amplitude as f64 + 0.5) as i16;
+        }
+
+        Reference {
+            table,
+            step: 0,
+            offset,
+        }
+    }
+
+    pub fn set_frequency(&mut self, freq: U24F8, rate: u32) {
+        self.step = (((freq.to_bits() as u64) << 24) / rate.max(1) as u64) as u32;
+    }
+
+    // phase after n samples
+    pub fn advance(&self, phase: u32, n: usize) -> u32 {
+        phase.wrapping_add(self.step.wrapping_mul(n as u32))
+    }
+
+    pub fn sample(&self, phase: u32) -> u16 {
+        let i = (phase >> 24) as usize;
+        let frac = ((phase >> 8) & 0xffff) as i32;
+        let (a, b) = (self.value(i) as i32, self.value((i + 1) % STEPS) as i32);
+
+        (self.offset as i32 + a + (((b - a) * frac) >> 16)) as u16
+    }
+
+    // samples starting from the phase
+    pub fn iter(&self, phase: u32) -> impl Iterator<Item = u16> + '_ {
+        (0..).map(move |n| self.sample(self.advance(phase, n)))
+    }
+
+    fn value(&self, i: usize) -> i16 {
+        let k = i % QUARTER;
+
+        match i / QUARTER {
+            0 => self.table[k],
+            1 => self.table[QUARTER - k],
+            2 => -self.table[k],
+            _ => -self.table[QUARTER - k],
+        }
+    }
+}
+
+// M points per frame at most, P frames on the screen; area up to 256 x 256 pixels
+pub struct Xy<const M: usize, const P: usize> {
+    frames: [[(u8, u8); M]; P],
+    len: [usize; P],
+    oldest: usize,
+}
+
+impl<const M: usize, const P: usize> Default for Xy<M, P> {
+    fn default() -> Xy<M, P> {
+        Xy::new()
+    }
+}
+
+impl<const M: usize, const P: usize> Xy<M, P> {
+    pub fn new() -> Xy<M, P> {
+        Xy {
+            frames: [[(0, 0); M]; P],
+            len: [0; P],
+            oldest: 0,
+        }
+    }
+
+    // replace the oldest frame with the first M points (x, y)
+    pub fn draw<D, I, const W: usize>(
+        &mut self,
+        display: &mut D,
+        plot: &Plot<W>,
+        points: I,
+        xscale: &Scale,
+        yscale: &Scale,
+    ) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Rgb565>,
+        I: Iterator<Item = (u16, u16)>,
+    {
+        let old = self.oldest;
+
+        self.erase(display, plot, old)?;
+
+        let mut n = 0;
+
+        for (x, y) in points.take(M) {
+            let p = (plot.col(x, xscale) as u8, plot.row(y, yscale) as u8);
+
+            // slow signals stay on the same pixel for many samples
+            if n > 0 && self.frames[old][n - 1] == p {
+                continue;
+            }
+
+            plot.draw_point(display, p.0 as i32, p.1 as i32)?;
+            self.frames[old][n] = p;
+            n += 1;
+        }
+
+        self.frames[old][..n].sort_unstable();
+        self.len[old] = n;
+        self.oldest = (old + 1) % P;
+
+        Ok(())
+    }
+
+    // erase all the frames
+    pub fn clear<D, const W: usize>(
+        &mut self,
+        display: &mut D,
+        plot: &Plot<W>,
+    ) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Rgb565>,
+    {
+        for f in 0..P {
+            self.erase(display, plot, f)?;
+        }
+
+        Ok(())
+    }
+
+    // Erase points of the frame f which are not in other frames. All frames are
+    // sorted: every other frame has a cursor that only moves forward while the
+    // points of f are visited in order, so each frame is walked once.
+    fn erase<D, const W: usize>(
+        &mut self,
+        display: &mut D,
+        plot: &Plot<W>,
+        f: usize,
+    ) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Rgb565>,
+    {
+        let mut pos = [0; P];
+
+        for (i, p) in self.frames[f][..self.len[f]].iter().enumerate() {
+            // duplicates are erased once
+            if i > 0 && self.frames[f][i - 1] == *p {
+                continue;
+            }
+
+            let mut shared = false;
+
+            for g in (0..P).filter(|g| *g != f) {
+                let points = &self.frames[g][..self.len[g]];
+
+                while pos[g] < points.len() && points[pos[g]] < *p {
+                    pos[g] += 1;
+                }
+
+                if pos[g] < points.len() && points[pos[g]] == *p {
+                    shared = true;
+                }
+            }
+
+            if !shared {
+                plot.erase_point(display, p.0 as i32, p.1 as i32)?;
+            }
+        }
+
+        self.len[f] = 0;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    // last color of every drawn pixel
+    #[derive(Default)]
+    struct Screen {
+        pixels: HashMap<Point, Rgb565>,
+    }
+
+    impl DrawTarget<Rgb565> for Screen {
+        type Error = core::convert::Infallible;
+
+        fn draw_pixel(&mut self, p: Pixel<Rgb565>) -> Result<(), Self::Error> {
+            self.pixels.insert(p.0, p.1);
+            Ok(())
+        }
+
+        fn size(&self) -> Size {
+            Size::new(320, 240)
+        }
+    }
+
+    impl Screen {
+        fn lit(&self) -> Vec<Point> {
+            let mut lit: Vec<Point> = self
+                .pixels
+                .iter()
+                .filter(|(_, c)| **c == Rgb565::YELLOW)
+                .map(|(p, _)| *p)
+                .collect();
+
+            lit.sort_by_key(|p| (p.x, p.y));
+            lit
+        }
+    }
+
+    // one count per pixel: x is the column, y is the row from the top
+    const XSCALE: Scale = Scale {
+        samples: 1,
+        counts: 24,
+        offset: 120,
+    };
+    const YSCALE: Scale = Scale {
+        samples: 1,
+        counts: 24,
+        offset: 96,
+    };
+
+    fn plot() -> Plot<240> {
+        Plot::new(0, 0, Rgb565::YELLOW, Rgb565::WHITE, Rgb565::BLACK)
+    }
+
+    fn points(p: &[(u16, u16)]) -> impl Iterator<Item = (u16, u16)> + '_ {
+        p.iter().map(|(x, y)| (*x, 192 - *y))
+    }
+
+    fn px(p: &[(i32, i32)]) -> Vec<Point> {
+        let mut v: Vec<Point> = p.iter().map(|(x, y)| Point::new(*x, *y)).collect();
+        v.sort_by_key(|p| (p.x, p.y));
+        v
+    }
+
+    #[test]
+    fn persistence() {
+        let (mut xy, plot) = (Xy::<8, 2>::new(), plot());
+        let mut screen = Screen::default();
+
+        let first = [(10, 10), (11, 10), (12, 10), (10, 10)];
+        let second = [(12, 10), (13, 10), (10, 10)];
+        let third = [(20, 20)];
+
+        xy.draw(&mut screen, &plot, points(&first), &XSCALE, &YSCALE)
+            .unwrap();
+        xy.draw(&mut screen, &plot, points(&second), &XSCALE, &YSCALE)
+            .unwrap();
+        assert_eq!(screen.lit(), px(&[(10, 10), (11, 10), (12, 10), (13, 10)]));
+
+        // the first frame goes away except for the pixels shared with the second one
+        xy.draw(&mut screen, &plot, points(&third), &XSCALE, &YSCALE)
+            .unwrap();
+        assert_eq!(screen.lit(), px(&[(10, 10), (12, 10), (13, 10), (20, 20)]));
+
+        // the background is restored under the erased point
+        assert_eq!(screen.pixels[&Point::new(11, 10)], Rgb565::BLACK);
+
+        xy.clear(&mut screen, &plot).unwrap();
+        assert!(screen.lit().is_empty());
+    }
+
+    #[test]
+    fn repeated_points() {
+        let (mut xy, plot) = (Xy::<4, 3>::new(), plot());
+        let mut screen = Screen::default();
+
+        // slow signal: repeated samples take one point, the frame is cut at M samples
+        let frame = [(5, 5), (5, 5), (5, 5), (6, 5), (7, 5), (8, 5)];
+
+        xy.draw(&mut screen, &plot, points(&frame), &XSCALE, &YSCALE)
+            .unwrap();
+        assert_eq!(xy.len[0], 2);
+        assert_eq!(screen.lit(), px(&[(5, 5), (6, 5)]));
+
+        // shared with a frame which has it twice
+        xy.draw(&mut screen, &plot, points(&[(7, 5)]), &XSCALE, &YSCALE)
+            .unwrap();
+        xy.draw(
+            &mut screen,
+            &plot,
+            points(&[(5, 5), (6, 6), (5, 5)]),
+            &XSCALE,
+            &YSCALE,
+        )
+        .unwrap();
+        xy.draw(&mut screen, &plot, points(&[(9, 9)]), &XSCALE, &YSCALE)
+            .unwrap();
+        assert_eq!(screen.lit(), px(&[(5, 5), (6, 6), (7, 5), (9, 9)]));
+    }
+
+    #[test]
+    fn overwritten_frames() {
+        // points moving around a small square, many frames through the ring
+        let (mut xy, plot) = (Xy::<16, 3>::new(), plot());
+        let mut screen = Screen::default();
+        let mut frames: Vec<Vec<(u16, u16)>> = Vec::new();
+
+        for k in 0..20u16 {
+            let frame: Vec<(u16, u16)> = (0..16)
+                .map(|i| (30 + (i * 7 + k * 3) % 6, 30 + (i * 5 + k) % 4))
+                .collect();
+
+            xy.draw(&mut screen, &plot, points(&frame), &XSCALE, &YSCALE)
+                .unwrap();
+            frames.push(frame);
+
+            // exactly the points of the last 3 frames are lit
+            let mut expected: Vec<(i32, i32)> = frames
+                .iter()
+                .rev()
+                .take(3)
+                .flatten()
+                .map(|(x, y)| (*x as i32, *y as i32))
+                .collect();
+            expected.sort_unstable();
+            expected.dedup();
+
+            assert_eq!(screen.lit(), px(&expected));
+        }
+    }
+}
@@ -0,0 +1,207 @@
+#!/usr/bin/env python3
+#
+# Decode captured frames streamed by the scope over RTT up-channel 1 and
+# convert them to CSV and WAV. Frame format is described in src/scope/export.rs.
+#
+# Input is a recorded byte stream file, '-' for stdin, or host:port of the
+# OpenOCD RTT server (see attach_capture in tools/openocd.cfg). Live stream
+# can be recorded with --raw and decoded again later.
+#
+# Usage: tools/capture.py [--csv out.csv] [--wav out.wav] [--raw out.bin] <input>
+#
+
+import argparse
+import csv
+import socket
+import struct
+import sys
+import wave
+from collections import namedtuple
+
+SYNC = b"\xa5\x5a"
+VERSION = 1
+HEADER = struct.Struct("<2sBBHIHHHHH")
+CRC_LEN = 2
+TRIGGERED = 1 << 0
+
+# the scope sends at most BUF_LEN (src/hw/capture.rs) samples per frame
+MAX_SAMPLES = 512
+
+Frame = namedtuple(
+    "Frame", "sequence rate vdiv vref offset trigger samples"
+)
+
+
+def frame_len(n):
+    return HEADER.size + (3 * n + 1) // 2 + CRC_LEN
+
+
+MAX_LEN = frame_len(MAX_SAMPLES)
+
+
+def crc16(data):
+    """CRC-16/CCITT-FALSE"""
+    crc = 0xFFFF
+    for b in data:
+        crc ^= b << 8
+        for _ in range(8):
+            crc = ((crc << 1) ^ 0x1021) if crc & 0x8000 else crc << 1
+            crc &= 0xFFFF
+    return crc
+
+
+def unpack(data, n):
+    """12-bit samples packed in pairs into 3 bytes"""
+    samples = []
+    for i in range(n):
+        b = data[i // 2 * 3:]
+        if i % 2 == 0:
+            samples.append(b[0] | (b[1] & 0xF) << 8)
+        else:
+            samples.append(b[1] >> 4 | b[2] << 4)
+    return samples
+
+
+class Decoder:
+    """Stream decoder: feed bytes in arbitrary chunks, get complete frames"""
+
+    def __init__(self):
+        self.buf = bytearray()
+        self.errors = 0
+
+    def feed(self, data):
+        self.buf.extend(data)
+        frames = []
+
+        while True:
+            start = self.buf.find(SYNC)
+            if start < 0:
+                # keep a possible first sync byte
+                del self.buf[:max(len(self.buf) - 1, 0)]
+                return frames
+
+            del self.buf[:start]
+
+            if len(self.buf) < HEADER.size:
+                return frames
+
+            _, version, flags, seq, rate, vdiv, vref, offset, trig, n = HEADER.unpack_from(self.buf)
+            size = frame_len(n)
+
+            # a bad length would make the decoder wait for bytes that never come
+            if version != VERSION or size > MAX_LEN:
+                self.errors += 1
+                del self.buf[:1]
+                continue
+
+            if len(self.buf) < size:
+                return frames
+
+            crc = struct.unpack_from("<H", self.buf, size - CRC_LEN)[0]
+            if crc != crc16(self.buf[len(SYNC):size - CRC_LEN]):
+                self.errors += 1
+                del self.buf[:1]
+                continue
+
+            samples = unpack(self.buf[HEADER.size:size - CRC_LEN], n)
+            trigger = trig if flags & TRIGGERED else None
+            frames.append(Frame(seq, rate, vdiv, vref, offset, trigger, samples))
+
+            del self.buf[:size]
+
+
+def chunks(source):
+    if source == "-":
+        stream = sys.stdin.buffer
+    elif ":" in source:
+        host, port = source.rsplit(":", 1)
+        stream = socket.create_connection((host, int(port))).makefile("rb")
+    else:
+        stream = open(source, "rb")
+
+    with stream:
+        while True:
+            data = stream.read1(4096) if hasattr(stream, "read1") else stream.read(4096)
+            if not data:
+                return
+            yield data
+
+
+def volts(frame, counts):
+    # 12-bit ADC
+    return (counts - frame.offset) * frame.vref / 4096 / 1000
+
+
+def main():
+    parser = argparse.ArgumentParser(description="decode scope capture stream")
+    parser.add_argument("input", help="stream file, '-' for stdin or host:port of RTT server")
+    parser.add_argument("--csv", help="write samples to CSV file")
+    parser.add_argument("--wav", help="write samples to 16-bit mono WAV file")
+    parser.add_argument("--raw", help="record received bytes to file")
+    args = parser.parse_args()
+
+    decoder = Decoder()
+    raw = open(args.raw, "wb") if args.raw else None
+    table = None
+    wav = None
+    count = 0
+    lost = 0
+    prev = None
+
+    if args.csv:
+        out = open(args.csv, "w", newline="")
+        table = csv.writer(out)
+        table.writerow(["frame", "sequence", "index", "time", "counts", "volts"])
+
+    try:
+        for data in chunks(args.input):
+            if raw:
+                raw.write(data)
+
+            for frame in decoder.feed(data):
+                if prev is not None:
+                    lost += (frame.sequence - prev - 1) & 0xFFFF
+                prev = frame.sequence
+
+                trigger = frame.trigger if frame.trigger is not None else 0
+
+                if table:
+                    for i, s in enumerate(frame.samples):
+                        t = (i - trigger) / frame.rate
+                        table.writerow([count, frame.sequence, i, "{:.9f}".format(t), s,
+                                        "{:.4f}".format(volts(frame, s))])
+
+                if args.wav:
+                    if wav is None:
+                        wav = wave.open(args.wav, "wb")
+                        wav.setnchannels(1)
+                        wav.setsampwidth(2)
+                        wav.setframerate(frame.rate)
+
+                    if wav.getframerate() == frame.rate:
+                        pcm = [max(-32768, min(32767, (s - frame.offset) * 16)) for s in frame.samples]
+                        wav.writeframes(struct.pack("<{}h".format(len(pcm)), *pcm))
+                    else:
+                        print("frame {}: {} Hz sample rate differs from WAV, skipped"
+                              .format(frame.sequence, frame.rate), file=sys.stderr)
+
+                print("frame {}: {} samples at {} Hz, {} mV/div, trigger {}".format(
+                    frame.sequence, len(frame.samples), frame.rate, frame.vdiv,
+                    frame.trigger if frame.trigger is not None else "-"), file=sys.stderr)
+
+                count += 1
+    except KeyboardInterrupt:
+        pass
+    finally:
+        if raw:
+            raw.close()
+        if table:
+            out.close()
+        if wav:
+            wav.close()
+
+    print("{} frames, {} lost, {} bad".format(count, lost, decoder.errors), file=sys.stderr)
+
+
+if __name__ == "__main__":
+    main()
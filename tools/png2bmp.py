@@ -0,0 +1,34 @@
+#!/usr/bin/env python3
+#
+# Convert PNG sprites into 24-bit BMP images understood by build.rs.
+# Transparent pixels (alpha < 128) are replaced by the magenta color key.
+#
+# Usage: tools/png2bmp.py input.png assets/output.bmp
+#
+
+import sys
+
+from PIL import Image
+
+KEY = (0xFF, 0x00, 0xFF)
+
+
+def main():
+    if len(sys.argv) != 3:
+        print("usage: {} <input.png> <output.bmp>".format(sys.argv[0]))
+        sys.exit(1)
+
+    src = Image.open(sys.argv[1]).convert("RGBA")
+    dst = Image.new("RGB", src.size, KEY)
+
+    for y in range(src.height):
+        for x in range(src.width):
+            r, g, b, a = src.getpixel((x, y))
+            if a >= 128:
+                dst.putpixel((x, y), (r, g, b))
+
+    dst.save(sys.argv[2], "BMP")
+
+
+if __name__ == "__main__":
+    main()
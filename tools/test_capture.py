@@ -0,0 +1,141 @@
+#!/usr/bin/env python3
+#
+# Tests for capture.py against the recorded stream in tools/testdata/capture.bin,
+# the same stream is decoded by the Rust tests in src/scope/export.rs.
+#
+# Usage: python3 tools/test_capture.py
+#
+
+import csv
+import os
+import struct
+import subprocess
+import sys
+import tempfile
+import unittest
+import wave
+
+sys.path.insert(0, os.path.dirname(os.path.abspath(__file__)))
+
+import capture
+
+TOOLS = os.path.dirname(os.path.abspath(__file__))
+CAPTURE = os.path.join(TOOLS, "capture.py")
+RECORDED = os.path.join(TOOLS, "testdata", "capture.bin")
+
+# garbage, frames 7 and 8 at 1000 Hz, frame 9 with bad CRC, frame 11 at 2000 Hz
+FIRST = [2048, 2148, 2248, 1948, 4095]
+SECOND = [0, 1024, 2048, 3072]
+LAST = [100, 200, 300, 400]
+
+
+def encode(seq, samples, n=None):
+    """frame with the samples, n overrides the sample count in the header"""
+    n = len(samples) if n is None else n
+    data = bytearray()
+    for i in range(0, len(samples), 2):
+        a, b = samples[i], samples[i + 1] if i + 1 < len(samples) else 0
+        data += bytes([a & 0xFF, a >> 8 | (b & 0xF) << 4, b >> 4])
+    data = data[:(3 * len(samples) + 1) // 2]
+    header = capture.HEADER.pack(capture.SYNC, capture.VERSION, 0, seq, 1000, 500, 3300, 2048, 0, n)
+    body = header[len(capture.SYNC):] + data
+    return header + data + struct.pack("<H", capture.crc16(body))
+
+
+def recorded():
+    with open(RECORDED, "rb") as f:
+        return f.read()
+
+
+class DecoderTest(unittest.TestCase):
+    def test_crc(self):
+        self.assertEqual(capture.crc16(b"123456789"), 0x29B1)
+
+    def test_recorded(self):
+        decoder = capture.Decoder()
+        frames = decoder.feed(recorded())
+
+        self.assertEqual([f.sequence for f in frames], [7, 8, 11])
+        self.assertEqual([f.samples for f in frames], [FIRST, SECOND, LAST])
+        self.assertEqual([f.trigger for f in frames], [2, None, 0])
+        self.assertEqual(decoder.errors, 1)
+
+    def test_byte_by_byte(self):
+        decoder = capture.Decoder()
+        frames = []
+
+        for b in recorded():
+            frames += decoder.feed(bytes([b]))
+
+        self.assertEqual([f.sequence for f in frames], [7, 8, 11])
+        self.assertEqual(decoder.errors, 1)
+
+    def test_encode(self):
+        frames = capture.Decoder().feed(encode(3, FIRST))
+
+        self.assertEqual([(f.sequence, f.samples) for f in frames], [(3, FIRST)])
+
+    def test_too_long(self):
+        decoder = capture.Decoder()
+
+        # header claims more samples than the scope ever sends: skipped at once,
+        # the next frame is decoded without waiting for the claimed length
+        frames = decoder.feed(encode(1, SECOND, n=0xFFFF) + encode(2, SECOND))
+        self.assertEqual([f.sequence for f in frames], [2])
+        self.assertEqual(decoder.errors, 1)
+
+        # the longest frame is still accepted
+        longest = [i % 4096 for i in range(capture.MAX_SAMPLES)]
+        frames = decoder.feed(encode(3, longest))
+        self.assertEqual([f.samples for f in frames], [longest])
+
+        frames = decoder.feed(encode(4, longest + [0], n=capture.MAX_SAMPLES + 1))
+        self.assertEqual(frames, [])
+        self.assertEqual(decoder.errors, 2)
+
+
+class OutputTest(unittest.TestCase):
+    def setUp(self):
+        self.dir = tempfile.TemporaryDirectory()
+        self.csv = os.path.join(self.dir.name, "out.csv")
+        self.wav = os.path.join(self.dir.name, "out.wav")
+
+        self.result = subprocess.run(
+            [sys.executable, CAPTURE, "--csv", self.csv, "--wav", self.wav, RECORDED],
+            stdout=subprocess.PIPE, stderr=subprocess.PIPE, universal_newlines=True, check=True)
+
+    def tearDown(self):
+        self.dir.cleanup()
+
+    def test_summary(self):
+        self.assertIn("3 frames, 2 lost, 1 bad", self.result.stderr)
+
+    def test_csv(self):
+        with open(self.csv, newline="") as f:
+            rows = list(csv.reader(f))
+
+        self.assertEqual(rows[0], ["frame", "sequence", "index", "time", "counts", "volts"])
+        self.assertEqual(len(rows), 1 + len(FIRST) + len(SECOND) + len(LAST))
+
+        # time relative to the trigger, volts relative to the ground level
+        self.assertEqual(rows[1], ["0", "7", "0", "-0.002000000", "2048", "0.0000"])
+        self.assertEqual(rows[2], ["0", "7", "1", "-0.001000000", "2148", "0.0806"])
+        self.assertEqual(rows[6], ["1", "8", "0", "0.000000000", "0", "-1.6500"])
+        self.assertEqual(rows[-1], ["2", "11", "3", "0.001500000", "400", "-1.3277"])
+
+    def test_wav(self):
+        with wave.open(self.wav, "rb") as w:
+            self.assertEqual(w.getnchannels(), 1)
+            self.assertEqual(w.getsampwidth(), 2)
+            self.assertEqual(w.getframerate(), 1000)
+
+            # frame 11 has another sample rate and is skipped
+            n = w.getnframes()
+            pcm = struct.unpack("<{}h".format(n), w.readframes(n))
+
+        expected = [max(-32768, min(32767, (s - 2048) * 16)) for s in FIRST + SECOND]
+        self.assertEqual(list(pcm), expected)
+
+
+if __name__ == "__main__":
+    unittest.main()